@@ -0,0 +1,188 @@
+//! Instructions per second for synthetic instruction mixes, a sprite heavy loop, a maze demo, a
+//! bouncing balls demo rom and any roms in roms/
+
+use chip8_rs::description;
+use chip8_rs::{asm, Chip};
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Instructions run by each iteration
+const INSTRUCTIONS: u64 = 10_000;
+
+/// Register arithmetic, skips and jumps, with no memory access or drawing
+const ARITHMETIC: &str = "
+        LD V0, 1
+        LD V1, 3
+loop:   ADD V2, V0
+        SUB V3, V1
+        OR V4, V2
+        AND V5, V3
+        XOR V6, V4
+        SHR V7, V2
+        SHL V8, V3
+        ADD V9, 7
+        SE V9, 0x50
+        SNE V2, V3
+        LD VA, V9
+        JP loop
+";
+
+/// Memory reads and writes through I
+const MEMORY: &str = "
+loop:   LD I, buffer
+        LD V0, 200
+        LD B, V0
+        LD V2, [I]
+        LD [I], V5
+        LD F, V1
+        ADD I, V1
+        CALL routine
+        JP loop
+routine:
+        RET
+buffer: DB 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0
+";
+
+/// Full height sprites across the screen, aligned and unaligned
+const SPRITES: &str = "
+        LD V2, 3
+        LD V3, 8
+loop:   LD I, block
+        DRW V0, V1, 15
+        ADD V0, V2
+        DRW V0, V1, 15
+        ADD V0, V3
+        ADD V1, 1
+        JP loop
+block:  DB 0xFF, 0x81, 0xBD, 0xA5, 0xA5, 0xBD, 0x81, 0xFF, 0x81, 0xBD, 0xA5, 0xA5, 0xBD, 0x81, 0xFF
+";
+
+/// Fills the screen with random diagonal lines, starting over when it's full
+const MAZE: &str = "
+start:  CLS
+        LD V0, 0
+        LD V1, 0
+loop:   LD I, left
+        RND V2, 1
+        SE V2, 0
+        LD I, right
+        DRW V0, V1, 4
+        ADD V0, 4
+        SE V0, 64
+        JP loop
+        LD V0, 0
+        ADD V1, 4
+        SE V1, 32
+        JP loop
+        JP start
+left:   DB 0x80, 0x40, 0x20, 0x10
+right:  DB 0x10, 0x20, 0x40, 0x80
+";
+
+/// A demo rom written for the benchmarks, so the roms group always has a rom to run. Four balls
+/// bounce off the edges of the screen, each erased and drawn again every step.
+const BOUNCE: &str = "
+        LD V7, 0
+draw:   LD I, balls
+        ADD I, V7
+        LD V3, [I]
+        LD I, ball
+        DRW V0, V1, 4
+        ADD V7, 4
+        SE V7, 16
+        JP draw
+loop:   LD V7, 0
+next:   LD I, balls
+        ADD I, V7
+        LD V3, [I]
+        LD I, ball
+        DRW V0, V1, 4
+        ADD V0, V2
+        SNE V0, 0
+        LD V2, 1
+        SNE V0, 60
+        LD V2, 0xFF
+        ADD V1, V3
+        SNE V1, 0
+        LD V3, 1
+        SNE V1, 28
+        LD V3, 0xFF
+        DRW V0, V1, 4
+        LD I, balls
+        ADD I, V7
+        LD [I], V3
+        ADD V7, 4
+        SE V7, 16
+        JP next
+        JP loop
+ball:   DB 0x60, 0xF0, 0xF0, 0x60
+balls:  DB 10, 5, 1, 1, 30, 20, 0xFF, 1, 50, 12, 1, 0xFF, 20, 27, 0xFF, 0xFF
+";
+
+fn chip_with(rom: &[u8]) -> Chip {
+    let mut chip = Chip::default();
+    chip.set_seed(0);
+    chip.load_rom_bytes(rom).unwrap();
+    chip
+}
+
+/// Run instructions, starting the rom over if it reaches one the interpreter rejects
+fn run(chip: &mut Chip) {
+    for _ in 0..INSTRUCTIONS {
+        if chip.tick().is_err() {
+            chip.reset();
+        }
+    }
+}
+
+fn programs(c: &mut Criterion) {
+    let mut group = c.benchmark_group("instructions");
+    group.throughput(Throughput::Elements(INSTRUCTIONS));
+    for (name, source) in [
+        ("arithmetic", ARITHMETIC),
+        ("memory", MEMORY),
+        ("sprites", SPRITES),
+        ("maze", MAZE),
+    ] {
+        let mut chip = chip_with(&asm::assemble(source, 0x200).unwrap());
+        group.bench_function(name, |b| b.iter(|| run(&mut chip)));
+    }
+    group.finish();
+}
+
+/// Benchmark the bouncing balls demo and every rom file in roms/. Only the descriptions in roms/
+/// are part of the source, so copy roms there to measure them. A rom which fails to load stops
+/// the benchmark rather than being left out.
+fn roms(c: &mut Criterion) {
+    let mut group = c.benchmark_group("roms");
+    group.throughput(Throughput::Elements(INSTRUCTIONS));
+    let mut chip = chip_with(&asm::assemble(BOUNCE, 0x200).unwrap());
+    group.bench_function("bounce", |b| b.iter(|| run(&mut chip)));
+
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("roms");
+    let mut paths: Vec<PathBuf> = fs::read_dir(&dir)
+        .unwrap_or_else(|error| panic!("{}: {}", dir.display(), error))
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| description::has_rom_extension(path))
+        .collect();
+    paths.sort();
+    if paths.is_empty() {
+        eprintln!(
+            "No roms in {}, so only the bounce demo is measured. Copy roms there to measure them.",
+            dir.display()
+        );
+    }
+    for path in paths {
+        let mut chip = Chip::default();
+        chip.set_seed(0);
+        chip.load_rom(&path.to_string_lossy())
+            .unwrap_or_else(|error| panic!("{}: {}", path.display(), error));
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        group.bench_function(name, |b| b.iter(|| run(&mut chip)));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, programs, roms);
+criterion_main!(benches);
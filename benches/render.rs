@@ -0,0 +1,77 @@
+//! The display path: drawing sprites into the frame buffer, the anti-flicker filters and
+//! rendering frames into window pixels
+
+use chip8_rs::filter::{DisplayFilter, FilterMode, Output};
+use chip8_rs::framebuffer::{Edge, FrameBuffer};
+use chip8_rs::palette::Palette;
+use chip8_rs::render::Renderer;
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+const WIDTH: usize = 64;
+const HEIGHT: usize = 32;
+
+/// A screen with a varied pattern of pixels
+fn screen() -> Vec<u8> {
+    (0..WIDTH * HEIGHT / 8)
+        .map(|index| (index as u8).wrapping_mul(37) ^ 0x5A)
+        .collect()
+}
+
+fn sprites(c: &mut Criterion) {
+    let sprite = [0xFF; 15];
+    let mut group = c.benchmark_group("draw_sprite");
+    group.throughput(Throughput::Elements(1));
+    for (name, x, edge) in [
+        ("aligned", 8, Edge::Wrap),
+        ("unaligned", 11, Edge::Wrap),
+        ("wrapped", 60, Edge::Wrap),
+        ("clipped", 60, Edge::Clip),
+    ] {
+        let mut buffer = FrameBuffer::new(WIDTH, HEIGHT);
+        group.bench_function(name, |b| {
+            b.iter(|| buffer.draw_sprite(black_box(x), black_box(20), &sprite, edge))
+        });
+    }
+    group.finish();
+}
+
+fn filters(c: &mut Criterion) {
+    let frames = [screen(), vec![0; WIDTH * HEIGHT / 8]];
+    let mut group = c.benchmark_group("filter");
+    group.throughput(Throughput::Elements((WIDTH * HEIGHT) as u64));
+    for (name, mode) in [("or", FilterMode::Or), ("decay", FilterMode::Decay(3))] {
+        let mut filter = DisplayFilter::new(mode);
+        let mut frame = 0;
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                frame ^= 1;
+                match filter.apply(&frames[frame]) {
+                    Output::Bits(bits) => bits.len(),
+                    Output::Levels(levels, _) => levels.len(),
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+fn render(c: &mut Criterion) {
+    let (first, second) = (screen(), vec![0xF0; WIDTH * HEIGHT / 8]);
+    let palette = Palette::default();
+    let mut group = c.benchmark_group("render");
+    for scale in [1, 10] {
+        let mut renderer = Renderer::new(WIDTH, HEIGHT, scale);
+        let pixels = renderer.width() * renderer.height();
+        group.throughput(Throughput::Elements(pixels as u64));
+        group.bench_function(format!("scale {}", scale), |b| {
+            b.iter(|| renderer.render(&[&first], &palette).len())
+        });
+        group.bench_function(format!("scale {} two planes", scale), |b| {
+            b.iter(|| renderer.render(&[&first, &second], &palette).len())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, sprites, filters, render);
+criterion_main!(benches);
@@ -0,0 +1,50 @@
+//! Loads corrupted save states. A valid state is overwritten with the input from the offset in
+//! its first two bytes, so every field can be reached without the fuzzer building all of
+//! memory. The third byte picks write protection and VIP timing. A state which loads must run
+//! and save again, with bad instructions coming back as errors rather than panics.
+
+#![no_main]
+
+use chip8_rs::memory::RegionKind;
+use chip8_rs::timing::Timing;
+use chip8_rs::Chip;
+use libfuzzer_sys::fuzz_target;
+
+/// Frames run from a loaded state
+const MAX_FRAMES: u32 = 60;
+
+/// Instructions per frame with fixed timing
+const INSTRUCTIONS_PER_FRAME: u32 = 15;
+
+fuzz_target!(|data: &[u8]| {
+    if data.len() < 3 {
+        return;
+    }
+    let (settings, patch) = data.split_at(3);
+
+    let mut chip = Chip::default();
+    chip.set_seed(0);
+    let mut state = chip.save_state();
+    let offset = usize::from(u16::from_be_bytes([settings[0], settings[1]])) % state.len();
+    let end = (offset + patch.len()).min(state.len());
+    state[offset..end].copy_from_slice(&patch[..end - offset]);
+    if chip.load_state(&state).is_err() {
+        return;
+    }
+
+    let option = |bit: u8| settings[2] & (1 << bit) != 0;
+    chip.memory_mut()
+        .set_write_protected(RegionKind::Font, option(0));
+    chip.memory_mut()
+        .set_write_protected(RegionKind::Interpreter, option(1));
+    if option(2) {
+        chip.set_timing(Timing::Vip);
+    }
+
+    for _ in 0..MAX_FRAMES {
+        if chip.run_frame(INSTRUCTIONS_PER_FRAME).is_err() {
+            break;
+        }
+    }
+    chip.save_state();
+});
@@ -0,0 +1,66 @@
+//! Runs arbitrary roms with arbitrary quirks and keys. The first byte of the input picks the
+//! quirks, the second byte whether the font and interpreter memory are write protected and
+//! whether VIP timing is used, the next two the keys held down and the rest is the rom. Bad
+//! instructions must come back as errors from run_frame, never as panics.
+
+#![no_main]
+
+use chip8_rs::config::Quirks;
+use chip8_rs::memory::RegionKind;
+use chip8_rs::timing::Timing;
+use chip8_rs::{Chip, Key, KeyState};
+use libfuzzer_sys::fuzz_target;
+
+/// Frames run before moving on to the next input
+const MAX_FRAMES: u32 = 600;
+
+/// Instructions per frame with fixed timing
+const INSTRUCTIONS_PER_FRAME: u32 = 15;
+
+fuzz_target!(|data: &[u8]| {
+    if data.len() < 4 {
+        return;
+    }
+    let (settings, rom) = data.split_at(4);
+
+    let mut chip = Chip::default();
+    chip.set_seed(0);
+    let quirk = |bit: u8| settings[0] & (1 << bit) != 0;
+    chip.set_quirks(Quirks {
+        vf_reset: quirk(0),
+        memory_increments_i: quirk(1),
+        shift_uses_vy: quirk(2),
+        jump_uses_vx: quirk(3),
+        clip_sprites: quirk(4),
+        display_wait: quirk(5),
+    });
+    let option = |bit: u8| settings[1] & (1 << bit) != 0;
+    chip.memory_mut()
+        .set_write_protected(RegionKind::Font, option(0));
+    chip.memory_mut()
+        .set_write_protected(RegionKind::Interpreter, option(1));
+    if option(2) {
+        chip.set_timing(Timing::Vip);
+    }
+    let keys = u16::from_be_bytes([settings[2], settings[3]]);
+    chip.update_keys(
+        (0..16)
+            .map(|key| Key {
+                state: if keys & (1 << key) != 0 {
+                    KeyState::Pressed
+                } else {
+                    KeyState::NotPressed
+                },
+            })
+            .collect(),
+    );
+    if chip.load_rom_bytes(rom).is_err() {
+        return;
+    }
+
+    for _ in 0..MAX_FRAMES {
+        if chip.run_frame(INSTRUCTIONS_PER_FRAME).is_err() {
+            break;
+        }
+    }
+});
@@ -0,0 +1,203 @@
+use crate::instruction::{parse_value, Instruction};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::io::{Error, ErrorKind};
+use std::vec::Vec;
+
+/// A line of source, split into its label, mnemonic and operands
+struct Line<'a> {
+    number: usize,
+    label: Option<&'a str>,
+    mnemonic: Option<&'a str>,
+    operands: Vec<&'a str>,
+}
+
+impl<'a> Line<'a> {
+    fn parse(number: usize, text: &'a str) -> Line<'a> {
+        let mut text = text.split(';').next().unwrap_or_default().trim();
+
+        let mut label = None;
+        if let Some(end) = text.find(':') {
+            label = Some(text[..end].trim());
+            text = text[end + 1..].trim();
+        }
+
+        let (mnemonic, operands) = match text.find(char::is_whitespace) {
+            Some(end) => (Some(&text[..end]), text[end..].trim()),
+            None if !text.is_empty() => (Some(text), ""),
+            None => (None, ""),
+        };
+        Line {
+            number,
+            label,
+            mnemonic,
+            operands: operands
+                .split(',')
+                .map(str::trim)
+                .filter(|operand| !operand.is_empty())
+                .collect(),
+        }
+    }
+
+    fn is_data(&self) -> bool {
+        self.mnemonic
+            .is_some_and(|mnemonic| mnemonic.eq_ignore_ascii_case("db"))
+    }
+
+    /// The number of bytes the line assembles to
+    fn size(&self) -> usize {
+        match self.mnemonic {
+            None => 0,
+            Some(_) if self.is_data() => self.operands.len(),
+            Some(_) => 2,
+        }
+    }
+
+    fn error(&self, message: String) -> Error {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("Line {}: {}", self.number, message),
+        )
+    }
+}
+
+/// Assemble source into a rom loaded at `origin`. Each line holds an optional `label:`, then
+/// either an instruction, e.g. `LD V1, 0x0A`, or `DB` followed by a comma separated list of
+/// bytes. Labels may be used anywhere an address or constant is expected. Comments start with
+/// `;`.
+pub fn assemble(source: &str, origin: u16) -> Result<Vec<u8>, Error> {
+    let lines: Vec<Line> = source
+        .lines()
+        .enumerate()
+        .map(|(index, text)| Line::parse(index + 1, text))
+        .collect();
+
+    // First pass, find the address of every label
+    let mut labels = HashMap::new();
+    let mut address = usize::from(origin);
+    for line in lines.iter() {
+        if let Some(label) = line.label {
+            let valid = label.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+            if !valid {
+                return Err(line.error(format!("Invalid label '{}'", label)));
+            }
+            if labels.insert(label.to_string(), address).is_some() {
+                return Err(line.error(format!("Label '{}' is defined twice", label)));
+            }
+        }
+        address += line.size();
+    }
+
+    // Second pass, substitute labels and encode
+    let resolve = |operand: &str| -> String {
+        match labels.get(operand) {
+            Some(address) => address.to_string(),
+            None => operand.to_string(),
+        }
+    };
+
+    let mut rom = Vec::new();
+    for line in lines.iter() {
+        let mnemonic = match line.mnemonic {
+            Some(mnemonic) => mnemonic,
+            None => continue,
+        };
+        let operands: Vec<String> = line.operands.iter().map(|o| resolve(o)).collect();
+
+        if line.is_data() {
+            for operand in operands.iter() {
+                match parse_value(operand) {
+                    Some(byte) if byte <= 0xFF => rom.push(byte as u8),
+                    _ => return Err(line.error(format!("Invalid byte '{}'", operand))),
+                }
+            }
+        } else {
+            let text = format!("{} {}", mnemonic, operands.join(", "));
+            let instruction: Instruction = text
+                .parse()
+                .map_err(|error: Error| line.error(error.to_string()))?;
+            rom.extend_from_slice(&instruction.encode().to_be_bytes());
+        }
+    }
+    Ok(rom)
+}
+
+/// Disassemble a rom loaded at `origin` into a listing, one opcode per line, e.g.
+/// "0200: 6001  LD V0, 0x01". Bytes which aren't a valid instruction are listed as data.
+pub fn disassemble(rom: &[u8], origin: u16) -> String {
+    let mut listing = String::new();
+    for (index, chunk) in rom.chunks(2).enumerate() {
+        let address = usize::from(origin) + index * 2;
+        match chunk {
+            [ms_byte, ls_byte] => {
+                let opcode = u16::from_be_bytes([*ms_byte, *ls_byte]);
+                match Instruction::decode(opcode) {
+                    Some(instruction) => {
+                        writeln!(listing, "{:04X}: {:04X}  {}", address, opcode, instruction)
+                    }
+                    None => writeln!(
+                        listing,
+                        "{:04X}: {:04X}  DB {:#04X}, {:#04X}",
+                        address, opcode, ms_byte, ls_byte
+                    ),
+                }
+            }
+            [byte] => writeln!(listing, "{:04X}: {:02X}    DB {:#04X}", address, byte, byte),
+            _ => unreachable!(),
+        }
+        .unwrap();
+    }
+    listing
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn assemble_instructions() {
+        let rom = assemble("CLS\nLD V1, 0x0A ; comment\n\nDRW V1, V2, 5", 0x200).unwrap();
+        assert_eq!(vec![0x00, 0xE0, 0x61, 0x0A, 0xD1, 0x25], rom);
+    }
+
+    #[test]
+    fn assemble_labels_and_data() {
+        let source = "start: LD I, sprite\n\
+                      loop:\n\
+                      JP loop\n\
+                      sprite: DB 0xF0, $90, 144\n";
+        let rom = assemble(source, 0x200).unwrap();
+        assert_eq!(vec![0xA2, 0x04, 0x12, 0x02, 0xF0, 0x90, 0x90], rom);
+    }
+
+    #[test]
+    fn assemble_errors() {
+        let error = assemble("CLS\nMOV V1, V2", 0x200).unwrap_err();
+        assert!(error.to_string().starts_with("Line 2:"));
+        assert!(assemble("a: CLS\na: CLS", 0x200).is_err());
+        assert!(assemble("1a: CLS", 0x200).is_err());
+        assert!(assemble("DB 256", 0x200).is_err());
+        assert!(assemble("JP missing", 0x200).is_err());
+    }
+
+    #[test]
+    fn disassemble_listing() {
+        let listing = disassemble(&[0x60, 0x01, 0xFF, 0xFF, 0x12], 0x200);
+        assert_eq!(
+            "0200: 6001  LD V0, 0x01\n0202: FFFF  DB 0xFF, 0xFF\n0204: 12    DB 0x12\n",
+            listing
+        );
+    }
+
+    #[test]
+    fn disassemble_assemble_round_trip() {
+        let rom = [0x00, 0xE0, 0xA2, 0x2A, 0x60, 0x0C, 0xD0, 0x15, 0x12, 0x00];
+        let listing = disassemble(&rom, 0x200);
+        let source: String = listing
+            .lines()
+            .map(|line| format!("{}\n", &line[12..]))
+            .collect();
+        assert_eq!(rom.to_vec(), assemble(&source, 0x200).unwrap());
+    }
+}
@@ -0,0 +1,212 @@
+use chip8_rs::description::{self, RomDescription};
+use chip8_rs::rom;
+use chip8_rs::romdb::RomDatabase;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+/// A rom which can be launched, with what's known about it
+#[derive(Clone, Debug, PartialEq)]
+pub struct RomEntry {
+    pub path: PathBuf,
+    pub title: String,
+
+    // Set for a description with no rom next to it, whose path is the description file
+    pub missing: bool,
+}
+
+impl RomEntry {
+    /// Describe a rom from its description file, else the rom database, else its file name
+    pub fn new(path: &Path, database: &RomDatabase) -> RomEntry {
+        let title = match RomDescription::for_rom(path) {
+            Some(description) => description.summary(),
+            None => rom::read_file(path)
+                .ok()
+                .and_then(|bytes| database.lookup(&bytes).cloned())
+                .and_then(|info| match (info.title, info.author) {
+                    (Some(title), Some(author)) => Some(format!("{} by {}", title, author)),
+                    (title, _) => title,
+                })
+                .unwrap_or_else(|| file_name(path)),
+        };
+        RomEntry {
+            path: path.to_path_buf(),
+            title,
+            missing: false,
+        }
+    }
+
+    /// Describe a description file whose rom isn't next to it
+    pub fn missing(description: &Path) -> Option<RomEntry> {
+        let title = RomDescription::load_file(description).ok()?.summary();
+        Some(RomEntry {
+            path: description.to_path_buf(),
+            title: format!("{} (rom missing)", title),
+            missing: true,
+        })
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+/// Find the roms in a directory, by extension, sorted by title. Descriptions without a rom are
+/// listed too, marked missing, so a directory of descriptions shows what to add.
+pub fn scan(dir: &Path, database: &RomDatabase) -> Result<Vec<RomEntry>, io::Error> {
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if !path.is_file() {
+            continue;
+        }
+        if description::has_rom_extension(&path) {
+            entries.push(RomEntry::new(&path, database));
+        } else if is_description(&path) && RomDescription::rom_for(&path).is_none() {
+            entries.extend(RomEntry::missing(&path));
+        }
+    }
+    entries.sort_by_key(|entry| entry.title.to_lowercase());
+    Ok(entries)
+}
+
+fn is_description(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("txt"))
+}
+
+/// The recently run roms which still exist, followed by the roms in the directory which aren't
+/// recent
+pub fn with_recent(
+    recent: &[PathBuf],
+    found: Vec<RomEntry>,
+    database: &RomDatabase,
+) -> Vec<RomEntry> {
+    let mut entries: Vec<RomEntry> = recent
+        .iter()
+        .filter(|path| path.is_file())
+        .map(|path| RomEntry::new(path, database))
+        .collect();
+    let is_recent = |path: &Path| {
+        let path = fs::canonicalize(path).ok();
+        recent
+            .iter()
+            .any(|recent| fs::canonicalize(recent).ok() == path)
+    };
+    entries.extend(found.into_iter().filter(|entry| !is_recent(&entry.path)));
+    entries
+}
+
+/// Show a numbered menu of roms and read the user's choice. Returns None if the user quits.
+/// Roms marked missing can't be chosen.
+pub fn choose<R: BufRead, W: Write>(
+    entries: &[RomEntry],
+    input: &mut R,
+    output: &mut W,
+) -> Result<Option<usize>, io::Error> {
+    for (number, entry) in entries.iter().enumerate() {
+        writeln!(output, "{:>3}. {}", number + 1, entry.title)?;
+    }
+
+    loop {
+        write!(output, "Choose a rom (1-{}, q to quit): ", entries.len())?;
+        output.flush()?;
+
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim();
+        if line.eq_ignore_ascii_case("q") {
+            return Ok(None);
+        }
+        match line.parse::<usize>() {
+            Ok(number) if (1..=entries.len()).contains(&number) => {
+                let entry = &entries[number - 1];
+                if !entry.missing {
+                    return Ok(Some(number - 1));
+                }
+                writeln!(
+                    output,
+                    "Put the rom next to {} to run it",
+                    entry.path.display()
+                )?;
+            }
+            _ => writeln!(output, "'{}' isn't a rom number", line)?,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn entry(title: &str) -> RomEntry {
+        RomEntry {
+            path: PathBuf::from(format!("{}.ch8", title)),
+            title: title.to_string(),
+            missing: false,
+        }
+    }
+
+    #[test]
+    fn scan_directory() {
+        let dir = env::temp_dir().join("chip8_rs_browse");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("zero.ch8"), [0x00, 0xE0]).unwrap();
+        fs::write(dir.join("Maze [David Winter, 199x].ch8"), [0x12, 0x00]).unwrap();
+        fs::write(dir.join("Maze [David Winter, 199x].txt"), "Draws a maze").unwrap();
+        fs::write(dir.join("notes.md"), "Not a rom").unwrap();
+        fs::write(dir.join("Pong [Paul Vervalin, 1990].txt"), "No rom").unwrap();
+
+        let database = RomDatabase::default();
+        let entries = scan(&dir, &database).unwrap();
+        let titles: Vec<&str> = entries.iter().map(|entry| entry.title.as_str()).collect();
+        assert_eq!(
+            vec![
+                "Maze by David Winter, 199x",
+                "Pong by Paul Vervalin, 1990 (rom missing)",
+                "zero.ch8"
+            ],
+            titles
+        );
+        assert!(entries[1].missing);
+        let entries: Vec<RomEntry> = entries.into_iter().filter(|entry| !entry.missing).collect();
+
+        // Recent roms come first and aren't listed twice
+        let recent = vec![dir.join("zero.ch8"), dir.join("missing.ch8")];
+        let entries = with_recent(&recent, entries, &database);
+        let titles: Vec<&str> = entries.iter().map(|entry| entry.title.as_str()).collect();
+        assert_eq!(vec!["zero.ch8", "Maze by David Winter, 199x"], titles);
+    }
+
+    #[test]
+    fn choose_from_menu() {
+        let missing = RomEntry {
+            missing: true,
+            ..entry("Tetris")
+        };
+        let entries = vec![entry("Maze"), entry("Pong"), missing];
+        let mut output = Vec::new();
+        let choice = choose(&entries, &mut "4\nx\n3\n2\n".as_bytes(), &mut output).unwrap();
+        assert_eq!(Some(1), choice);
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("  1. Maze\n  2. Pong\n  3. Tetris\n"));
+        assert!(output.contains("'4' isn't a rom number"));
+        assert!(output.contains("Put the rom next to Tetris.ch8 to run it"));
+
+        assert_eq!(
+            None,
+            choose(&entries, &mut "q\n".as_bytes(), &mut Vec::new()).unwrap()
+        );
+        assert_eq!(
+            None,
+            choose(&entries, &mut "".as_bytes(), &mut Vec::new()).unwrap()
+        );
+    }
+}
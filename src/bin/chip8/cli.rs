@@ -0,0 +1,301 @@
+use chip8_rs::config::{Platform, Quirks};
+use chip8_rs::filter::FilterMode;
+use chip8_rs::instruction::parse_value;
+use chip8_rs::palette::Palette;
+use chip8_rs::timing::Timing;
+use clap::{Args, Parser, Subcommand};
+use std::path::PathBuf;
+
+/// The most instructions per frame --speed and the speed setting accept, and the largest
+/// --fast-forward multiple
+pub const MAX_SPEED: u32 = 100_000;
+
+/// A chip-8 emulator, debugger, assembler and disassembler
+#[derive(Debug, Parser)]
+#[command(name = "chip8", version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+
+    /// The settings file [default: $XDG_CONFIG_HOME/chip8-rs/config.toml]
+    #[arg(long, global = true)]
+    pub config: Option<PathBuf>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run a rom in a window, or headless with --frames
+    Run(RunArgs),
+
+    /// Run a rom in the interactive debugger
+    Debug(RunArgs),
+
+    /// Run a rom headless and print the final screen, optionally checking its hash
+    Test(TestArgs),
+
+    /// Print a listing of a rom
+    Disasm {
+        /// The rom to disassemble
+        rom: PathBuf,
+
+        /// The address the rom is loaded at
+        #[arg(long, default_value = "0x200", value_parser = parse_address)]
+        origin: u16,
+    },
+
+    /// Assemble a source file into a rom
+    Asm {
+        /// The source file to assemble
+        source: PathBuf,
+
+        /// Where to write the rom
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// The address the rom will be loaded at
+        #[arg(long, default_value = "0x200", value_parser = parse_address)]
+        origin: u16,
+    },
+
+    /// List the roms in a directory and recently run roms, and run the one chosen
+    Browse {
+        /// The directory to list
+        #[arg(default_value = "roms")]
+        dir: PathBuf,
+
+        /// A rom database file layered over the builtin database
+        #[arg(long)]
+        romdb: Option<PathBuf>,
+    },
+
+    /// Print what is known about a rom
+    Info {
+        /// The rom to describe
+        rom: PathBuf,
+
+        /// A rom database file layered over the builtin database
+        #[arg(long)]
+        romdb: Option<PathBuf>,
+    },
+
+    /// Show or change the saved defaults for run options
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigAction {
+    /// Print the settings file
+    Show,
+
+    /// Change a setting: scale, palette, filter, speed, timing, quirks or keymap
+    Set { key: String, value: String },
+
+    /// Remove a setting, so the built in default is used
+    Unset { key: String },
+}
+
+/// Options shared by every command which runs a rom
+#[derive(Debug, Default, Args)]
+pub struct RunArgs {
+    /// The rom to run. Gzip files and zip archives holding a single rom are supported.
+    pub rom: PathBuf,
+
+    /// Window pixels per chip-8 pixel [default: 10]
+    #[arg(long, value_parser = clap::value_parser!(u8).range(1..=64))]
+    pub scale: Option<u8>,
+
+    /// Instructions executed per frame [default: from the rom database, or 1]
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..=MAX_SPEED as i64))]
+    pub speed: Option<u32>,
+
+    /// fixed runs --speed instructions each frame, vip runs as many as the COSMAC VIP would,
+    /// using each instruction's cost in machine cycles [default: fixed]
+    #[arg(long)]
+    pub timing: Option<Timing>,
+
+    /// The machine to emulate: chip8, vip2k, eti660, hybrid-vip or xochip. Disables
+    /// configuration from the rom database.
+    #[arg(long)]
+    pub platform: Option<Platform>,
+
+    /// Quirk preset: default, chip8, schip or xochip. Overrides the rom database.
+    #[arg(long)]
+    pub quirks: Option<Quirks>,
+
+    /// A theme: classic, green, amber, lcd or high-contrast. Or 2, 4 or 16 RRGGBB colors in pixel
+    /// value order, background first, e.g. 101010:EEEEEE
+    /// [default: from the rom database, or classic]
+    #[arg(long)]
+    pub palette: Option<Palette>,
+
+    /// Blend frames to hide flicker: off, or (the last two frames), decay or decay:<frames>
+    /// [default: from the rom database, or off]
+    #[arg(long)]
+    pub filter: Option<FilterMode>,
+
+    /// Seed for the random number generator, for reproducible runs
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// A keymap file, see the keymap module for the format. Keys remapped with F1 are saved here.
+    #[arg(long)]
+    pub keymap: Option<PathBuf>,
+
+    /// A rom database file layered over the builtin database
+    #[arg(long)]
+    pub romdb: Option<PathBuf>,
+
+    /// Speed while Tab is held, as a multiple of normal speed. 0 runs as fast as possible.
+    /// [default: 4]
+    #[arg(long, value_parser = clap::value_parser!(u32).range(0..=MAX_SPEED as i64))]
+    pub fast_forward: Option<u32>,
+
+    /// Reload and restart the rom whenever its file changes, e.g. after assembling it again
+    #[arg(long)]
+    pub watch: bool,
+
+    /// Run without a window for this many frames, print the screen and exit
+    #[arg(long)]
+    pub frames: Option<u32>,
+
+    /// A save state file. It's loaded on start if it exists, F5 saves and F9 loads. Headless
+    /// runs save it on exit.
+    #[arg(long)]
+    pub state: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct TestArgs {
+    #[command(flatten)]
+    pub run: RunArgs,
+
+    /// The expected SHA-1 of the final screen buffer. The exit code is 1 if it doesn't match.
+    #[arg(long)]
+    pub expect: Option<String>,
+
+    /// Write an lcov report of which words of the rom were executed, read or written. Line
+    /// numbers match the lines of --listing.
+    #[arg(long)]
+    pub coverage: Option<PathBuf>,
+
+    /// Write a listing of the rom with each word marked as executed (X), read (R) or written (W)
+    #[arg(long)]
+    pub listing: Option<PathBuf>,
+}
+
+/// Parse an address, either decimal or 0x prefixed hex
+fn parse_address(text: &str) -> Result<u16, String> {
+    match parse_value(text) {
+        Some(address) if address <= 0x0FFF => Ok(address),
+        _ => Err(format!("'{}' is not an address between 0 and 0xFFF", text)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn cli_is_valid() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn parse_run() {
+        let cli = Cli::try_parse_from([
+            "chip8",
+            "run",
+            "maze.ch8",
+            "--scale",
+            "4",
+            "--quirks",
+            "schip",
+            "--palette",
+            "000000:#FFFFFF",
+            "--filter",
+            "decay:5",
+            "--seed",
+            "7",
+            "--timing",
+            "vip",
+            "--watch",
+        ])
+        .unwrap();
+        match cli.command {
+            Command::Run(args) => {
+                assert_eq!(Some(4), args.scale);
+                assert_eq!(Some("schip".parse().unwrap()), args.quirks);
+                assert_eq!(
+                    Some(Palette::new(vec![0x000000, 0xFFFFFF]).unwrap()),
+                    args.palette
+                );
+                assert_eq!(Some(FilterMode::Decay(5)), args.filter);
+                assert_eq!(Some(7), args.seed);
+                assert_eq!(Some(Timing::Vip), args.timing);
+                assert!(args.watch);
+            }
+            command => panic!("Parsed the wrong command {:?}", command),
+        }
+    }
+
+    #[test]
+    fn fast_forward_range() {
+        let parse =
+            |value| Cli::try_parse_from(["chip8", "run", "maze.ch8", "--fast-forward", value]);
+        assert!(parse("0").is_ok());
+        assert!(parse("100000").is_ok());
+        assert!(parse("100001").is_err());
+        assert!(parse("4000000000").is_err());
+    }
+
+    #[test]
+    fn parse_test() {
+        let cli = Cli::try_parse_from([
+            "chip8",
+            "test",
+            "maze.ch8",
+            "--coverage",
+            "maze.lcov",
+            "--listing",
+            "maze.txt",
+        ])
+        .unwrap();
+        match cli.command {
+            Command::Test(args) => {
+                assert_eq!(PathBuf::from("maze.ch8"), args.run.rom);
+                assert_eq!(None, args.expect);
+                assert_eq!(Some(PathBuf::from("maze.lcov")), args.coverage);
+                assert_eq!(Some(PathBuf::from("maze.txt")), args.listing);
+            }
+            command => panic!("Parsed the wrong command {:?}", command),
+        }
+    }
+
+    #[test]
+    fn parse_browse() {
+        match Cli::try_parse_from(["chip8", "browse"]).unwrap().command {
+            Command::Browse { dir, romdb } => {
+                assert_eq!(PathBuf::from("roms"), dir);
+                assert_eq!(None, romdb);
+            }
+            command => panic!("Parsed the wrong command {:?}", command),
+        }
+    }
+
+    #[test]
+    fn validation_errors() {
+        assert!(Cli::try_parse_from(["chip8", "run", "maze.ch8", "--scale", "0"]).is_err());
+        assert!(Cli::try_parse_from(["chip8", "run", "maze.ch8", "--platform", "nes"]).is_err());
+        assert!(Cli::try_parse_from(["chip8", "run", "maze.ch8", "--palette", "red"]).is_err());
+        assert!(Cli::try_parse_from(["chip8", "run", "maze.ch8", "--filter", "blur"]).is_err());
+        assert!(
+            Cli::try_parse_from(["chip8", "disasm", "maze.ch8", "--origin", "0x1000"]).is_err()
+        );
+        assert!(Cli::try_parse_from(["chip8", "asm", "maze.s"]).is_err());
+        assert!(Cli::try_parse_from(["chip8", "maze.ch8"]).is_err());
+    }
+}
@@ -0,0 +1,263 @@
+use minifb::Key as HostKey;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Pauses or resumes emulation
+pub const PAUSE_KEY: HostKey = HostKey::F2;
+
+/// Runs a single frame while paused
+pub const FRAME_ADVANCE_KEY: HostKey = HostKey::F3;
+
+/// Turns slow motion on or off
+pub const SLOW_MOTION_KEY: HostKey = HostKey::F4;
+
+/// Runs faster while held down
+pub const FAST_FORWARD_KEY: HostKey = HostKey::Tab;
+
+/// Fast forward speed when none is configured
+pub const DEFAULT_FAST_FORWARD: u32 = 4;
+
+/// Slow motion runs one frame for every this many
+const SLOW_MOTION_DIVISOR: u32 = 4;
+
+/// How fast emulation runs
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    Normal,
+    Paused,
+
+    // Most frames per scheduled frame, 0 for as many as fit in the frame's time
+    FastForward(u32),
+    SlowMotion,
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Mode::Normal => write!(f, "Normal"),
+            Mode::Paused => write!(f, "Paused"),
+            Mode::FastForward(0) => write!(f, "Fast forward"),
+            Mode::FastForward(speed) => write!(f, "Fast forward {}x", speed),
+            Mode::SlowMotion => write!(f, "Slow motion 1/{}x", SLOW_MOTION_DIVISOR),
+        }
+    }
+}
+
+/// The frames to run in one step of the run loop
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Frames {
+    Count(u32),
+
+    // Run up to `most` frames, stopping early once the time is reached, for fast forward
+    Until { deadline: Instant, most: u32 },
+}
+
+impl Frames {
+    /// Call `frame` once per frame to run and return how many frames ran, stopping at the first
+    /// error. At least one frame runs before a deadline is checked, so fast forward always makes
+    /// progress.
+    pub fn run<E, F: FnMut() -> Result<(), E>>(self, mut frame: F) -> Result<u32, E> {
+        let mut frames = 0;
+        match self {
+            Frames::Count(count) => {
+                for _ in 0..count {
+                    frame()?;
+                }
+                frames = count;
+            }
+            Frames::Until { deadline, most } => {
+                while frames < most && (frames == 0 || Instant::now() < deadline) {
+                    frame()?;
+                    frames += 1;
+                }
+            }
+        }
+        Ok(frames)
+    }
+}
+
+/// Speed controls driven by hotkeys
+#[derive(Debug)]
+pub struct Controls {
+    fast_forward: u32,
+    frame_duration: Duration,
+    paused: bool,
+    slow_motion: bool,
+    fast_forwarding: bool,
+    advance: bool,
+
+    // Scheduled frames not yet run in slow motion
+    slow_frames: u32,
+}
+
+impl Controls {
+    /// Create controls for frames of the given length. Fast forward runs that many times faster,
+    /// or as fast as possible if it's 0.
+    pub fn new(fast_forward: u32, frame_duration: Duration) -> Controls {
+        Controls {
+            fast_forward,
+            frame_duration,
+            paused: false,
+            slow_motion: false,
+            fast_forwarding: false,
+            advance: false,
+            slow_frames: 0,
+        }
+    }
+
+    pub fn mode(&self) -> Mode {
+        if self.paused {
+            Mode::Paused
+        } else if self.fast_forwarding {
+            Mode::FastForward(self.fast_forward)
+        } else if self.slow_motion {
+            Mode::SlowMotion
+        } else {
+            Mode::Normal
+        }
+    }
+
+    /// Handle the hotkeys. `pressed` tells whether a key was pressed since the last update and
+    /// `fast_forward_held` whether the fast forward key is down.
+    pub fn update<F: Fn(HostKey) -> bool>(&mut self, pressed: F, fast_forward_held: bool) {
+        if pressed(PAUSE_KEY) {
+            self.paused = !self.paused;
+        }
+        if pressed(SLOW_MOTION_KEY) {
+            self.slow_motion = !self.slow_motion;
+            self.slow_frames = 0;
+        }
+        if self.paused && pressed(FRAME_ADVANCE_KEY) {
+            self.advance = true;
+        }
+        self.fast_forwarding = fast_forward_held;
+    }
+
+    /// The frames to run when the scheduler has frames due
+    pub fn frames(&mut self, due: u32) -> Frames {
+        match self.mode() {
+            Mode::Normal => Frames::Count(due),
+            Mode::Paused => Frames::Count(u32::from(std::mem::take(&mut self.advance))),
+            // Fast forward never runs past the next frame's deadline, so the window keeps
+            // polling input however large the multiple is
+            Mode::FastForward(speed) => Frames::Until {
+                deadline: Instant::now() + self.frame_duration,
+                most: match speed {
+                    0 => u32::MAX,
+                    speed => due.saturating_mul(speed),
+                },
+            },
+            Mode::SlowMotion => {
+                self.slow_frames += due;
+                let frames = self.slow_frames / SLOW_MOTION_DIVISOR;
+                self.slow_frames %= SLOW_MOTION_DIVISOR;
+                Frames::Count(frames)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn controls() -> Controls {
+        Controls::new(DEFAULT_FAST_FORWARD, Duration::from_millis(16))
+    }
+
+    fn press(controls: &mut Controls, key: HostKey) {
+        controls.update(|k| k == key, false);
+    }
+
+    #[test]
+    fn pause_and_advance() {
+        let mut controls = controls();
+        assert_eq!(Frames::Count(1), controls.frames(1));
+
+        press(&mut controls, PAUSE_KEY);
+        assert_eq!(Mode::Paused, controls.mode());
+        assert_eq!(Frames::Count(0), controls.frames(1));
+
+        press(&mut controls, FRAME_ADVANCE_KEY);
+        assert_eq!(Frames::Count(1), controls.frames(3));
+        assert_eq!(Frames::Count(0), controls.frames(1));
+
+        press(&mut controls, PAUSE_KEY);
+        assert_eq!(Mode::Normal, controls.mode());
+        assert_eq!(Frames::Count(2), controls.frames(2));
+    }
+
+    #[test]
+    fn advance_only_while_paused() {
+        let mut controls = controls();
+        press(&mut controls, FRAME_ADVANCE_KEY);
+        press(&mut controls, PAUSE_KEY);
+        assert_eq!(Frames::Count(0), controls.frames(1));
+    }
+
+    #[test]
+    fn fast_forward_while_held() {
+        let mut controls = controls();
+        controls.update(|_| false, true);
+        assert_eq!(Mode::FastForward(4), controls.mode());
+        assert_eq!(Ok(8), controls.frames(2).run(|| Ok::<(), ()>(())));
+
+        controls.update(|_| false, false);
+        assert_eq!(Frames::Count(2), controls.frames(2));
+
+        let mut uncapped = Controls::new(0, Duration::from_millis(16));
+        uncapped.update(|_| false, true);
+        assert_eq!("Fast forward", uncapped.mode().to_string());
+        match uncapped.frames(1) {
+            Frames::Until { deadline, most } => {
+                assert!(deadline > Instant::now());
+                assert_eq!(u32::MAX, most);
+            }
+            frames => panic!("Expected a deadline, got {:?}", frames),
+        }
+    }
+
+    #[test]
+    fn fast_forward_stops_at_the_deadline() {
+        let frame_duration = Duration::from_millis(10);
+        let mut fastest = Controls::new(100_000, frame_duration);
+        fastest.update(|_| false, true);
+
+        let start = Instant::now();
+        let sleep = || {
+            std::thread::sleep(Duration::from_millis(1));
+            Ok::<(), ()>(())
+        };
+        let frames = fastest.frames(50_000).run(sleep).unwrap();
+        assert!((1..1_000).contains(&frames), "ran {} frames", frames);
+        assert!(start.elapsed() < frame_duration * 10);
+
+        // A frame runs even if the deadline has already passed
+        let late = Frames::Until {
+            deadline: Instant::now(),
+            most: u32::MAX,
+        };
+        assert_eq!(Ok(1), late.run(sleep));
+
+        // Frames stop at the first error
+        let mut count = 0;
+        let failing = Frames::Count(3).run(|| {
+            count += 1;
+            Err(count)
+        });
+        assert_eq!(Err(1), failing);
+    }
+
+    #[test]
+    fn slow_motion() {
+        let mut controls = controls();
+        press(&mut controls, SLOW_MOTION_KEY);
+        assert_eq!("Slow motion 1/4x", controls.mode().to_string());
+        assert_eq!(Frames::Count(0), controls.frames(3));
+        assert_eq!(Frames::Count(1), controls.frames(1));
+        assert_eq!(Frames::Count(2), controls.frames(8));
+
+        press(&mut controls, SLOW_MOTION_KEY);
+        assert_eq!(Mode::Normal, controls.mode());
+    }
+}
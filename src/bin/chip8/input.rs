@@ -0,0 +1,76 @@
+use chip8_rs::keymap::{Keymap, KeymapConfig, Remapper};
+use std::fs;
+use std::path::PathBuf;
+
+/// Host key which starts remapping the chip8 keys
+pub const REMAP_KEY: minifb::Key = minifb::Key::F1;
+
+/// Host key which cancels remapping, or quits when not remapping
+pub const QUIT_KEY: minifb::Key = minifb::Key::Escape;
+
+/// Keyboard state carried between ticks
+pub struct Input {
+    pub keymap: Keymap,
+    pub remapper: Option<Remapper>,
+
+    // The loaded keymap file, which a remapped keymap is saved into as an override for this rom
+    pub keymaps: KeymapConfig,
+    pub keymap_file: Option<PathBuf>,
+    pub rom: String,
+    pub title: String,
+}
+
+impl Input {
+    /// Prompt for the next chip8 key in the window title and on the terminal
+    fn prompt(&self, display: &mut chip8_rs::RomWindow) {
+        match self.remapper.as_ref().and_then(|r| r.waiting_for()) {
+            Some(chip_key) => {
+                let prompt = format!("Press the key for chip8 key {:X}", chip_key);
+                println!("{}", prompt);
+                display.window.set_title(&prompt);
+            }
+            None => display.window.set_title(&self.title),
+        }
+    }
+
+    /// Start remapping every chip8 key
+    pub fn start_remap(&mut self, display: &mut chip8_rs::RomWindow, held: &[minifb::Key]) {
+        println!("Remapping keys, press Escape to cancel");
+        self.remapper = Some(Remapper::new(held));
+        self.prompt(display);
+    }
+
+    /// Stop remapping and keep the current keymap
+    pub fn cancel_remap(&mut self, display: &mut chip8_rs::RomWindow) {
+        println!("Remapping cancelled, the keymap is unchanged");
+        self.remapper = None;
+        self.prompt(display);
+    }
+
+    /// Feed held keys to the remapper, switching to the new keymap once every key is bound
+    pub fn update_remap(&mut self, display: &mut chip8_rs::RomWindow, held: &[minifb::Key]) {
+        let remapper = match self.remapper.as_mut() {
+            Some(remapper) => remapper,
+            None => return,
+        };
+        if !remapper.update(held) {
+            return;
+        }
+
+        if remapper.waiting_for().is_none() {
+            if let Some(keymap) = self.remapper.take().and_then(Remapper::finish) {
+                self.keymap = keymap;
+            }
+            self.keymaps.set_rom(&self.rom, self.keymap.clone());
+            if let Some(file) = &self.keymap_file {
+                match fs::write(file, self.keymaps.to_toml()) {
+                    Ok(_) => println!("Saved keymap to {}", file.display()),
+                    Err(error) => {
+                        eprintln!("Unable to save keymap to {}: {}", file.display(), error)
+                    }
+                }
+            }
+        }
+        self.prompt(display);
+    }
+}
@@ -0,0 +1,655 @@
+mod browser;
+mod cli;
+mod controls;
+mod input;
+mod scheduler;
+mod settings;
+mod watch;
+
+use chip8_rs::config::{ChipConfig, Platform};
+use chip8_rs::description::RomDescription;
+use chip8_rs::filter::FilterMode;
+use chip8_rs::keymap::KeymapConfig;
+use chip8_rs::palette::Palette;
+use chip8_rs::rom;
+use chip8_rs::romdb::{sha1_hex, RomDatabase};
+use chip8_rs::{asm, Chip};
+use clap::Parser;
+use cli::{Cli, ConfigAction, RunArgs, TestArgs};
+use controls::{Controls, Mode, DEFAULT_FAST_FORWARD, FAST_FORWARD_KEY};
+use input::{Input, QUIT_KEY, REMAP_KEY};
+use scheduler::{Scheduler, Speed};
+use settings::Settings;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use std::{fs, io, process, thread};
+use watch::FileWatcher;
+
+use chip8_rs::debugger::{Command, Debugger};
+
+/// Window pixels per chip8 pixel when neither the command line nor the settings give a scale
+const DEFAULT_SCALE: u8 = 10;
+
+/// Host keys which save and load the state file
+const SAVE_STATE_KEY: minifb::Key = minifb::Key::F5;
+const LOAD_STATE_KEY: minifb::Key = minifb::Key::F9;
+
+/// Host keys which restart the rom. A soft reset keeps memory, a hard reset loads the rom again
+/// and reload reads the rom file from disk first.
+const SOFT_RESET_KEY: minifb::Key = minifb::Key::F6;
+const HARD_RESET_KEY: minifb::Key = minifb::Key::F7;
+const RELOAD_KEY: minifb::Key = minifb::Key::F8;
+
+/// The files a running rom reads and writes
+struct SessionFiles<'a> {
+    rom: &'a Path,
+    state: Option<&'a Path>,
+}
+
+/// Get the builtin rom database, merged with an override file
+fn load_rom_database(file: Option<&Path>) -> Result<RomDatabase, io::Error> {
+    let mut database = RomDatabase::builtin().clone();
+    if let Some(file) = file {
+        database.merge(RomDatabase::load_file(file)?);
+    }
+    Ok(database)
+}
+
+/// The file name of a rom without its extension, used to find per rom settings
+fn rom_name(path: &Path) -> String {
+    path.file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// A chip with a rom loaded and configured from the command line
+struct Session {
+    chip: Chip,
+    sha1: String,
+    instructions_per_frame: u32,
+}
+
+/// Create a chip and load the rom, applying every option which affects emulation
+fn start_session(args: &RunArgs) -> Result<Session, io::Error> {
+    let rom = rom::read_file(&args.rom)?;
+
+    // An explicit platform on the command line wins over the rom database
+    let mut chip = Chip::with_config(ChipConfig::from(args.platform.unwrap_or(Platform::Chip8)));
+    chip.set_rom_database(load_rom_database(args.romdb.as_deref())?);
+    chip.set_auto_configure(args.platform.is_none());
+    chip.load_rom_bytes(&rom)?;
+
+    if let Some(quirks) = args.quirks {
+        chip.set_quirks(quirks);
+    }
+    if let Some(timing) = args.timing {
+        chip.set_timing(timing);
+    }
+    if let Some(seed) = args.seed {
+        chip.set_seed(seed);
+    }
+    if let Some(state) = args.state.as_ref().filter(|state| state.is_file()) {
+        chip.load_state_file(state)?;
+        println!("Loaded state from {}", state.display());
+    }
+
+    let instructions_per_frame = args
+        .speed
+        .or_else(|| chip.rom_info().and_then(|info| info.instructions_per_frame))
+        .unwrap_or(1);
+    Ok(Session {
+        chip,
+        sha1: sha1_hex(&rom),
+        instructions_per_frame,
+    })
+}
+
+/// Print what the rom database knows about the loaded rom
+fn print_rom_info(chip: &Chip) {
+    if let Some(info) = chip.rom_info() {
+        println!(
+            "{} by {}",
+            info.title.as_deref().unwrap_or("Unknown title"),
+            info.author.as_deref().unwrap_or("unknown author")
+        );
+        for hint in info.keys.iter() {
+            println!("  {}", hint);
+        }
+    }
+}
+
+/// The window title: the description's summary, else the database title, else the file name
+fn window_title(chip: &Chip, rom: &Path, description: &Option<RomDescription>) -> String {
+    match (description, chip.rom_info().and_then(|i| i.title.clone())) {
+        (Some(description), _) => description.summary(),
+        (None, Some(title)) => title,
+        (None, None) => rom.display().to_string(),
+    }
+}
+
+/// The palette from the command line, else the rom database
+fn rom_palette(args: &RunArgs, chip: &Chip) -> Option<Palette> {
+    args.palette
+        .clone()
+        .or_else(|| chip.rom_info().and_then(|info| info.palette.clone()))
+}
+
+/// The display filter from the command line, else the rom database
+fn rom_filter(args: &RunArgs, chip: &Chip) -> Option<FilterMode> {
+    args.filter
+        .or_else(|| chip.rom_info().and_then(|info| info.filter))
+}
+
+/// Execute a number of frames without a window, optionally recording coverage. Returns the chip
+/// for inspection.
+fn run_headless(args: &RunArgs, frames: u32, coverage: bool) -> Result<Chip, io::Error> {
+    let Session {
+        mut chip,
+        instructions_per_frame,
+        ..
+    } = start_session(args)?;
+    if coverage {
+        chip.enable_coverage();
+    }
+    for _ in 0..frames {
+        chip.run_frame(instructions_per_frame)?;
+    }
+
+    if let Some(state) = &args.state {
+        chip.save_state_file(state)?;
+    }
+    Ok(chip)
+}
+
+/// Read the rom file again and restart it
+fn reload_rom(chip: &mut Chip, rom: &Path) -> Result<(), io::Error> {
+    chip.reload_rom_bytes(&rom::read_file(rom)?)
+}
+
+/// Execute an atomic step through the system. Read user input, execute the frames due as the speed
+/// controls allow, update the display. Returns the number of frames run, or None once the window
+/// is closed, and an error if an instruction fails.
+fn tick(
+    chip: &mut Chip,
+    display: &mut chip8_rs::RomWindow,
+    input: &mut Input,
+    controls: &mut Controls,
+    files: &SessionFiles,
+    instructions_per_frame: u32,
+    due: u32,
+) -> Result<Option<u32>, io::Error> {
+    let keys = display.window.get_keys().unwrap_or_default();
+    let pressed = |key| display.window.is_key_pressed(key, minifb::KeyRepeat::No);
+
+    // The chip is paused while remapping keys, and Escape cancels remapping rather than quitting
+    let mut frames = 0;
+    let mut quit = pressed(QUIT_KEY);
+
+    // The display filter forgets the old screen when the rom restarts
+    let mut restarted = false;
+    if input.remapper.is_some() && quit {
+        input.cancel_remap(display);
+        quit = false;
+    } else if input.remapper.is_some() {
+        let keys: Vec<minifb::Key> = keys.into_iter().filter(|k| *k != REMAP_KEY).collect();
+        input.update_remap(display, &keys);
+    } else if pressed(REMAP_KEY) {
+        input.start_remap(display, &keys);
+    } else {
+        if pressed(SOFT_RESET_KEY) {
+            chip.soft_reset();
+            restarted = true;
+            println!("Soft reset");
+        } else if pressed(HARD_RESET_KEY) {
+            chip.reset();
+            restarted = true;
+            println!("Hard reset");
+        } else if pressed(RELOAD_KEY) {
+            match reload_rom(chip, files.rom) {
+                Ok(_) => {
+                    restarted = true;
+                    println!("Reloaded {}", files.rom.display());
+                }
+                Err(error) => eprintln!("Unable to reload the rom: {}", error),
+            }
+        }
+
+        if let Some(state) = files.state {
+            if pressed(SAVE_STATE_KEY) {
+                match chip.save_state_file(state) {
+                    Ok(_) => println!("Saved state to {}", state.display()),
+                    Err(error) => eprintln!("Unable to save state: {}", error),
+                }
+            } else if pressed(LOAD_STATE_KEY) {
+                match chip.load_state_file(state) {
+                    Ok(_) => println!("Loaded state from {}", state.display()),
+                    Err(error) => eprintln!("Unable to load state: {}", error),
+                }
+            }
+        }
+
+        controls.update(pressed, display.window.is_key_down(FAST_FORWARD_KEY));
+        chip.update_keys(input.keymap.map(&keys));
+
+        // Execute the frames due, more than one when catching up or fast forwarding
+        frames = controls
+            .frames(due)
+            .run(|| chip.run_frame(instructions_per_frame))?;
+    }
+
+    // Update the display
+    if restarted {
+        display.clear_filter();
+    }
+    if display.window.is_open() && !quit {
+        display.present(chip);
+        return Ok(Some(frames));
+    }
+    Ok(None)
+}
+
+/// The window title with the speed mode, unless running normally, and the measured speed
+fn status_title(title: &str, mode: Mode, speed: Option<&Speed>) -> String {
+    let mut status = title.to_string();
+    if mode != Mode::Normal {
+        status.push_str(&format!(" - {}", mode));
+    }
+    if let Some(speed) = speed {
+        status.push_str(&format!(
+            " - {:.0} FPS, {:.0} IPS",
+            speed.frames_per_second, speed.instructions_per_second
+        ));
+    }
+    status
+}
+
+/// Run a rom in a window, or headless with --frames. This is the default mode of execution for
+/// the emulator.
+fn run(refresh_rate: u16, args: &RunArgs) -> Result<(), io::Error> {
+    if let Some(frames) = args.frames {
+        let chip = run_headless(args, frames, false)?;
+        print!("{}", chip.screen_buffer.to_text());
+        return Ok(());
+    }
+    run_window(refresh_rate, args, start_session(args)?)
+}
+
+/// Run frames at a given refresh rate until the window is closed. Frames are paced against the
+/// clock, so time spent emulating and drawing doesn't slow the emulator down. The measured speed
+/// is shown in the title bar.
+fn run_window(refresh_rate: u16, args: &RunArgs, session: Session) -> Result<(), io::Error> {
+    let Session {
+        mut chip,
+        sha1,
+        instructions_per_frame,
+    } = session;
+    println!("starting application {}", args.rom.display());
+    print_rom_info(&chip);
+
+    let description = RomDescription::for_rom(&args.rom);
+    let title = window_title(&chip, &args.rom, &description);
+
+    let keymaps = match &args.keymap {
+        Some(file) => KeymapConfig::load_file(file)?,
+        None => KeymapConfig::default(),
+    };
+    let mut input = Input {
+        keymap: keymaps.for_rom(&sha1, &rom_name(&args.rom)).clone(),
+        remapper: None,
+        keymaps,
+        keymap_file: args.keymap.clone(),
+        rom: sha1,
+        title: title.clone(),
+    };
+
+    let mut display = chip8_rs::RomWindow::new(args.scale.unwrap_or(DEFAULT_SCALE), &title, &chip);
+    if let Some(palette) = rom_palette(args, &chip) {
+        display.set_palette(palette);
+    }
+    if let Some(filter) = rom_filter(args, &chip) {
+        display.set_filter(filter);
+    }
+
+    let mut scheduler = Scheduler::new(refresh_rate, Instant::now());
+    let mut controls = Controls::new(
+        args.fast_forward.unwrap_or(DEFAULT_FAST_FORWARD),
+        scheduler.frame_duration(),
+    );
+    let mut mode = controls.mode();
+    let mut speed = None;
+    let files = SessionFiles {
+        rom: &args.rom,
+        state: args.state.as_deref(),
+    };
+    let mut watcher = if args.watch {
+        Some(FileWatcher::new(files.rom, Instant::now()))
+    } else {
+        None
+    };
+    loop {
+        let now = Instant::now();
+        if let Some(watcher) = watcher.as_mut() {
+            if watcher.poll(now) {
+                match reload_rom(&mut chip, files.rom) {
+                    Ok(_) => {
+                        display.clear_filter();
+                        println!("Reloaded {}", files.rom.display());
+                    }
+                    Err(error) => eprintln!("Unable to reload the rom: {}", error),
+                }
+            }
+        }
+        let due = scheduler.frames_due(now);
+        if due == 0 {
+            thread::sleep(scheduler.until_next_frame(now));
+            continue;
+        }
+
+        let executed = chip.instruction_count();
+        let frames = match tick(
+            &mut chip,
+            &mut display,
+            &mut input,
+            &mut controls,
+            &files,
+            instructions_per_frame,
+            due,
+        )? {
+            Some(frames) => frames,
+            None => return Ok(()),
+        };
+        let executed = chip.instruction_count().saturating_sub(executed);
+        let measured = scheduler.record(frames, executed, now);
+        if measured.is_some() || controls.mode() != mode {
+            speed = measured.or(speed);
+            mode = controls.mode();
+
+            // The title shows a prompt while remapping keys
+            if input.remapper.is_none() {
+                display
+                    .window
+                    .set_title(&status_title(&title, mode, speed.as_ref()));
+            }
+        }
+    }
+}
+
+/// Run a rom in the debugger. Continue hands the chip over to a window, which runs until it's
+/// closed.
+fn run_debug(args: &RunArgs) -> Result<(), io::Error> {
+    // @todo add restart and breakpoints
+    // @todo add capability to write to memory and registers
+
+    let mut session = start_session(args)?;
+    let mut debugger = chip8_rs::debugger::Chip8Debugger::new();
+    debugger.set_description(RomDescription::for_rom(&args.rom));
+    debugger.set_instructions_per_frame(session.instructions_per_frame);
+    debugger.welcome();
+    loop {
+        match debugger.get_user_input() {
+            Some(Command::Info) => debugger.info(&session.chip),
+            Some(Command::Step) => debugger.step(&mut session.chip),
+            Some(Command::PrintMemory((address, length))) => {
+                debugger.print_memory(&session.chip, address, length)
+            }
+            Some(Command::PrintRegisters(register)) => {
+                debugger.print_registers(&session.chip, register)
+            }
+            Some(Command::PrintInstructions) => debugger.print_instructions(&session.chip),
+            Some(Command::PrintKeys) => debugger.print_keys(&session.chip),
+            Some(Command::PrintStack) => debugger.print_stack(&session.chip),
+            Some(Command::PrintScreenBuffer) => debugger.print_screen(&session.chip),
+            Some(Command::PrintSoundTimer) => debugger.print_sound_timer(&session.chip),
+            Some(Command::PrintDelayTimer) => debugger.print_delay_timer(&session.chip),
+            Some(Command::Help) => debugger.help(),
+            Some(Command::Handoff) => return run_window(60, args, session),
+            Some(Command::Quit) => return Ok(()),
+            None => println!("Invalid command. Use help (h) to show valid commands"),
+        }
+    }
+}
+
+/// Frames run by the test command when --frames isn't given, five seconds at 60Hz
+const DEFAULT_TEST_FRAMES: u32 = 300;
+
+/// Run a rom headless, print the screen and its hash, and check the hash if one is expected.
+/// Returns false if the hash doesn't match.
+fn run_test(args: &TestArgs) -> Result<bool, io::Error> {
+    let coverage = args.coverage.is_some() || args.listing.is_some();
+    let frames = args.run.frames.unwrap_or(DEFAULT_TEST_FRAMES);
+    let chip = run_headless(&args.run, frames, coverage)?;
+    let hash = sha1_hex(chip.screen_buffer.as_bytes());
+    print!("{}", chip.screen_buffer.to_text());
+    println!("screen sha1 {}", hash);
+    write_coverage(&chip, args)?;
+
+    match &args.expect {
+        Some(expected) if !expected.eq_ignore_ascii_case(&hash) => {
+            eprintln!("Expected screen sha1 {}", expected);
+            Ok(false)
+        }
+        _ => Ok(true),
+    }
+}
+
+/// Print how much of the rom was covered and write the reports asked for
+fn write_coverage(chip: &Chip, args: &TestArgs) -> Result<(), io::Error> {
+    let (coverage, rom) = match (chip.coverage(), chip.rom_range()) {
+        (Some(coverage), Some(rom)) => (coverage, rom),
+        _ => return Ok(()),
+    };
+    println!(
+        "coverage {} of {} rom bytes",
+        coverage.covered_bytes(rom.start, rom.end),
+        rom.len()
+    );
+
+    if let Some(path) = &args.coverage {
+        let source = args.run.rom.display().to_string();
+        fs::write(path, coverage.lcov(&source, rom.start, rom.end))?;
+    }
+    if let Some(path) = &args.listing {
+        let memory = chip.memory().as_slice();
+        fs::write(path, coverage.annotated_listing(memory, rom.start, rom.end))?;
+    }
+    Ok(())
+}
+
+fn disassemble(rom: &Path, origin: u16) -> Result<(), io::Error> {
+    print!("{}", asm::disassemble(&rom::read_file(rom)?, origin));
+    Ok(())
+}
+
+fn assemble(source: &Path, output: &Path, origin: u16) -> Result<(), io::Error> {
+    let rom = asm::assemble(&fs::read_to_string(source)?, origin)?;
+    fs::write(output, &rom)?;
+    println!("Wrote {} bytes to {}", rom.len(), output.display());
+    Ok(())
+}
+
+fn info(rom_path: &Path, romdb: Option<PathBuf>) -> Result<(), io::Error> {
+    let rom = rom::read_file(rom_path)?;
+    println!("file   {}", rom_path.display());
+    println!("size   {} bytes", rom.len());
+    println!("sha1   {}", sha1_hex(&rom));
+
+    match load_rom_database(romdb.as_deref())?.lookup(&rom) {
+        Some(info) => {
+            if let Some(title) = &info.title {
+                println!("title  {}", title);
+            }
+            if let Some(author) = &info.author {
+                println!("author {}", author);
+            }
+            if let Some(platform) = info.platform {
+                println!("platform {:?}", platform);
+            }
+            if let Some(quirks) = info.quirks {
+                println!("quirks {:?}", quirks);
+            }
+            if let Some(speed) = info.instructions_per_frame {
+                println!("speed  {} instructions per frame", speed);
+            }
+            for hint in info.keys.iter() {
+                println!("key    {}", hint);
+            }
+        }
+        None => println!("Not in the rom database"),
+    }
+
+    if let Some(description) = RomDescription::for_rom(rom_path) {
+        println!("\n{}", description);
+    }
+    Ok(())
+}
+
+/// Show or change the settings file
+fn configure(path: Option<PathBuf>, action: &ConfigAction) -> Result<(), io::Error> {
+    let path = path.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            "No config directory, set XDG_CONFIG_HOME or pass --config",
+        )
+    })?;
+    let mut settings = Settings::load(&path)?;
+    match action {
+        ConfigAction::Show => {
+            println!("# {}", path.display());
+            print!("{}", settings.to_toml());
+            return Ok(());
+        }
+        ConfigAction::Set { key, value } => settings.set(key, value)?,
+        ConfigAction::Unset { key } => settings.unset(key)?,
+    }
+    settings.save(&path)
+}
+
+/// Fill in options from the settings file
+fn apply_settings(path: Option<&Path>, args: &mut RunArgs) -> Result<(), io::Error> {
+    match path {
+        Some(path) => Settings::load(path)?.apply(args),
+        None => Ok(()),
+    }
+}
+
+/// Remember a rom as recently run. Only roms run in a window are remembered, so headless runs,
+/// e.g. in CI, never write the settings file.
+fn add_recent(path: Option<&Path>, args: &RunArgs) {
+    let path = match path {
+        Some(path) if args.frames.is_none() => path,
+        _ => return,
+    };
+    let saved = Settings::load(path).and_then(|mut settings| {
+        settings.add_recent(&args.rom);
+        settings.save(path)
+    });
+    if let Err(error) = saved {
+        eprintln!(
+            "Unable to save recent roms to {}: {}",
+            path.display(),
+            error
+        );
+    }
+}
+
+/// List the roms in a directory, with recently run roms first, and run the one chosen. The list
+/// is shown again once the rom's window is closed.
+fn browse(
+    settings_path: Option<&Path>,
+    dir: &Path,
+    romdb: Option<PathBuf>,
+) -> Result<(), io::Error> {
+    let database = load_rom_database(romdb.as_deref())?;
+    let found = browser::scan(dir, &database)
+        .map_err(|error| io::Error::new(error.kind(), format!("{}: {}", dir.display(), error)))?;
+    loop {
+        let recent = match settings_path {
+            Some(path) => Settings::load(path)?.recent,
+            None => Vec::new(),
+        };
+        let entries = browser::with_recent(&recent, found.clone(), &database);
+        if entries.is_empty() {
+            println!("No roms found in {}", dir.display());
+            return Ok(());
+        }
+
+        let choice = browser::choose(&entries, &mut io::stdin().lock(), &mut io::stdout())?;
+        let entry = match choice {
+            Some(choice) => &entries[choice],
+            None => return Ok(()),
+        };
+        let mut args = RunArgs {
+            rom: entry.path.clone(),
+            romdb: romdb.clone(),
+            ..Default::default()
+        };
+        let result = apply_settings(settings_path, &mut args).and_then(|_| {
+            add_recent(settings_path, &args);
+            run(60, &args)
+        });
+        if let Err(error) = result {
+            eprintln!("{}", error);
+        }
+    }
+}
+
+fn main() {
+    let mut cli = Cli::parse();
+    let settings_path = cli.config.clone().or_else(Settings::default_path);
+    let result = match &mut cli.command {
+        cli::Command::Run(args) => apply_settings(settings_path.as_deref(), args).and_then(|_| {
+            add_recent(settings_path.as_deref(), args);
+            run(60, args)
+        }),
+        cli::Command::Debug(args) => {
+            apply_settings(settings_path.as_deref(), args).and_then(|_| run_debug(args))
+        }
+        // Test runs ignore the settings file, so results don't depend on who runs them
+        cli::Command::Test(args) => match run_test(args) {
+            Ok(false) => process::exit(1),
+            result => result.map(|_| ()),
+        },
+        cli::Command::Disasm { rom, origin } => disassemble(rom, *origin),
+        cli::Command::Asm {
+            source,
+            output,
+            origin,
+        } => assemble(source, output, *origin),
+        cli::Command::Info { rom, romdb } => info(rom, romdb.clone()),
+        cli::Command::Browse { dir, romdb } => browse(settings_path.as_deref(), dir, romdb.clone()),
+        cli::Command::Config { action } => configure(settings_path, action),
+    };
+
+    if let Err(error) = result {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chip8_rs::keymap::RESERVED_KEYS;
+    use controls::{FRAME_ADVANCE_KEY, PAUSE_KEY, SLOW_MOTION_KEY};
+
+    #[test]
+    fn hotkeys_are_reserved() {
+        let hotkeys = [
+            REMAP_KEY,
+            QUIT_KEY,
+            PAUSE_KEY,
+            FRAME_ADVANCE_KEY,
+            SLOW_MOTION_KEY,
+            FAST_FORWARD_KEY,
+            SAVE_STATE_KEY,
+            LOAD_STATE_KEY,
+            SOFT_RESET_KEY,
+            HARD_RESET_KEY,
+            RELOAD_KEY,
+        ];
+        for key in hotkeys.iter() {
+            assert!(RESERVED_KEYS.contains(key), "{:?} isn't reserved", key);
+        }
+    }
+}
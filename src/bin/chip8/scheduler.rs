@@ -0,0 +1,164 @@
+use std::time::{Duration, Instant};
+
+/// The most frames run at once to catch up after the emulator falls behind. Any more are dropped.
+const MAX_CATCH_UP_FRAMES: u32 = 4;
+
+/// How often the measured speed is updated
+const MEASURE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Measured emulation speed
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Speed {
+    pub frames_per_second: f64,
+    pub instructions_per_second: f64,
+}
+
+/// Paces frames against the clock. Frames are due at a fixed rate however long each one takes to
+/// run, so the emulator keeps the configured rate rather than drifting slower.
+#[derive(Debug)]
+pub struct Scheduler {
+    frame_duration: Duration,
+
+    // When the next frame is due
+    next_frame: Instant,
+
+    // Frames and instructions run since the measurement started
+    measure_start: Instant,
+    frames: u32,
+    instructions: u64,
+}
+
+impl Scheduler {
+    /// Schedule frames at a refresh rate in Hz, starting now. Rates outside 20 to 300 Hz use
+    /// 60 Hz.
+    pub fn new(refresh_rate: u16, now: Instant) -> Scheduler {
+        let refresh_rate = if (20..=300).contains(&refresh_rate) {
+            refresh_rate
+        } else {
+            60
+        };
+        Scheduler {
+            frame_duration: Duration::from_secs(1) / u32::from(refresh_rate),
+            next_frame: now,
+            measure_start: now,
+            frames: 0,
+            instructions: 0,
+        }
+    }
+
+    pub fn frame_duration(&self) -> Duration {
+        self.frame_duration
+    }
+
+    /// The number of frames which should run now. Usually 0 or 1, more when catching up. When too
+    /// far behind, e.g. after the window was dragged, the missed frames are dropped.
+    pub fn frames_due(&mut self, now: Instant) -> u32 {
+        let mut due = 0;
+        while self.next_frame <= now {
+            due += 1;
+            self.next_frame += self.frame_duration;
+            if due == MAX_CATCH_UP_FRAMES {
+                if self.next_frame <= now {
+                    self.next_frame = now + self.frame_duration;
+                }
+                break;
+            }
+        }
+        due
+    }
+
+    /// How long to wait for the next frame
+    pub fn until_next_frame(&self, now: Instant) -> Duration {
+        self.next_frame.saturating_duration_since(now)
+    }
+
+    /// Count frames and instructions which have run. Returns the measured speed about once a
+    /// second.
+    pub fn record(&mut self, frames: u32, instructions: u64, now: Instant) -> Option<Speed> {
+        self.frames += frames;
+        self.instructions += instructions;
+        let elapsed = now.saturating_duration_since(self.measure_start);
+        if elapsed < MEASURE_INTERVAL {
+            return None;
+        }
+        let seconds = elapsed.as_secs_f64();
+        let speed = Speed {
+            frames_per_second: f64::from(self.frames) / seconds,
+            instructions_per_second: self.instructions as f64 / seconds,
+        };
+        self.measure_start = now;
+        self.frames = 0;
+        self.instructions = 0;
+        Some(speed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_rate() {
+        let now = Instant::now();
+        assert_eq!(
+            Duration::from_secs(1) / 60,
+            Scheduler::new(60, now).frame_duration()
+        );
+        assert_eq!(
+            Duration::from_secs(1) / 60,
+            Scheduler::new(1000, now).frame_duration()
+        );
+        assert_eq!(
+            Duration::from_millis(10),
+            Scheduler::new(100, now).frame_duration()
+        );
+    }
+
+    #[test]
+    fn frames_run_on_time() {
+        let start = Instant::now();
+        let mut scheduler = Scheduler::new(100, start);
+        assert_eq!(1, scheduler.frames_due(start));
+        assert_eq!(0, scheduler.frames_due(start + Duration::from_millis(4)));
+        assert_eq!(
+            Duration::from_millis(6),
+            scheduler.until_next_frame(start + Duration::from_millis(4))
+        );
+
+        // A slow frame doesn't push back the ones after it
+        assert_eq!(1, scheduler.frames_due(start + Duration::from_millis(13)));
+        assert_eq!(1, scheduler.frames_due(start + Duration::from_millis(20)));
+    }
+
+    #[test]
+    fn catch_up_then_drop() {
+        let start = Instant::now();
+        let mut scheduler = Scheduler::new(100, start);
+        assert_eq!(3, scheduler.frames_due(start + Duration::from_millis(25)));
+
+        let late = start + Duration::from_secs(2);
+        assert_eq!(MAX_CATCH_UP_FRAMES, scheduler.frames_due(late));
+        assert_eq!(0, scheduler.frames_due(late));
+        assert_eq!(1, scheduler.frames_due(late + Duration::from_millis(10)));
+    }
+
+    #[test]
+    fn measure_speed() {
+        let start = Instant::now();
+        let mut scheduler = Scheduler::new(60, start);
+        assert_eq!(
+            None,
+            scheduler.record(30, 300, start + Duration::from_millis(500))
+        );
+        let speed = scheduler
+            .record(30, 300, start + Duration::from_secs(1))
+            .unwrap();
+        assert_eq!(60.0, speed.frames_per_second);
+        assert_eq!(600.0, speed.instructions_per_second);
+
+        assert_eq!(
+            None,
+            scheduler.record(1, 1, start + Duration::from_millis(1500))
+        );
+    }
+}
@@ -0,0 +1,308 @@
+use crate::cli::{RunArgs, MAX_SPEED};
+use chip8_rs::config::Quirks;
+use chip8_rs::filter::FilterMode;
+use chip8_rs::palette::Palette;
+use chip8_rs::timing::Timing;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::vec::Vec;
+
+/// The number of roms remembered in the recent list
+const MAX_RECENT_ROMS: usize = 10;
+
+/// The names accepted by `config set` and `config unset`
+pub const KEYS: [&str; 7] = [
+    "scale", "palette", "filter", "speed", "timing", "quirks", "keymap",
+];
+
+/// Defaults for command line options, saved in the user's config directory. Options given on
+/// the command line win over the settings file.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    pub scale: Option<u8>,
+    pub palette: Option<String>,
+    pub filter: Option<String>,
+    pub speed: Option<u32>,
+    pub timing: Option<String>,
+    pub quirks: Option<String>,
+    pub keymap: Option<PathBuf>,
+
+    // Most recently run roms, newest first
+    pub recent: Vec<PathBuf>,
+}
+
+impl Settings {
+    /// The settings file: $XDG_CONFIG_HOME/chip8-rs/config.toml, falling back to
+    /// ~/.config/chip8-rs/config.toml
+    pub fn default_path() -> Option<PathBuf> {
+        let config_home = env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+        Some(config_home.join("chip8-rs").join("config.toml"))
+    }
+
+    /// Parse settings from TOML, checking every value
+    pub fn parse(text: &str) -> Result<Settings, Error> {
+        let settings: Settings =
+            toml::from_str(text).map_err(|error| Error::new(ErrorKind::InvalidData, error))?;
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    /// Load the settings file. A missing file gives the default settings.
+    pub fn load(path: &Path) -> Result<Settings, Error> {
+        match fs::read_to_string(path) {
+            Ok(text) => Settings::parse(&text).map_err(|error| {
+                Error::new(error.kind(), format!("{}: {}", path.display(), error))
+            }),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(Settings::default()),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Write the settings file, creating the config directory if needed
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.to_toml())
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).expect("Settings are always valid TOML")
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        let invalid = |message: String| Err(Error::new(ErrorKind::InvalidData, message));
+        match self.scale {
+            Some(scale) if !(1..=64).contains(&scale) => {
+                return invalid(format!("scale {} is not between 1 and 64", scale))
+            }
+            _ => {}
+        }
+        match self.speed {
+            Some(speed) if !(1..=MAX_SPEED).contains(&speed) => {
+                return invalid(format!(
+                    "speed {} is not between 1 and {}",
+                    speed, MAX_SPEED
+                ))
+            }
+            _ => {}
+        }
+        if let Some(palette) = &self.palette {
+            palette.parse::<Palette>()?;
+        }
+        if let Some(filter) = &self.filter {
+            filter.parse::<FilterMode>()?;
+        }
+        if let Some(timing) = &self.timing {
+            timing.parse::<Timing>()?;
+        }
+        if let Some(quirks) = &self.quirks {
+            quirks.parse::<Quirks>()?;
+        }
+        Ok(())
+    }
+
+    /// Set a single setting from its name and a string value, as given to `config set`
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), Error> {
+        let mut updated = self.clone();
+        match key {
+            "scale" => updated.scale = Some(parse_number(value)?),
+            "palette" => updated.palette = Some(value.to_string()),
+            "filter" => updated.filter = Some(value.to_string()),
+            "speed" => updated.speed = Some(parse_number(value)?),
+            "timing" => updated.timing = Some(value.to_string()),
+            "quirks" => updated.quirks = Some(value.to_string()),
+            "keymap" => updated.keymap = Some(PathBuf::from(value)),
+            _ => return Err(unknown_key(key)),
+        }
+        updated.validate()?;
+        *self = updated;
+        Ok(())
+    }
+
+    /// Clear a single setting, so the built in default is used
+    pub fn unset(&mut self, key: &str) -> Result<(), Error> {
+        match key {
+            "scale" => self.scale = None,
+            "palette" => self.palette = None,
+            "filter" => self.filter = None,
+            "speed" => self.speed = None,
+            "timing" => self.timing = None,
+            "quirks" => self.quirks = None,
+            "keymap" => self.keymap = None,
+            _ => return Err(unknown_key(key)),
+        }
+        Ok(())
+    }
+
+    /// Fill in every option not given on the command line
+    pub fn apply(&self, args: &mut RunArgs) -> Result<(), Error> {
+        if args.scale.is_none() {
+            args.scale = self.scale;
+        }
+        if args.speed.is_none() {
+            args.speed = self.speed;
+        }
+        if args.timing.is_none() {
+            args.timing = self.timing.as_deref().map(str::parse).transpose()?;
+        }
+        if args.palette.is_none() {
+            args.palette = self.palette.as_deref().map(str::parse).transpose()?;
+        }
+        if args.filter.is_none() {
+            args.filter = self.filter.as_deref().map(str::parse).transpose()?;
+        }
+        if args.quirks.is_none() {
+            args.quirks = self.quirks.as_deref().map(str::parse).transpose()?;
+        }
+        if args.keymap.is_none() {
+            args.keymap = self.keymap.clone();
+        }
+        Ok(())
+    }
+
+    /// Move a rom to the front of the recent list
+    pub fn add_recent(&mut self, rom: &Path) {
+        let rom = fs::canonicalize(rom).unwrap_or_else(|_| rom.to_path_buf());
+        self.recent.retain(|recent| *recent != rom);
+        self.recent.insert(0, rom);
+        self.recent.truncate(MAX_RECENT_ROMS);
+    }
+}
+
+fn parse_number<T: FromStr>(value: &str) -> Result<T, Error> {
+    value.parse().map_err(|_| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!("'{}' is not a number", value),
+        )
+    })
+}
+
+fn unknown_key(key: &str) -> Error {
+    Error::new(
+        ErrorKind::InvalidInput,
+        format!(
+            "Unknown setting '{}', expected one of {}",
+            key,
+            KEYS.join(", ")
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::{Cli, Command};
+    use clap::Parser;
+
+    fn run_args(args: &[&str]) -> RunArgs {
+        let argv = ["chip8", "run", "maze.ch8"].iter().chain(args.iter());
+        match Cli::try_parse_from(argv).unwrap().command {
+            Command::Run(args) => args,
+            command => panic!("Parsed the wrong command {:?}", command),
+        }
+    }
+
+    #[test]
+    fn parse() {
+        let settings = Settings::parse(
+            "scale = 4\npalette = \"000000:FFB000\"\nquirks = \"schip\"\nspeed = 100000\n\
+             recent = [\"/roms/maze.ch8\"]",
+        )
+        .unwrap();
+        assert_eq!(Some(4), settings.scale);
+        assert_eq!(Some(100_000), settings.speed);
+        assert_eq!(vec![PathBuf::from("/roms/maze.ch8")], settings.recent);
+        assert_eq!(settings, Settings::parse(&settings.to_toml()).unwrap());
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert!(Settings::parse("scale = 0").is_err());
+        assert!(Settings::parse("speed = 0").is_err());
+        assert!(Settings::parse("speed = 100001").is_err());
+        assert!(Settings::parse("palette = \"red\"").is_err());
+        assert!(Settings::parse("filter = \"decay:0\"").is_err());
+        assert!(Settings::parse("timing = \"fast\"").is_err());
+        assert!(Settings::parse("quirks = \"nes\"").is_err());
+        assert!(Settings::parse("colour = 1").is_err());
+    }
+
+    #[test]
+    fn set_and_unset() {
+        let mut settings = Settings::default();
+        settings.set("scale", "6").unwrap();
+        settings.set("quirks", "chip8").unwrap();
+        assert_eq!(Some(6), settings.scale);
+
+        assert!(settings.set("scale", "100").is_err());
+        assert!(settings.set("scale", "big").is_err());
+        assert!(settings.set("speed", "4000000000").is_err());
+        assert!(settings.set("fullscreen", "1").is_err());
+        assert_eq!(Some(6), settings.scale);
+
+        settings.unset("scale").unwrap();
+        assert_eq!(None, settings.scale);
+        assert_eq!(Some("chip8".to_string()), settings.quirks);
+    }
+
+    #[test]
+    fn command_line_wins() {
+        let mut settings = Settings::default();
+        settings.set("scale", "6").unwrap();
+        settings.set("speed", "20").unwrap();
+        settings.set("quirks", "schip").unwrap();
+        settings.set("filter", "or").unwrap();
+
+        let mut args = run_args(&["--scale", "3"]);
+        settings.apply(&mut args).unwrap();
+        assert_eq!(Some(3), args.scale);
+        assert_eq!(Some(20), args.speed);
+        assert_eq!(Some("schip".parse().unwrap()), args.quirks);
+        assert_eq!(Some(FilterMode::Or), args.filter);
+        assert_eq!(None, args.palette);
+    }
+
+    #[test]
+    fn recent_roms() {
+        let mut settings = Settings::default();
+        for i in 0..12 {
+            settings.add_recent(Path::new(&format!("/missing/{}.ch8", i)));
+        }
+        settings.add_recent(Path::new("/missing/5.ch8"));
+        assert_eq!(MAX_RECENT_ROMS, settings.recent.len());
+        assert_eq!(PathBuf::from("/missing/5.ch8"), settings.recent[0]);
+        assert_eq!(PathBuf::from("/missing/11.ch8"), settings.recent[1]);
+        assert_eq!(
+            1,
+            settings
+                .recent
+                .iter()
+                .filter(|r| r.ends_with("5.ch8"))
+                .count()
+        );
+    }
+
+    #[test]
+    fn load_and_save() {
+        let path = env::temp_dir()
+            .join("chip8_rs_settings")
+            .join("config.toml");
+        let _ = fs::remove_file(&path);
+        assert_eq!(Settings::default(), Settings::load(&path).unwrap());
+
+        let mut settings = Settings::default();
+        settings.set("palette", "001100:33FF66").unwrap();
+        settings.save(&path).unwrap();
+        assert_eq!(settings, Settings::load(&path).unwrap());
+    }
+}
@@ -0,0 +1,88 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// How often the watched file is checked
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Watches a file for changes by polling its modification time and size, so it works anywhere
+/// without a file notification service
+#[derive(Debug)]
+pub struct FileWatcher {
+    path: PathBuf,
+
+    // The modification time and size last seen, None while the file is missing
+    stamp: Option<(SystemTime, u64)>,
+    next_poll: Instant,
+}
+
+impl FileWatcher {
+    /// Start watching a file as it is now
+    pub fn new(path: &Path, now: Instant) -> FileWatcher {
+        FileWatcher {
+            path: path.to_path_buf(),
+            stamp: stamp(path),
+            next_poll: now + POLL_INTERVAL,
+        }
+    }
+
+    /// Check the file if it's time to. Returns true once for each change, but not while the file
+    /// is missing, e.g. while an assembler replaces it.
+    pub fn poll(&mut self, now: Instant) -> bool {
+        if now < self.next_poll {
+            return false;
+        }
+        self.next_poll = now + POLL_INTERVAL;
+
+        let stamp = stamp(&self.path);
+        if stamp == self.stamp {
+            return false;
+        }
+        self.stamp = stamp;
+        stamp.is_some()
+    }
+}
+
+fn stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs::File;
+
+    fn touch(path: &Path, seconds: u64) {
+        File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds))
+            .unwrap();
+    }
+
+    #[test]
+    fn detects_changes() {
+        let path = env::temp_dir().join("chip8_rs_watch.ch8");
+        fs::write(&path, [0x00, 0xE0]).unwrap();
+        touch(&path, 1000);
+
+        let start = Instant::now();
+        let mut watcher = FileWatcher::new(&path, start);
+        assert!(!watcher.poll(start + POLL_INTERVAL));
+
+        // Changes are only seen once the poll interval has passed
+        touch(&path, 2000);
+        assert!(!watcher.poll(start + POLL_INTERVAL));
+        assert!(watcher.poll(start + POLL_INTERVAL * 2));
+        assert!(!watcher.poll(start + POLL_INTERVAL * 3));
+
+        // A missing file isn't a change, but the file coming back is
+        fs::remove_file(&path).unwrap();
+        assert!(!watcher.poll(start + POLL_INTERVAL * 4));
+        fs::write(&path, [0x12, 0x00]).unwrap();
+        assert!(watcher.poll(start + POLL_INTERVAL * 5));
+    }
+}
@@ -0,0 +1,218 @@
+use serde::Deserialize;
+use std::io::{Error, ErrorKind};
+use std::str::FromStr;
+
+/// The machines a rom can be written for. Each platform implies a memory layout.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Platform {
+    // The standard 4 KiB chip-8 machine
+    Chip8,
+
+    // A COSMAC VIP with only 2 KiB of RAM
+    CosmacVip2K,
+
+    // The ETI-660, which loads programs at 0x600
+    Eti660,
+
+    // COSMAC VIP hybrid programs, which ship their own interpreter patch in 0x200 - 0x25F
+    // and start executing at 0x260
+    HybridVip,
+
+    // XO-CHIP, with a full 64 KiB address space
+    XoChip,
+}
+
+/// Behaviors which differ between chip-8 interpreters. Roms written for one interpreter often
+/// depend on its quirks. The default matches the behavior of this emulator before quirks were
+/// configurable, except that FX1E no longer sets vf when I overflows. No preset sets vf there,
+/// as the COSMAC VIP, SUPER-CHIP and XO-CHIP all leave it alone, so it isn't a quirk.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Quirks {
+    // 8XY1, 8XY2 and 8XY3 reset vf to 0
+    pub vf_reset: bool,
+
+    // FX55 and FX65 leave I pointing past the last register stored or loaded
+    pub memory_increments_i: bool,
+
+    // 8XY6 and 8XYE shift vy into vx, rather than shifting vx in place
+    pub shift_uses_vy: bool,
+
+    // BNNN jumps to XNN + vx, rather than NNN + v0
+    pub jump_uses_vx: bool,
+
+    // DXYN clips sprites at the edge of the screen, rather than wrapping them around
+    pub clip_sprites: bool,
+
+    // DXYN waits for the next vertical blank interrupt, so at most one sprite is drawn per frame
+    pub display_wait: bool,
+}
+
+impl FromStr for Quirks {
+    type Err = Error;
+
+    /// Get the quirks of a well known interpreter. Every preset, like the default, leaves vf
+    /// alone in FX1E.
+    fn from_str(name: &str) -> Result<Quirks, Error> {
+        match name.to_ascii_lowercase().as_str() {
+            "default" => Ok(Quirks::default()),
+            "chip8" | "chip-8" | "vip" => Ok(Quirks {
+                vf_reset: true,
+                memory_increments_i: true,
+                shift_uses_vy: true,
+                jump_uses_vx: false,
+                clip_sprites: true,
+                display_wait: true,
+            }),
+            "schip" | "superchip" => Ok(Quirks {
+                vf_reset: false,
+                memory_increments_i: false,
+                shift_uses_vy: false,
+                jump_uses_vx: true,
+                clip_sprites: true,
+                display_wait: false,
+            }),
+            "xochip" | "xo-chip" => Ok(Quirks {
+                vf_reset: false,
+                memory_increments_i: true,
+                shift_uses_vy: true,
+                jump_uses_vx: false,
+                clip_sprites: false,
+                display_wait: false,
+            }),
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Unknown quirk preset '{}', expected one of default, chip8, schip, xochip",
+                    name
+                ),
+            )),
+        }
+    }
+}
+
+/// The configuration a Chip is created with
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChipConfig {
+    pub memory_size: usize,
+    pub load_address: usize,
+    pub start_address: usize,
+    pub screen_width: usize,
+    pub screen_height: usize,
+    pub quirks: Quirks,
+}
+
+impl Default for ChipConfig {
+    fn default() -> ChipConfig {
+        ChipConfig::from(Platform::Chip8)
+    }
+}
+
+impl From<Platform> for ChipConfig {
+    fn from(platform: Platform) -> ChipConfig {
+        let (memory_size, load_address, start_address) = match platform {
+            Platform::Chip8 => (0x1000, 0x200, 0x200),
+            Platform::CosmacVip2K => (0x800, 0x200, 0x200),
+            Platform::Eti660 => (0x1000, 0x600, 0x600),
+            Platform::HybridVip => (0x1000, 0x200, 0x260),
+            Platform::XoChip => (0x10000, 0x200, 0x200),
+        };
+        ChipConfig {
+            memory_size,
+            load_address,
+            start_address,
+            screen_width: 64,
+            screen_height: 32,
+            quirks: Quirks::default(),
+        }
+    }
+}
+
+impl FromStr for Platform {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<Platform, Error> {
+        match name.to_ascii_lowercase().as_str() {
+            "chip8" | "chip-8" => Ok(Platform::Chip8),
+            "vip2k" => Ok(Platform::CosmacVip2K),
+            "eti660" | "eti-660" => Ok(Platform::Eti660),
+            "hybrid-vip" | "hybrid" => Ok(Platform::HybridVip),
+            "xochip" | "xo-chip" => Ok(Platform::XoChip),
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Unknown platform '{}', expected one of chip8, vip2k, eti660, hybrid-vip, xochip",
+                    name
+                ),
+            )),
+        }
+    }
+}
+
+impl ChipConfig {
+    /// The largest rom which fits in memory at the load address
+    pub fn max_rom_size(&self) -> usize {
+        self.memory_size.saturating_sub(self.load_address)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_is_chip8() {
+        let config = ChipConfig::default();
+        assert_eq!(0x1000, config.memory_size);
+        assert_eq!(0x200, config.load_address);
+        assert_eq!(0xE00, config.max_rom_size());
+    }
+
+    #[test]
+    fn platforms() {
+        assert_eq!(
+            0x600,
+            ChipConfig::from(Platform::CosmacVip2K).max_rom_size()
+        );
+        assert_eq!(0xA00, ChipConfig::from(Platform::Eti660).max_rom_size());
+        assert_eq!(0xFE00, ChipConfig::from(Platform::XoChip).max_rom_size());
+    }
+
+    #[test]
+    fn start_address() {
+        assert_eq!(0x600, ChipConfig::from(Platform::Eti660).start_address);
+        assert_eq!(0x260, ChipConfig::from(Platform::HybridVip).start_address);
+        assert_eq!(0x200, ChipConfig::from(Platform::HybridVip).load_address);
+    }
+
+    #[test]
+    fn platform_from_str() {
+        assert_eq!(Platform::Eti660, "eti660".parse::<Platform>().unwrap());
+        assert_eq!(
+            Platform::HybridVip,
+            "Hybrid-VIP".parse::<Platform>().unwrap()
+        );
+        assert_eq!(Platform::Chip8, "chip-8".parse::<Platform>().unwrap());
+        assert!("superchip".parse::<Platform>().is_err());
+    }
+
+    #[test]
+    fn quirks_from_str() {
+        assert_eq!(Quirks::default(), "default".parse::<Quirks>().unwrap());
+        assert!("VIP".parse::<Quirks>().unwrap().vf_reset);
+        assert!("schip".parse::<Quirks>().unwrap().jump_uses_vx);
+        assert!("xochip".parse::<Quirks>().unwrap().shift_uses_vy);
+        assert!(!"xochip".parse::<Quirks>().unwrap().clip_sprites);
+        assert!("chip8".parse::<Quirks>().unwrap().display_wait);
+        assert!("megachip".parse::<Quirks>().is_err());
+    }
+
+    #[test]
+    fn max_rom_size_no_room() {
+        let config = ChipConfig {
+            memory_size: 0x100,
+            ..Default::default()
+        };
+        assert_eq!(0, config.max_rom_size());
+    }
+}
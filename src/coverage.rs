@@ -0,0 +1,216 @@
+use crate::memory::{Access, MemoryObserver};
+use std::fmt::Write;
+use std::vec::Vec;
+
+/// Access counts for a single byte of memory
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ByteCoverage {
+    pub executed: u32,
+    pub read: u32,
+    pub written: u32,
+}
+
+impl ByteCoverage {
+    /// True if the byte has been touched in any way
+    pub fn is_covered(&self) -> bool {
+        self.executed > 0 || self.read > 0 || self.written > 0
+    }
+}
+
+/// Records how every byte of memory is accessed while a rom runs, so a rom can be checked for
+/// dead code and untested paths
+#[derive(Clone, Debug)]
+pub struct Coverage {
+    bytes: Vec<ByteCoverage>,
+}
+
+impl Coverage {
+    /// Create an empty coverage map for a memory of the given size
+    pub fn new(memory_size: usize) -> Coverage {
+        Coverage {
+            bytes: vec![ByteCoverage::default(); memory_size],
+        }
+    }
+
+    /// Record a single access of a memory address. Addresses outside of memory are ignored.
+    pub fn record(&mut self, address: usize, access: Access) {
+        if let Some(byte) = self.bytes.get_mut(address) {
+            match access {
+                Access::Execute => byte.executed = byte.executed.saturating_add(1),
+                Access::Read => byte.read = byte.read.saturating_add(1),
+                Access::Write => byte.written = byte.written.saturating_add(1),
+            }
+        }
+    }
+
+    /// Get the access counts for a memory address
+    pub fn get(&self, address: usize) -> Option<&ByteCoverage> {
+        self.bytes.get(address)
+    }
+
+    /// Forget everything recorded so far
+    pub fn clear(&mut self) {
+        for byte in self.bytes.iter_mut() {
+            *byte = ByteCoverage::default();
+        }
+    }
+
+    /// Match a new memory size, keeping the counts of addresses in both sizes
+    pub fn resize(&mut self, memory_size: usize) {
+        self.bytes.resize(memory_size, ByteCoverage::default());
+    }
+
+    /// Count the bytes in [start, end) which have been touched in any way
+    pub fn covered_bytes(&self, start: usize, end: usize) -> usize {
+        self.region(start, end)
+            .iter()
+            .filter(|byte| byte.is_covered())
+            .count()
+    }
+
+    /// Produce an annotated listing of memory in [start, end). Each line holds one 2 byte word
+    /// and is marked with X (executed), R (read) and W (written), followed by the execution count.
+    ///
+    /// # Arguments
+    ///
+    /// memory The memory the coverage was recorded against
+    /// start The first address to list
+    /// end One past the last address to list
+    pub fn annotated_listing(&self, memory: &[u8], start: usize, end: usize) -> String {
+        let end = end.min(memory.len()).min(self.bytes.len());
+        let mut listing = String::new();
+        for address in (start..end).step_by(2) {
+            let word = &memory[address..(address + 2).min(end)];
+            let coverage = &self.bytes[address..(address + 2).min(end)];
+
+            let mut hex = String::new();
+            for byte in word {
+                write!(hex, "{:02X}", byte).unwrap();
+            }
+
+            let marker = |covered: bool, c: char| if covered { c } else { '-' };
+            writeln!(
+                listing,
+                "{:04X}: {:<4} {}{}{} {}",
+                address,
+                hex,
+                marker(coverage.iter().any(|b| b.executed > 0), 'X'),
+                marker(coverage.iter().any(|b| b.read > 0), 'R'),
+                marker(coverage.iter().any(|b| b.written > 0), 'W'),
+                coverage[0].executed,
+            )
+            .unwrap();
+        }
+        listing
+    }
+
+    /// Produce an lcov tracefile for memory in [start, end). Line numbers match the lines of
+    /// annotated_listing() so the two can be viewed side by side. The hit count of a line is the
+    /// highest access count of either byte in the word.
+    ///
+    /// # Arguments
+    ///
+    /// source The name reported as the source file, typically the rom path
+    /// start The first address to report
+    /// end One past the last address to report
+    pub fn lcov(&self, source: &str, start: usize, end: usize) -> String {
+        let end = end.min(self.bytes.len());
+        let mut report = String::new();
+        writeln!(report, "TN:").unwrap();
+        writeln!(report, "SF:{}", source).unwrap();
+
+        let mut lines_found = 0;
+        let mut lines_hit = 0;
+        for (line, address) in (start..end).step_by(2).enumerate() {
+            let word = &self.bytes[address..(address + 2).min(end)];
+            let hits = word
+                .iter()
+                .map(|b| b.executed.max(b.read).max(b.written))
+                .max()
+                .unwrap_or(0);
+
+            writeln!(report, "DA:{},{}", line + 1, hits).unwrap();
+            lines_found += 1;
+            if hits > 0 {
+                lines_hit += 1;
+            }
+        }
+
+        writeln!(report, "LF:{}", lines_found).unwrap();
+        writeln!(report, "LH:{}", lines_hit).unwrap();
+        writeln!(report, "end_of_record").unwrap();
+        report
+    }
+
+    fn region(&self, start: usize, end: usize) -> &[ByteCoverage] {
+        let end = end.min(self.bytes.len());
+        &self.bytes[start.min(end)..end]
+    }
+}
+
+impl MemoryObserver for Coverage {
+    fn on_access(&mut self, address: usize, _value: u8, access: Access) {
+        self.record(address, access);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record() {
+        let mut coverage = Coverage::new(0x10);
+        coverage.record(0x2, Access::Execute);
+        coverage.record(0x2, Access::Execute);
+        coverage.record(0x3, Access::Read);
+        coverage.record(0x4, Access::Write);
+
+        assert_eq!(2, coverage.get(0x2).unwrap().executed);
+        assert_eq!(1, coverage.get(0x3).unwrap().read);
+        assert_eq!(1, coverage.get(0x4).unwrap().written);
+        assert_eq!(3, coverage.covered_bytes(0, 0x10));
+    }
+
+    #[test]
+    fn record_out_of_bounds() {
+        let mut coverage = Coverage::new(0x10);
+        coverage.record(0x10, Access::Execute);
+        assert_eq!(0, coverage.covered_bytes(0, 0x10));
+        assert!(coverage.get(0x10).is_none());
+    }
+
+    #[test]
+    fn clear() {
+        let mut coverage = Coverage::new(0x10);
+        coverage.record(0x0, Access::Execute);
+        coverage.clear();
+        assert_eq!(0, coverage.covered_bytes(0, 0x10));
+    }
+
+    #[test]
+    fn annotated_listing() {
+        let memory = vec![0x60, 0x01, 0x12, 0x00, 0xFF, 0x00];
+        let mut coverage = Coverage::new(memory.len());
+        coverage.record(0x0, Access::Execute);
+        coverage.record(0x1, Access::Execute);
+        coverage.record(0x4, Access::Read);
+
+        let expected = "0000: 6001 X-- 1\n0002: 1200 --- 0\n0004: FF00 -R- 0\n";
+        assert_eq!(
+            expected,
+            coverage.annotated_listing(&memory, 0, memory.len())
+        );
+    }
+
+    #[test]
+    fn lcov() {
+        let mut coverage = Coverage::new(0x6);
+        coverage.record(0x2, Access::Execute);
+        coverage.record(0x3, Access::Execute);
+        coverage.record(0x5, Access::Write);
+
+        let expected = "TN:\nSF:test.ch8\nDA:1,0\nDA:2,1\nDA:3,1\nLF:3\nLH:2\nend_of_record\n";
+        assert_eq!(expected, coverage.lcov("test.ch8", 0, 0x6));
+    }
+}
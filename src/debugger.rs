@@ -1,4 +1,9 @@
+use crate::description::RomDescription;
+use crate::instruction::Instruction;
+use crate::memory::Bus;
+use crate::timing::{self, Timing};
 use crate::Chip;
+use std::convert::TryFrom;
 use std::io::{self, Write};
 
 #[derive(Debug)]
@@ -7,10 +12,10 @@ pub enum Command {
     Step,
 
     // Print a region of memory. memory address, length
-    PrintMemory((u8, u16)),
+    PrintMemory((u16, u16)),
 
-    // Print a register
-    PrintRegisters(u8),
+    // Print a register, or every register when none is given
+    PrintRegisters(Option<u8>),
 
     // Print the next 10 instructions from the program counter
     PrintInstructions,
@@ -30,12 +35,18 @@ pub enum Command {
     // Print the delay timer
     PrintDelayTimer,
 
+    // Print what is known about the loaded rom
+    Info,
+
     // Start automatic execution from the current state. The debug session will end when the
     // program exits.
     Handoff,
 
     // Exit the program and the debugging session
     Quit,
+
+    // List the commands
+    Help,
 }
 
 pub trait Debugger {
@@ -45,11 +56,26 @@ pub trait Debugger {
     }
 }
 
-pub struct Chip8Debugger<'a> {
-    chip: &'a Chip,
+pub struct Chip8Debugger {
+    description: Option<RomDescription>,
+    instructions_per_frame: u32,
+
+    // Instructions, or machine cycles with VIP timing, stepped through in the current frame.
+    // None when the next step starts a new frame.
+    frame_spent: Option<u32>,
+}
+
+impl Default for Chip8Debugger {
+    fn default() -> Chip8Debugger {
+        Chip8Debugger {
+            description: None,
+            instructions_per_frame: 1,
+            frame_spent: None,
+        }
+    }
 }
 
-impl<'a> Debugger for Chip8Debugger<'a> {
+impl Debugger for Chip8Debugger {
     fn get_user_input(&self) -> Option<Command> {
         // fail at the first invalid token, or no token
         // only parse first command of a given line
@@ -58,7 +84,7 @@ impl<'a> Debugger for Chip8Debugger<'a> {
 
         let mut user_line = String::new();
         match io::stdin().read_line(&mut user_line) {
-            Ok(bytes_read) => self.get_token(&user_line),
+            Ok(_) => self.get_token(&user_line),
             Err(error) => {
                 println!("error: {}", error);
                 None
@@ -67,18 +93,199 @@ impl<'a> Debugger for Chip8Debugger<'a> {
     }
 }
 
-impl<'a> Chip8Debugger<'a> {
-    pub fn new(chip: &'a Chip) -> Chip8Debugger<'a> {
-        Chip8Debugger { chip }
+impl Chip8Debugger {
+    pub fn new() -> Chip8Debugger {
+        Chip8Debugger::default()
+    }
+
+    /// Set the rom's companion description, shown by the info command
+    pub fn set_description(&mut self, description: Option<RomDescription>) {
+        self.description = description;
+    }
+
+    /// Set how many instructions step runs before the next frame's vertical blank, as run_frame
+    /// does. VIP timing counts machine cycles instead.
+    pub fn set_instructions_per_frame(&mut self, instructions_per_frame: u32) {
+        self.instructions_per_frame = instructions_per_frame.max(1);
     }
 
-    fn get_token(&self, input: &String) -> Option<Command> {
+    fn get_token(&self, input: &str) -> Option<Command> {
         // Parse the first command from the input string.
         let mut tokens = input.split_ascii_whitespace();
-        for token in tokens {
-            println!("{}", token);
+        let command = match tokens.next()? {
+            "step" | "s" => Command::Step,
+            "memory" | "m" => {
+                let address = parse_number(tokens.next()?)?;
+                let length = parse_number(tokens.next().unwrap_or("1"))?;
+                Command::PrintMemory((u16::try_from(address).ok()?, u16::try_from(length).ok()?))
+            }
+            "registers" | "r" => match tokens.next() {
+                Some(register) => {
+                    Command::PrintRegisters(Some(u8::try_from(parse_number(register)?).ok()?))
+                }
+                None => Command::PrintRegisters(None),
+            },
+            "instructions" => Command::PrintInstructions,
+            "keys" => Command::PrintKeys,
+            "stack" => Command::PrintStack,
+            "screen" => Command::PrintScreenBuffer,
+            "sound" => Command::PrintSoundTimer,
+            "delay" => Command::PrintDelayTimer,
+            "info" | "i" => Command::Info,
+            "continue" | "c" => Command::Handoff,
+            "quit" | "q" => Command::Quit,
+            "help" | "h" => Command::Help,
+            _ => return None,
+        };
+        Some(command)
+    }
+
+    /// Print and execute the next instruction. Illegal instructions, and calls and returns which
+    /// would overflow or underflow the stack, are printed with the error instead. Steps are
+    /// grouped into frames like run_frame's: the first step of a frame signals the vertical blank,
+    /// which counts the timers down, and a frame ends once its instructions or machine cycles are
+    /// spent or DXYN waits for the next vertical blank.
+    pub fn step(&mut self, chip: &mut Chip) {
+        let vip = chip.timing() == Timing::Vip;
+        let spent = match self.frame_spent {
+            Some(spent) => spent,
+            None => {
+                chip.vblank();
+                if vip {
+                    chip.cycle_debt
+                } else {
+                    0
+                }
+            }
+        };
+        let (cost, budget) = if vip {
+            (
+                chip.next_instruction_cycles(),
+                timing::MACHINE_CYCLES_PER_FRAME - timing::DISPLAY_CYCLES_PER_FRAME,
+            )
+        } else {
+            (1, self.instructions_per_frame)
+        };
+
+        print!("{}", format_instructions(chip, 1));
+        if let Err(error) = chip.tick() {
+            println!("Failed: {}", error);
         }
-        None
+
+        let spent = spent + cost;
+        self.frame_spent = if spent >= budget || chip.waiting_for_vblank() {
+            if vip {
+                chip.cycle_debt = spent.saturating_sub(budget);
+            }
+            None
+        } else {
+            Some(spent)
+        };
+    }
+
+    /// Print the rom's description and database entry
+    pub fn info(&self, chip: &Chip) {
+        match &self.description {
+            Some(description) => println!("{}", description),
+            None => println!("No description found for this rom"),
+        }
+        if let Some(info) = chip.rom_info() {
+            println!("sha1: {}", info.sha1);
+            for hint in info.keys.iter() {
+                println!("  {}", hint);
+            }
+        }
+    }
+
+    /// Print length bytes of memory from address, 16 to a line. The address must be in memory,
+    /// and the bytes stop at the end of memory.
+    pub fn print_memory(&self, chip: &Chip, address: u16, length: u16) {
+        match format_memory(chip.memory().as_slice(), address, length) {
+            Ok(dump) => print!("{}", dump),
+            Err(error) => println!("{}", error),
+        }
+    }
+
+    /// Print one register, or all of them with the index register and program counter
+    pub fn print_registers(&self, chip: &Chip, register: Option<u8>) {
+        match register {
+            Some(register) if usize::from(register) < chip.registers.len() => {
+                println!(
+                    "V{:X}: {:02X}",
+                    register,
+                    chip.registers[usize::from(register)]
+                )
+            }
+            Some(register) => println!("There's no register V{:X}, they go up to VF", register),
+            None => {
+                for (row, registers) in chip.registers.chunks(8).enumerate() {
+                    let registers: Vec<String> = registers
+                        .iter()
+                        .enumerate()
+                        .map(|(index, value)| format!("V{:X}: {:02X}", row * 8 + index, value))
+                        .collect();
+                    println!("{}", registers.join("  "));
+                }
+                println!("I: {:04X}  PC: {:04X}", chip.address, chip.program_counter);
+            }
+        }
+    }
+
+    /// Print the next 10 instructions from the program counter
+    pub fn print_instructions(&self, chip: &Chip) {
+        print!("{}", format_instructions(chip, 10));
+    }
+
+    /// Print the keys held down
+    pub fn print_keys(&self, chip: &Chip) {
+        let pressed: Vec<String> = (0..chip.keys.len())
+            .filter(|&key| chip.keys[key].is_pressed())
+            .map(|key| format!("{:X}", key))
+            .collect();
+        if pressed.is_empty() {
+            println!("No keys pressed");
+        } else {
+            println!("Pressed: {}", pressed.join(" "));
+        }
+    }
+
+    /// Print the return addresses on the stack, the most recent call first
+    pub fn print_stack(&self, chip: &Chip) {
+        if chip.stack.data.is_empty() {
+            println!("The stack is empty");
+        }
+        for (depth, address) in chip.stack.data.iter().rev().enumerate() {
+            println!("{:2}: {:04X}", depth, address);
+        }
+    }
+
+    /// Print the screen, one character per pixel
+    pub fn print_screen(&self, chip: &Chip) {
+        print!("{}", chip.screen_buffer.to_text());
+    }
+
+    pub fn print_sound_timer(&self, chip: &Chip) {
+        println!("sound timer: {}", chip.sound_timer);
+    }
+
+    pub fn print_delay_timer(&self, chip: &Chip) {
+        println!("delay timer: {}", chip.delay_timer);
+    }
+
+    pub fn help(&self) {
+        println!("step (s)                    execute the next instruction");
+        println!("memory (m) address [length] print memory, e.g. m 0x200 16");
+        println!("registers (r) [register]    print a register, e.g. r 0xF, or all of them");
+        println!("instructions                print the next 10 instructions");
+        println!("keys                        print the keys held down");
+        println!("stack                       print the stack");
+        println!("screen                      print the screen");
+        println!("sound                       print the sound timer");
+        println!("delay                       print the delay timer");
+        println!("info (i)                    describe the rom");
+        println!("continue (c)                run the rom in a window");
+        println!("quit (q)                    exit the debugger");
+        println!("help (h)                    show this list");
     }
 
     pub fn welcome(&self) {
@@ -86,3 +293,163 @@ impl<'a> Chip8Debugger<'a> {
         println!("use 'help' to show available commands \n");
     }
 }
+
+/// Format bytes of memory as lines of an address and up to 16 bytes
+fn format_memory(memory: &[u8], address: u16, length: u16) -> Result<String, io::Error> {
+    let start = usize::from(address);
+    if start >= memory.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "Address {:#05X} is outside of {} bytes of memory",
+                address,
+                memory.len()
+            ),
+        ));
+    }
+
+    let end = (start + usize::from(length)).min(memory.len());
+    let mut dump = String::new();
+    for (line, bytes) in memory[start..end].chunks(16).enumerate() {
+        let bytes: Vec<String> = bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+        dump.push_str(&format!("{:04X}: {}\n", start + line * 16, bytes.join(" ")));
+    }
+    Ok(dump)
+}
+
+/// Format count instructions from the program counter as lines of an address, the opcode and
+/// the instruction. Addresses wrap around the end of memory, as the program counter does.
+fn format_instructions(chip: &Chip, count: usize) -> String {
+    let memory = chip.memory();
+    let mut listing = String::new();
+    let mut address = usize::from(chip.program_counter) % memory.len();
+    for _ in 0..count {
+        let bytes = [address, (address + 1) % memory.len()].map(|address| memory.peek(address));
+        let line = match bytes {
+            [Ok(high), Ok(low)] => {
+                let opcode = u16::from_be_bytes([high, low]);
+                match Instruction::decode(opcode) {
+                    Some(instruction) => {
+                        format!("{:04X}: {:04X}  {}", address, opcode, instruction)
+                    }
+                    None => format!("{:04X}: {:04X}  illegal", address, opcode),
+                }
+            }
+            _ => format!("{:04X}: unreadable", address),
+        };
+        listing.push_str(&line);
+        listing.push('\n');
+        address = (address + 2) % memory.len();
+    }
+    listing
+}
+
+/// Parse a decimal or 0x prefixed hexadecimal number
+fn parse_number(token: &str) -> Option<u32> {
+    match token.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => token.parse().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn get_token() {
+        let debugger = Chip8Debugger::new();
+        assert!(matches!(debugger.get_token("info\n"), Some(Command::Info)));
+        assert!(matches!(debugger.get_token("  s "), Some(Command::Step)));
+        assert!(matches!(
+            debugger.get_token("r 0xF"),
+            Some(Command::PrintRegisters(Some(0xF)))
+        ));
+        assert!(matches!(
+            debugger.get_token("memory 16 0x20"),
+            Some(Command::PrintMemory((16, 0x20)))
+        ));
+        assert!(matches!(
+            debugger.get_token("m 0x200"),
+            Some(Command::PrintMemory((0x200, 1)))
+        ));
+        assert!(matches!(debugger.get_token("help"), Some(Command::Help)));
+        assert!(debugger.get_token("m 0x10000").is_none());
+        assert!(debugger.get_token("").is_none());
+        assert!(debugger.get_token("jump").is_none());
+        assert!(matches!(
+            debugger.get_token("registers"),
+            Some(Command::PrintRegisters(None))
+        ));
+        assert!(matches!(
+            debugger.get_token("stack"),
+            Some(Command::PrintStack)
+        ));
+        assert!(debugger.get_token("r 256").is_none());
+    }
+
+    #[test]
+    fn format_memory() {
+        let memory: Vec<u8> = (0..0x20).collect();
+        assert_eq!(
+            "0010: 10 11 12\n",
+            super::format_memory(&memory, 0x10, 3).unwrap()
+        );
+        assert_eq!(
+            "0008: 08 09 0A 0B 0C 0D 0E 0F 10 11 12 13 14 15 16 17\n0018: 18 19 1A 1B 1C 1D 1E 1F\n",
+            super::format_memory(&memory, 0x8, 0x100).unwrap()
+        );
+        assert!(super::format_memory(&memory, 0x20, 1).is_err());
+    }
+
+    #[test]
+    fn step() {
+        let mut chip = Chip::default();
+        chip.load_rom_bytes(&[0x60, 0x55, 0x00, 0xEE]).unwrap();
+        let mut debugger = Chip8Debugger::new();
+
+        debugger.step(&mut chip);
+        assert_eq!(0x55, chip.registers[0]);
+        assert_eq!(0x202, chip.program_counter);
+
+        // Returning with an empty stack isn't executed
+        debugger.step(&mut chip);
+        assert_eq!(0x202, chip.program_counter);
+    }
+
+    #[test]
+    fn step_counts_the_timers_down_once_per_frame() {
+        let mut chip = Chip::default();
+        chip.load_rom_bytes(&[0x60, 0x01, 0x60, 0x02, 0x60, 0x03, 0x60, 0x04])
+            .unwrap();
+        chip.delay_timer = 5;
+        let mut debugger = Chip8Debugger::new();
+        debugger.set_instructions_per_frame(2);
+
+        let delays: Vec<u8> = (0..4)
+            .map(|_| {
+                debugger.step(&mut chip);
+                chip.delay_timer
+            })
+            .collect();
+        assert_eq!(vec![4, 4, 3, 3], delays);
+    }
+
+    #[test]
+    fn format_instructions() {
+        let mut chip = Chip::default();
+        chip.load_rom_bytes(&[0x60, 0x55, 0xFF, 0xFF]).unwrap();
+        assert_eq!(
+            format!(
+                "0200: 6055  {}\n0202: FFFF  illegal\n",
+                Instruction::decode(0x6055).unwrap()
+            ),
+            super::format_instructions(&chip, 2)
+        );
+
+        // The listing wraps around the end of memory
+        chip.program_counter = 0xFFE;
+        assert!(super::format_instructions(&chip, 2).starts_with("0FFE: 0000  "));
+        assert!(super::format_instructions(&chip, 2).contains("\n0000: "));
+    }
+}
@@ -0,0 +1,336 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::vec::Vec;
+
+/// Extensions tried, in order, when looking for the rom matching a description
+pub const ROM_EXTENSIONS: [&str; 6] = ["ch8", "c8", "rom", "bin", "gz", "zip"];
+
+/// Extensions of compressed roms, which can follow another rom extension, e.g. "Maze.ch8.gz"
+const COMPRESSED_EXTENSIONS: [&str; 2] = ["gz", "zip"];
+
+/// The contents of a rom's companion description file, e.g.
+/// "Maze (alt) [David Winter, 199x].txt"
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RomDescription {
+    pub title: String,
+    pub author: Option<String>,
+    pub year: Option<String>,
+
+    // Anything else in parentheses in the file name, e.g. "alt"
+    pub variant: Option<String>,
+
+    // "Field : value" lines from the body of the file
+    pub fields: Vec<(String, String)>,
+
+    // Lines of the body which describe key usage
+    pub keys: Vec<String>,
+
+    // The full body of the file
+    pub notes: String,
+}
+
+impl RomDescription {
+    /// Parse a description from its file name (without extension) and contents
+    pub fn parse(stem: &str, contents: &str) -> RomDescription {
+        let mut description = RomDescription::parse_name(stem);
+        description.notes = contents.trim_end().to_string();
+
+        for line in contents.lines() {
+            if let Some((field, value)) = line.split_once(':') {
+                let (field, value) = (field.trim(), value.trim());
+                let is_field = !field.is_empty()
+                    && !value.is_empty()
+                    && field.len() <= 20
+                    && field.chars().all(|c| c.is_alphanumeric() || c == ' ');
+                if is_field {
+                    description
+                        .fields
+                        .push((field.to_string(), value.to_string()));
+                }
+            }
+
+            let lower = line.to_ascii_lowercase();
+            if lower.contains("key") || lower.contains("press") {
+                description.keys.push(line.trim().to_string());
+            }
+        }
+        description
+    }
+
+    /// Parse the file naming convention "Title (variant or year) [Author, Year]"
+    fn parse_name(stem: &str) -> RomDescription {
+        let title_end = stem.find(['(', '[']).unwrap_or(stem.len());
+        let mut description = RomDescription {
+            title: stem[..title_end].trim().to_string(),
+            ..Default::default()
+        };
+
+        for group in RomDescription::groups(&stem[title_end..], '(', ')') {
+            if is_year(group) {
+                description.year = Some(group.to_string());
+            } else {
+                description.variant = Some(group.to_string());
+            }
+        }
+
+        if let Some(group) = RomDescription::groups(&stem[title_end..], '[', ']').first() {
+            for part in group.split(',').map(str::trim) {
+                if is_year(part) && description.author.is_some() {
+                    description.year = Some(part.to_string());
+                } else if description.author.is_none() && !part.is_empty() {
+                    description.author = Some(part.to_string());
+                }
+            }
+        }
+        description
+    }
+
+    /// Get the contents of every open ... close group in a string
+    fn groups(text: &str, open: char, close: char) -> Vec<&str> {
+        let mut groups = Vec::new();
+        let mut rest = text;
+        while let Some(start) = rest.find(open) {
+            match rest[start..].find(close) {
+                Some(end) => {
+                    groups.push(rest[start + 1..start + end].trim());
+                    rest = &rest[start + end + 1..];
+                }
+                None => break,
+            }
+        }
+        groups
+    }
+
+    /// Load a description file
+    pub fn load_file<P: AsRef<Path>>(path: P) -> Result<RomDescription, io::Error> {
+        let path = path.as_ref();
+        let stem = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Invalid file name"))?;
+
+        // Descriptions aren't always utf-8, so don't fail on the odd latin-1 character
+        let contents = fs::read(path)?;
+        Ok(RomDescription::parse(
+            stem,
+            &String::from_utf8_lossy(&contents),
+        ))
+    }
+
+    /// Find and load the description next to a rom, e.g. "Maze.txt" for "Maze.ch8"
+    pub fn for_rom<P: AsRef<Path>>(rom: P) -> Option<RomDescription> {
+        let path = RomDescription::path_for_rom(rom);
+        if path.is_file() {
+            RomDescription::load_file(path).ok()
+        } else {
+            None
+        }
+    }
+
+    /// The path of a rom's description, with every rom and compression extension replaced by
+    /// .txt, e.g. "Maze.txt" for "Maze.ch8.gz"
+    pub fn path_for_rom<P: AsRef<Path>>(rom: P) -> PathBuf {
+        let mut path = rom.as_ref().to_path_buf();
+        while has_rom_extension(&path) {
+            path.set_extension("");
+        }
+        let mut name = path.file_name().unwrap_or_default().to_os_string();
+        name.push(".txt");
+        path.with_file_name(name)
+    }
+
+    /// Find the rom described by a description file, e.g. "Maze.ch8" or "Maze.ch8.gz" for
+    /// "Maze.txt"
+    pub fn rom_for<P: AsRef<Path>>(description: P) -> Option<PathBuf> {
+        let compressed = ROM_EXTENSIONS
+            .iter()
+            .filter(|extension| !COMPRESSED_EXTENSIONS.contains(extension))
+            .flat_map(|extension| {
+                COMPRESSED_EXTENSIONS
+                    .iter()
+                    .map(move |compression| format!("{}.{}", extension, compression))
+            });
+        ROM_EXTENSIONS
+            .iter()
+            .map(|extension| extension.to_string())
+            .chain(compressed)
+            .map(|extension| description.as_ref().with_extension(extension))
+            .find(|path| path.is_file())
+    }
+
+    /// A single line summary, e.g. for a window title
+    pub fn summary(&self) -> String {
+        let mut summary = self.title.clone();
+        if let Some(variant) = &self.variant {
+            summary.push_str(&format!(" ({})", variant));
+        }
+        if let Some(author) = &self.author {
+            summary.push_str(&format!(" by {}", author));
+        }
+        if let Some(year) = &self.year {
+            summary.push_str(&format!(", {}", year));
+        }
+        summary
+    }
+}
+
+impl fmt::Display for RomDescription {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", self.summary())?;
+        if !self.keys.is_empty() {
+            writeln!(f, "\nKeys:")?;
+            for key in self.keys.iter() {
+                writeln!(f, "  {}", key)?;
+            }
+        }
+        if !self.notes.is_empty() {
+            writeln!(f, "\n{}", self.notes)?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether a path ends in one of the rom extensions, ignoring case
+pub fn has_rom_extension(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| ROM_EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+/// Years are 4 characters of digits, with unknown digits written as x, e.g. "199x"
+fn is_year(text: &str) -> bool {
+    text.len() == 4
+        && text.starts_with(|c: char| c.is_ascii_digit())
+        && text.chars().all(|c| c.is_ascii_digit() || c == 'x')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_author_and_year() {
+        let d = RomDescription::parse("Maze [David Winter, 199x]", "");
+        assert_eq!("Maze", d.title);
+        assert_eq!(Some("David Winter".to_string()), d.author);
+        assert_eq!(Some("199x".to_string()), d.year);
+        assert_eq!(None, d.variant);
+    }
+
+    #[test]
+    fn parse_variant() {
+        let d = RomDescription::parse("Maze (alt) [David Winter, 199x]", "");
+        assert_eq!("Maze", d.title);
+        assert_eq!(Some("alt".to_string()), d.variant);
+        assert_eq!("Maze (alt) by David Winter, 199x", d.summary());
+    }
+
+    #[test]
+    fn parse_year_in_parentheses() {
+        let d = RomDescription::parse("Trip8 Demo (2008) [Revival Studios]", "");
+        assert_eq!("Trip8 Demo", d.title);
+        assert_eq!(Some("Revival Studios".to_string()), d.author);
+        assert_eq!(Some("2008".to_string()), d.year);
+    }
+
+    #[test]
+    fn parse_plain_name() {
+        let d = RomDescription::parse("Pong", "");
+        assert_eq!("Pong", d.title);
+        assert_eq!(None, d.author);
+        assert_eq!("Pong", d.summary());
+    }
+
+    #[test]
+    fn parse_contents() {
+        let contents = "Title\t\t:\tTrip8\nGenre : Demo\nsee www.example.com:80\n\
+                        Press 5 to start, keys 4 and 6 move\n";
+        let d = RomDescription::parse("Trip8", contents);
+        assert_eq!(
+            vec![
+                ("Title".to_string(), "Trip8".to_string()),
+                ("Genre".to_string(), "Demo".to_string())
+            ],
+            d.fields
+        );
+        assert_eq!(
+            vec!["Press 5 to start, keys 4 and 6 move".to_string()],
+            d.keys
+        );
+        assert!(d.notes.starts_with("Title"));
+    }
+
+    #[test]
+    fn load_shipped_descriptions() {
+        let d = RomDescription::load_file("roms/Zero Demo [zeroZshadow, 2007].txt").unwrap();
+        assert_eq!("Zero Demo", d.title);
+        assert_eq!(Some("zeroZshadow".to_string()), d.author);
+        assert!(d.notes.contains("bouncing sprites"));
+
+        let d = RomDescription::load_file("roms/Trip8 Demo (2008) [Revival Studios].txt").unwrap();
+        assert!(d.fields.contains(&(
+            "Author".to_string(),
+            "Martijn Wenting / Revival Studios".to_string()
+        )));
+    }
+
+    #[test]
+    fn for_rom() {
+        let dir = std::env::temp_dir().join("chip8_rs_description_for_rom");
+        fs::create_dir_all(&dir).unwrap();
+        let rom = dir.join("Pong [Paul Vervalin, 1990].ch8");
+        fs::write(&rom, [0x12, 0x00]).unwrap();
+        fs::write(
+            dir.join("Pong [Paul Vervalin, 1990].txt"),
+            "Use keys 1 and 4",
+        )
+        .unwrap();
+
+        let d = RomDescription::for_rom(&rom).unwrap();
+        assert_eq!(Some("Paul Vervalin".to_string()), d.author);
+        assert_eq!(
+            Some(rom),
+            RomDescription::rom_for(dir.join("Pong [Paul Vervalin, 1990].txt"))
+        );
+        assert!(RomDescription::for_rom(dir.join("missing.ch8")).is_none());
+    }
+
+    #[test]
+    fn for_compressed_rom() {
+        let dir = std::env::temp_dir().join("chip8_rs_description_for_compressed_rom");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("Maze [David Winter, 199x].txt"), "A maze").unwrap();
+        fs::write(dir.join("Pong (1 player).txt"), "Use keys 1 and 4").unwrap();
+
+        let gz = dir.join("Maze [David Winter, 199x].ch8.gz");
+        fs::write(&gz, [0x1F, 0x8B]).unwrap();
+        let d = RomDescription::for_rom(&gz).unwrap();
+        assert_eq!(Some("David Winter".to_string()), d.author);
+        assert_eq!(
+            Some(gz),
+            RomDescription::rom_for(dir.join("Maze [David Winter, 199x].txt"))
+        );
+
+        let zip = dir.join("Pong (1 player).ZIP");
+        fs::write(&zip, [0x50, 0x4B]).unwrap();
+        assert_eq!(
+            Some("1 player".to_string()),
+            RomDescription::for_rom(&zip).unwrap().variant
+        );
+    }
+
+    #[test]
+    fn path_for_rom() {
+        let path = |rom| RomDescription::path_for_rom(rom);
+        assert_eq!(PathBuf::from("roms/Maze.txt"), path("roms/Maze.ch8"));
+        assert_eq!(PathBuf::from("roms/Maze.txt"), path("roms/Maze.ch8.gz"));
+        assert_eq!(PathBuf::from("Maze.txt"), path("Maze.c8.zip"));
+        assert_eq!(PathBuf::from("Maze.txt"), path("Maze.zip"));
+        assert_eq!(PathBuf::from("Maze v1.2.txt"), path("Maze v1.2.bin"));
+        assert_eq!(PathBuf::from("Maze.txt"), path("Maze"));
+    }
+}
@@ -0,0 +1,187 @@
+use std::io::{Error, ErrorKind};
+use std::str::FromStr;
+use std::vec::Vec;
+
+/// The number of frames an erased pixel takes to fade out when decay is chosen without a count
+const DEFAULT_DECAY_FRAMES: u8 = 3;
+
+/// How frames are blended before they're displayed. Chip-8 games erase sprites by drawing
+/// them again, so sprites which move every frame are only visible every other frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum FilterMode {
+    // Show each frame as it is
+    #[default]
+    Off,
+
+    // Show a pixel if it's lit in this frame or the last one
+    Or,
+
+    // Fade erased pixels out over a number of frames, like the phosphor of a CRT
+    Decay(u8),
+}
+
+/// A filtered frame, ready to render
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Output<'a> {
+    // Byte packed pixels, one bit per pixel
+    Bits(&'a [u8]),
+
+    // One brightness per pixel, from 0 for the background to the maximum for the foreground
+    Levels(&'a [u8], u8),
+}
+
+impl FromStr for FilterMode {
+    type Err = Error;
+
+    /// Parse "off", "or", "decay" or "decay:<frames>"
+    fn from_str(text: &str) -> Result<FilterMode, Error> {
+        let text = text.to_ascii_lowercase();
+        let (name, frames) = match text.split_once(':') {
+            Some((name, frames)) => (name, Some(frames)),
+            None => (text.as_str(), None),
+        };
+        match (name, frames) {
+            ("off" | "none", None) => Ok(FilterMode::Off),
+            ("or" | "blend", None) => Ok(FilterMode::Or),
+            ("decay", None) => Ok(FilterMode::Decay(DEFAULT_DECAY_FRAMES)),
+            ("decay", Some(frames)) => match frames.parse() {
+                Ok(frames) if frames > 0 => Ok(FilterMode::Decay(frames)),
+                _ => Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("'{}' is not a number of frames from 1 to 255", frames),
+                )),
+            },
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Unknown filter '{}', expected off, or, decay or decay:<frames>",
+                    text
+                ),
+            )),
+        }
+    }
+}
+
+/// Blends byte packed screen buffers, one bit per pixel, as they're displayed
+#[derive(Clone, Debug, Default)]
+pub struct DisplayFilter {
+    mode: FilterMode,
+    previous: Vec<u8>,
+
+    // The brightness of each pixel, which falls by one every frame after it's erased, used by
+    // Decay
+    levels: Vec<u8>,
+    output: Vec<u8>,
+}
+
+impl DisplayFilter {
+    pub fn new(mode: FilterMode) -> DisplayFilter {
+        DisplayFilter {
+            mode,
+            ..Default::default()
+        }
+    }
+
+    pub fn mode(&self) -> FilterMode {
+        self.mode
+    }
+
+    /// Forget earlier frames, e.g. after a reset
+    pub fn clear(&mut self) {
+        self.previous.clear();
+        self.levels.clear();
+    }
+
+    /// Blend a frame with the frames before it, returning what to display
+    pub fn apply<'a>(&'a mut self, frame: &'a [u8]) -> Output<'a> {
+        match self.mode {
+            FilterMode::Off => Output::Bits(frame),
+            FilterMode::Or => {
+                if self.previous.len() != frame.len() {
+                    self.previous = frame.to_vec();
+                }
+                self.output.clear();
+                self.output
+                    .extend(frame.iter().zip(self.previous.iter()).map(|(a, b)| a | b));
+                self.previous.copy_from_slice(frame);
+                Output::Bits(&self.output)
+            }
+            FilterMode::Decay(frames) => {
+                if self.levels.len() != frame.len() * 8 {
+                    self.levels = vec![0; frame.len() * 8];
+                }
+                for (byte, levels) in frame.iter().zip(self.levels.chunks_mut(8)) {
+                    for (bit, level) in levels.iter_mut().enumerate() {
+                        if byte & (0x80 >> bit) != 0 {
+                            *level = frames;
+                        } else {
+                            *level = level.saturating_sub(1);
+                        }
+                    }
+                }
+                Output::Levels(&self.levels, frames)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!(FilterMode::Off, "off".parse().unwrap());
+        assert_eq!(FilterMode::Or, "OR".parse().unwrap());
+        assert_eq!(FilterMode::Decay(3), "decay".parse().unwrap());
+        assert_eq!(FilterMode::Decay(8), "decay:8".parse().unwrap());
+        assert!("decay:0".parse::<FilterMode>().is_err());
+        assert!("decay:x".parse::<FilterMode>().is_err());
+        assert!("or:2".parse::<FilterMode>().is_err());
+        assert!("blur".parse::<FilterMode>().is_err());
+    }
+
+    #[test]
+    fn off() {
+        let mut filter = DisplayFilter::new(FilterMode::Off);
+        assert_eq!(Output::Bits(&[0x80]), filter.apply(&[0x80]));
+        assert_eq!(Output::Bits(&[0x00]), filter.apply(&[0x00]));
+    }
+
+    #[test]
+    fn or_last_two_frames() {
+        let mut filter = DisplayFilter::new(FilterMode::Or);
+        assert_eq!(Output::Bits(&[0x80, 0x00]), filter.apply(&[0x80, 0x00]));
+        assert_eq!(Output::Bits(&[0x80, 0x01]), filter.apply(&[0x00, 0x01]));
+        assert_eq!(Output::Bits(&[0x00, 0x01]), filter.apply(&[0x00, 0x00]));
+        assert_eq!(Output::Bits(&[0x00, 0x00]), filter.apply(&[0x00, 0x00]));
+    }
+
+    #[test]
+    fn decay() {
+        let mut filter = DisplayFilter::new(FilterMode::Decay(3));
+        let levels = |first, last| {
+            let mut levels = [0; 8];
+            levels[0] = first;
+            levels[7] = last;
+            levels
+        };
+        assert_eq!(Output::Levels(&levels(3, 3), 3), filter.apply(&[0x81]));
+        assert_eq!(Output::Levels(&levels(2, 3), 3), filter.apply(&[0x01]));
+        assert_eq!(Output::Levels(&levels(1, 3), 3), filter.apply(&[0x01]));
+        assert_eq!(Output::Levels(&levels(0, 3), 3), filter.apply(&[0x01]));
+
+        // A pixel lit again is at full brightness
+        assert_eq!(Output::Levels(&levels(3, 2), 3), filter.apply(&[0x80]));
+        assert_eq!(Output::Levels(&levels(2, 1), 3), filter.apply(&[0x00]));
+        assert_eq!(Output::Levels(&levels(1, 0), 3), filter.apply(&[0x00]));
+    }
+
+    #[test]
+    fn clear() {
+        let mut filter = DisplayFilter::new(FilterMode::Or);
+        filter.apply(&[0xFF]);
+        filter.clear();
+        assert_eq!(Output::Bits(&[0x00]), filter.apply(&[0x00]));
+    }
+}
@@ -0,0 +1,232 @@
+use std::slice::Chunks;
+use std::vec::Vec;
+
+/// What happens to the parts of a sprite drawn past the edge of the screen
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Edge {
+    // Pixels past the edge reappear on the opposite side
+    Wrap,
+
+    // Pixels past the edge are not drawn
+    Clip,
+}
+
+/// A monochrome screen, packed 8 pixels to a byte with the leftmost pixel in the most
+/// significant bit. Rows are stored top to bottom.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FrameBuffer {
+    width: usize,
+    height: usize,
+    bytes: Vec<u8>,
+}
+
+impl FrameBuffer {
+    /// Create a blank screen. The width must be a multiple of 8.
+    pub fn new(width: usize, height: usize) -> FrameBuffer {
+        assert!(
+            width.is_multiple_of(8),
+            "Screen width {} is not a multiple of 8",
+            width
+        );
+        FrameBuffer {
+            width,
+            height,
+            bytes: vec![0; width * height / 8],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The packed pixels of the whole screen
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        &mut self.bytes
+    }
+
+    /// The packed pixels of a single row
+    pub fn row(&self, y: usize) -> &[u8] {
+        let bytes_per_row = self.width / 8;
+        &self.bytes[y * bytes_per_row..(y + 1) * bytes_per_row]
+    }
+
+    /// Every row from top to bottom
+    pub fn rows(&self) -> Chunks<'_, u8> {
+        self.bytes.chunks(self.width / 8)
+    }
+
+    /// The screen as text, one line per row with # for a lit pixel and . for a dark one
+    pub fn to_text(&self) -> String {
+        let mut text = String::with_capacity((self.width + 1) * self.height);
+        for row in self.rows() {
+            for byte in row.iter() {
+                for bit in (0..8).rev() {
+                    text.push(if (byte >> bit) & 0x1 == 1 { '#' } else { '.' });
+                }
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    /// Turn every pixel off
+    pub fn clear(&mut self) {
+        self.bytes.iter_mut().for_each(|byte| *byte = 0);
+    }
+
+    /// Find the byte holding a pixel and the pixel's mask within it. Panics if the pixel is off
+    /// the screen.
+    fn locate(&self, x: usize, y: usize) -> (usize, u8) {
+        assert!(
+            x < self.width && y < self.height,
+            "Pixel ({}, {}) is off the screen",
+            x,
+            y
+        );
+        ((y * self.width + x) / 8, 0x80 >> (x % 8))
+    }
+
+    pub fn get_pixel(&self, x: usize, y: usize) -> bool {
+        let (index, mask) = self.locate(x, y);
+        self.bytes[index] & mask != 0
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, on: bool) {
+        let (index, mask) = self.locate(x, y);
+        if on {
+            self.bytes[index] |= mask;
+        } else {
+            self.bytes[index] &= !mask;
+        }
+    }
+
+    /// Flip a pixel, returning true if it was on and has been erased
+    pub fn xor_pixel(&mut self, x: usize, y: usize) -> bool {
+        let (index, mask) = self.locate(x, y);
+        let erased = self.bytes[index] & mask != 0;
+        self.bytes[index] ^= mask;
+        erased
+    }
+
+    /// XOR a sprite onto the screen, one byte per row. The starting position always wraps onto
+    /// the screen, the edge decides what happens to the rest of the sprite. Returns the number of
+    /// rows which erased at least one pixel, so 0 means there was no collision.
+    pub fn draw_sprite(&mut self, x: usize, y: usize, sprite: &[u8], edge: Edge) -> usize {
+        let x = x % self.width;
+        let y = y % self.height;
+        let mut collisions = 0;
+        for (row, byte) in sprite.iter().enumerate() {
+            let mut py = y + row;
+            if py >= self.height {
+                match edge {
+                    Edge::Wrap => py %= self.height,
+                    Edge::Clip => break,
+                }
+            }
+
+            let mut collided = false;
+            for column in (0..8).filter(|column| byte & (0x80 >> column) != 0) {
+                let mut px = x + column;
+                if px >= self.width {
+                    match edge {
+                        Edge::Wrap => px %= self.width,
+                        Edge::Clip => break,
+                    }
+                }
+                collided |= self.xor_pixel(px, py);
+            }
+            if collided {
+                collisions += 1;
+            }
+        }
+        collisions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pixels() {
+        let mut screen = FrameBuffer::new(16, 2);
+        screen.set_pixel(9, 1, true);
+        assert!(screen.get_pixel(9, 1));
+        assert_eq!(&[0x00, 0x40], screen.row(1));
+        assert_eq!(&[0, 0, 0, 0x40], screen.as_bytes());
+
+        assert!(screen.xor_pixel(9, 1));
+        assert!(!screen.get_pixel(9, 1));
+        assert!(!screen.xor_pixel(0, 0));
+        assert_eq!(0x80, screen.as_bytes()[0]);
+
+        screen.set_pixel(0, 0, false);
+        assert!(screen.as_bytes().iter().all(|byte| *byte == 0));
+    }
+
+    #[test]
+    #[should_panic]
+    fn pixel_off_the_screen() {
+        FrameBuffer::new(8, 1).get_pixel(8, 0);
+    }
+
+    #[test]
+    fn to_text() {
+        let mut screen = FrameBuffer::new(8, 2);
+        screen.set_pixel(0, 0, true);
+        screen.set_pixel(7, 1, true);
+        assert_eq!("#.......\n.......#\n", screen.to_text());
+    }
+
+    #[test]
+    fn clear_keeps_the_size() {
+        let mut screen = FrameBuffer::new(64, 32);
+        screen.set_pixel(63, 31, true);
+        screen.clear();
+        assert_eq!(256, screen.as_bytes().len());
+        assert!(!screen.get_pixel(63, 31));
+    }
+
+    #[test]
+    fn draw_unaligned() {
+        let mut screen = FrameBuffer::new(16, 2);
+        assert_eq!(0, screen.draw_sprite(4, 0, &[0xFF, 0x81], Edge::Clip));
+        assert_eq!(&[0x0F, 0xF0], screen.row(0));
+        assert_eq!(&[0x08, 0x10], screen.row(1));
+
+        // Drawing the same sprite erases it and reports every row which collided
+        assert_eq!(2, screen.draw_sprite(4, 0, &[0xFF, 0x81], Edge::Clip));
+        assert!(screen.as_bytes().iter().all(|byte| *byte == 0));
+    }
+
+    #[test]
+    fn draw_clipped() {
+        let mut screen = FrameBuffer::new(16, 2);
+        screen.draw_sprite(12, 1, &[0xFF, 0xFF], Edge::Clip);
+        assert_eq!(&[0x00, 0x00], screen.row(0));
+        assert_eq!(&[0x00, 0x0F], screen.row(1));
+    }
+
+    #[test]
+    fn draw_wrapped() {
+        let mut screen = FrameBuffer::new(16, 2);
+        screen.draw_sprite(12, 1, &[0xFF, 0xFF], Edge::Wrap);
+        assert_eq!(&[0xF0, 0x0F], screen.row(0));
+        assert_eq!(&[0xF0, 0x0F], screen.row(1));
+    }
+
+    #[test]
+    fn start_position_wraps() {
+        let mut screen = FrameBuffer::new(16, 2);
+        screen.draw_sprite(17, 3, &[0x80], Edge::Clip);
+        assert!(screen.get_pixel(1, 1));
+    }
+}
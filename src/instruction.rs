@@ -0,0 +1,482 @@
+use std::fmt;
+use std::io::{Error, ErrorKind};
+use std::str::FromStr;
+use std::vec::Vec;
+
+/// A decoded chip-8 instruction. Registers are register numbers, addresses are 12 bits and
+/// constants are 8 bits. Mnemonics follow Cowgod's chip-8 technical reference.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Instruction {
+    // 0NNN Call a machine code routine, ignored by most interpreters
+    Sys(u16),
+
+    // 00E0 Clear the screen
+    Cls,
+
+    // 00EE Return from a subroutine
+    Ret,
+
+    // 1NNN Jump to NNN
+    Jp(u16),
+
+    // 2NNN Call the subroutine at NNN
+    Call(u16),
+
+    // 3XNN Skip if vx == NN
+    SeByte(u8, u8),
+
+    // 4XNN Skip if vx != NN
+    SneByte(u8, u8),
+
+    // 5XY0 Skip if vx == vy
+    SeReg(u8, u8),
+
+    // 6XNN vx = NN
+    LdByte(u8, u8),
+
+    // 7XNN vx += NN
+    AddByte(u8, u8),
+
+    // 8XY0 vx = vy
+    LdReg(u8, u8),
+
+    // 8XY1 vx |= vy
+    Or(u8, u8),
+
+    // 8XY2 vx &= vy
+    And(u8, u8),
+
+    // 8XY3 vx ^= vy
+    Xor(u8, u8),
+
+    // 8XY4 vx += vy, vf = carry
+    AddReg(u8, u8),
+
+    // 8XY5 vx -= vy, vf = not borrow
+    Sub(u8, u8),
+
+    // 8XY6 vx >>= 1, vf = lsb
+    Shr(u8, u8),
+
+    // 8XY7 vx = vy - vx, vf = not borrow
+    Subn(u8, u8),
+
+    // 8XYE vx <<= 1, vf = msb
+    Shl(u8, u8),
+
+    // 9XY0 Skip if vx != vy
+    SneReg(u8, u8),
+
+    // ANNN I = NNN
+    LdI(u16),
+
+    // BNNN Jump to NNN + v0
+    JpV0(u16),
+
+    // CXNN vx = random & NN
+    Rnd(u8, u8),
+
+    // DXYN Draw an N byte sprite at vx, vy
+    Drw(u8, u8, u8),
+
+    // EX9E Skip if the key in vx is pressed
+    Skp(u8),
+
+    // EXA1 Skip if the key in vx is not pressed
+    Sknp(u8),
+
+    // FX07 vx = delay timer
+    LdVxDt(u8),
+
+    // FX0A Wait for a key press, store it in vx
+    LdVxK(u8),
+
+    // FX15 delay timer = vx
+    LdDtVx(u8),
+
+    // FX18 sound timer = vx
+    LdStVx(u8),
+
+    // FX1E I += vx
+    AddI(u8),
+
+    // FX29 I = font sprite for vx
+    LdF(u8),
+
+    // FX33 Store the binary coded decimal of vx at I
+    LdB(u8),
+
+    // FX55 Store v0 - vx at I
+    LdIVx(u8),
+
+    // FX65 Load v0 - vx from I
+    LdVxI(u8),
+}
+
+impl Instruction {
+    /// Decode an opcode, or None if it isn't a valid instruction
+    pub fn decode(opcode: u16) -> Option<Instruction> {
+        let x = ((opcode & 0x0F00) >> 8) as u8;
+        let y = ((opcode & 0x00F0) >> 4) as u8;
+        let n = (opcode & 0x000F) as u8;
+        let nn = (opcode & 0x00FF) as u8;
+        let nnn = opcode & 0x0FFF;
+
+        let instruction = match (opcode & 0xF000) >> 12 {
+            0x0 => match nnn {
+                0x0E0 => Instruction::Cls,
+                0x0EE => Instruction::Ret,
+                _ => Instruction::Sys(nnn),
+            },
+            0x1 => Instruction::Jp(nnn),
+            0x2 => Instruction::Call(nnn),
+            0x3 => Instruction::SeByte(x, nn),
+            0x4 => Instruction::SneByte(x, nn),
+            0x5 if n == 0 => Instruction::SeReg(x, y),
+            0x6 => Instruction::LdByte(x, nn),
+            0x7 => Instruction::AddByte(x, nn),
+            0x8 => match n {
+                0x0 => Instruction::LdReg(x, y),
+                0x1 => Instruction::Or(x, y),
+                0x2 => Instruction::And(x, y),
+                0x3 => Instruction::Xor(x, y),
+                0x4 => Instruction::AddReg(x, y),
+                0x5 => Instruction::Sub(x, y),
+                0x6 => Instruction::Shr(x, y),
+                0x7 => Instruction::Subn(x, y),
+                0xE => Instruction::Shl(x, y),
+                _ => return None,
+            },
+            0x9 if n == 0 => Instruction::SneReg(x, y),
+            0xA => Instruction::LdI(nnn),
+            0xB => Instruction::JpV0(nnn),
+            0xC => Instruction::Rnd(x, nn),
+            0xD => Instruction::Drw(x, y, n),
+            0xE => match nn {
+                0x9E => Instruction::Skp(x),
+                0xA1 => Instruction::Sknp(x),
+                _ => return None,
+            },
+            0xF => match nn {
+                0x07 => Instruction::LdVxDt(x),
+                0x0A => Instruction::LdVxK(x),
+                0x15 => Instruction::LdDtVx(x),
+                0x18 => Instruction::LdStVx(x),
+                0x1E => Instruction::AddI(x),
+                0x29 => Instruction::LdF(x),
+                0x33 => Instruction::LdB(x),
+                0x55 => Instruction::LdIVx(x),
+                0x65 => Instruction::LdVxI(x),
+                _ => return None,
+            },
+            _ => return None,
+        };
+        Some(instruction)
+    }
+
+    /// Encode the instruction as an opcode
+    pub fn encode(&self) -> u16 {
+        let xy = |base: u16, x: u8, y: u8| base | u16::from(x & 0xF) << 8 | u16::from(y & 0xF) << 4;
+        let xnn = |base: u16, x: u8, nn: u8| base | u16::from(x & 0xF) << 8 | u16::from(nn);
+        let x = |base: u16, x: u8| base | u16::from(x & 0xF) << 8;
+
+        match *self {
+            Instruction::Sys(nnn) => nnn & 0x0FFF,
+            Instruction::Cls => 0x00E0,
+            Instruction::Ret => 0x00EE,
+            Instruction::Jp(nnn) => 0x1000 | (nnn & 0x0FFF),
+            Instruction::Call(nnn) => 0x2000 | (nnn & 0x0FFF),
+            Instruction::SeByte(vx, nn) => xnn(0x3000, vx, nn),
+            Instruction::SneByte(vx, nn) => xnn(0x4000, vx, nn),
+            Instruction::SeReg(vx, vy) => xy(0x5000, vx, vy),
+            Instruction::LdByte(vx, nn) => xnn(0x6000, vx, nn),
+            Instruction::AddByte(vx, nn) => xnn(0x7000, vx, nn),
+            Instruction::LdReg(vx, vy) => xy(0x8000, vx, vy),
+            Instruction::Or(vx, vy) => xy(0x8001, vx, vy),
+            Instruction::And(vx, vy) => xy(0x8002, vx, vy),
+            Instruction::Xor(vx, vy) => xy(0x8003, vx, vy),
+            Instruction::AddReg(vx, vy) => xy(0x8004, vx, vy),
+            Instruction::Sub(vx, vy) => xy(0x8005, vx, vy),
+            Instruction::Shr(vx, vy) => xy(0x8006, vx, vy),
+            Instruction::Subn(vx, vy) => xy(0x8007, vx, vy),
+            Instruction::Shl(vx, vy) => xy(0x800E, vx, vy),
+            Instruction::SneReg(vx, vy) => xy(0x9000, vx, vy),
+            Instruction::LdI(nnn) => 0xA000 | (nnn & 0x0FFF),
+            Instruction::JpV0(nnn) => 0xB000 | (nnn & 0x0FFF),
+            Instruction::Rnd(vx, nn) => xnn(0xC000, vx, nn),
+            Instruction::Drw(vx, vy, n) => xy(0xD000, vx, vy) | u16::from(n & 0xF),
+            Instruction::Skp(vx) => x(0xE09E, vx),
+            Instruction::Sknp(vx) => x(0xE0A1, vx),
+            Instruction::LdVxDt(vx) => x(0xF007, vx),
+            Instruction::LdVxK(vx) => x(0xF00A, vx),
+            Instruction::LdDtVx(vx) => x(0xF015, vx),
+            Instruction::LdStVx(vx) => x(0xF018, vx),
+            Instruction::AddI(vx) => x(0xF01E, vx),
+            Instruction::LdF(vx) => x(0xF029, vx),
+            Instruction::LdB(vx) => x(0xF033, vx),
+            Instruction::LdIVx(vx) => x(0xF055, vx),
+            Instruction::LdVxI(vx) => x(0xF065, vx),
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Instruction::Sys(nnn) => write!(f, "SYS {:#05X}", nnn),
+            Instruction::Cls => write!(f, "CLS"),
+            Instruction::Ret => write!(f, "RET"),
+            Instruction::Jp(nnn) => write!(f, "JP {:#05X}", nnn),
+            Instruction::Call(nnn) => write!(f, "CALL {:#05X}", nnn),
+            Instruction::SeByte(vx, nn) => write!(f, "SE V{:X}, {:#04X}", vx, nn),
+            Instruction::SneByte(vx, nn) => write!(f, "SNE V{:X}, {:#04X}", vx, nn),
+            Instruction::SeReg(vx, vy) => write!(f, "SE V{:X}, V{:X}", vx, vy),
+            Instruction::LdByte(vx, nn) => write!(f, "LD V{:X}, {:#04X}", vx, nn),
+            Instruction::AddByte(vx, nn) => write!(f, "ADD V{:X}, {:#04X}", vx, nn),
+            Instruction::LdReg(vx, vy) => write!(f, "LD V{:X}, V{:X}", vx, vy),
+            Instruction::Or(vx, vy) => write!(f, "OR V{:X}, V{:X}", vx, vy),
+            Instruction::And(vx, vy) => write!(f, "AND V{:X}, V{:X}", vx, vy),
+            Instruction::Xor(vx, vy) => write!(f, "XOR V{:X}, V{:X}", vx, vy),
+            Instruction::AddReg(vx, vy) => write!(f, "ADD V{:X}, V{:X}", vx, vy),
+            Instruction::Sub(vx, vy) => write!(f, "SUB V{:X}, V{:X}", vx, vy),
+            Instruction::Shr(vx, vy) => write!(f, "SHR V{:X}, V{:X}", vx, vy),
+            Instruction::Subn(vx, vy) => write!(f, "SUBN V{:X}, V{:X}", vx, vy),
+            Instruction::Shl(vx, vy) => write!(f, "SHL V{:X}, V{:X}", vx, vy),
+            Instruction::SneReg(vx, vy) => write!(f, "SNE V{:X}, V{:X}", vx, vy),
+            Instruction::LdI(nnn) => write!(f, "LD I, {:#05X}", nnn),
+            Instruction::JpV0(nnn) => write!(f, "JP V0, {:#05X}", nnn),
+            Instruction::Rnd(vx, nn) => write!(f, "RND V{:X}, {:#04X}", vx, nn),
+            Instruction::Drw(vx, vy, n) => write!(f, "DRW V{:X}, V{:X}, {}", vx, vy, n),
+            Instruction::Skp(vx) => write!(f, "SKP V{:X}", vx),
+            Instruction::Sknp(vx) => write!(f, "SKNP V{:X}", vx),
+            Instruction::LdVxDt(vx) => write!(f, "LD V{:X}, DT", vx),
+            Instruction::LdVxK(vx) => write!(f, "LD V{:X}, K", vx),
+            Instruction::LdDtVx(vx) => write!(f, "LD DT, V{:X}", vx),
+            Instruction::LdStVx(vx) => write!(f, "LD ST, V{:X}", vx),
+            Instruction::AddI(vx) => write!(f, "ADD I, V{:X}", vx),
+            Instruction::LdF(vx) => write!(f, "LD F, V{:X}", vx),
+            Instruction::LdB(vx) => write!(f, "LD B, V{:X}", vx),
+            Instruction::LdIVx(vx) => write!(f, "LD [I], V{:X}", vx),
+            Instruction::LdVxI(vx) => write!(f, "LD V{:X}, [I]", vx),
+        }
+    }
+}
+
+/// A single instruction operand
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Operand {
+    Register(u8),
+    Value(u16),
+    I,
+    IndirectI,
+    DelayTimer,
+    SoundTimer,
+    Key,
+    Font,
+    Bcd,
+}
+
+impl FromStr for Operand {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Operand, Error> {
+        let upper = text.to_ascii_uppercase();
+        let operand = match upper.as_str() {
+            "I" => Operand::I,
+            "[I]" => Operand::IndirectI,
+            "DT" => Operand::DelayTimer,
+            "ST" => Operand::SoundTimer,
+            "K" => Operand::Key,
+            "F" => Operand::Font,
+            "B" => Operand::Bcd,
+            _ => match upper.strip_prefix('V') {
+                Some(register) if register.len() == 1 => Operand::Register(
+                    u8::from_str_radix(register, 16).map_err(|_| invalid_operand(text))?,
+                ),
+                _ => Operand::Value(parse_value(text).ok_or_else(|| invalid_operand(text))?),
+            },
+        };
+        Ok(operand)
+    }
+}
+
+fn invalid_operand(text: &str) -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        format!("Invalid operand '{}'", text),
+    )
+}
+
+/// Parse a number, either decimal, 0x prefixed hex, $ prefixed hex or 0b prefixed binary
+pub fn parse_value(text: &str) -> Option<u16> {
+    let text = text.trim();
+    if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        u16::from_str_radix(hex, 16).ok()
+    } else if let Some(hex) = text.strip_prefix('$') {
+        u16::from_str_radix(hex, 16).ok()
+    } else if let Some(binary) = text.strip_prefix("0b").or_else(|| text.strip_prefix("0B")) {
+        u16::from_str_radix(binary, 2).ok()
+    } else {
+        text.parse().ok()
+    }
+}
+
+impl FromStr for Instruction {
+    type Err = Error;
+
+    /// Parse a single instruction, e.g. "LD V1, 0x0A". Operands must be registers or numbers.
+    fn from_str(text: &str) -> Result<Instruction, Error> {
+        let text = text.trim();
+        let (mnemonic, operands) = match text.find(char::is_whitespace) {
+            Some(end) => (&text[..end], text[end..].trim()),
+            None => (text, ""),
+        };
+        let operands = if operands.is_empty() {
+            Vec::new()
+        } else {
+            operands
+                .split(',')
+                .map(|operand| operand.trim().parse())
+                .collect::<Result<Vec<Operand>, Error>>()?
+        };
+
+        let address = |value: u16| -> Result<u16, Error> {
+            if value <= 0x0FFF {
+                Ok(value)
+            } else {
+                Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Address {:#X} does not fit in 12 bits", value),
+                ))
+            }
+        };
+        let byte = |value: u16| -> Result<u8, Error> {
+            if value <= 0xFF {
+                Ok(value as u8)
+            } else {
+                Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Constant {:#X} does not fit in 8 bits", value),
+                ))
+            }
+        };
+
+        use Operand::*;
+        let instruction = match (mnemonic.to_ascii_uppercase().as_str(), &operands[..]) {
+            ("CLS", []) => Instruction::Cls,
+            ("RET", []) => Instruction::Ret,
+            ("SYS", [Value(nnn)]) => Instruction::Sys(address(*nnn)?),
+            ("JP", [Value(nnn)]) => Instruction::Jp(address(*nnn)?),
+            ("JP", [Register(0), Value(nnn)]) => Instruction::JpV0(address(*nnn)?),
+            ("CALL", [Value(nnn)]) => Instruction::Call(address(*nnn)?),
+            ("SE", [Register(x), Value(nn)]) => Instruction::SeByte(*x, byte(*nn)?),
+            ("SE", [Register(x), Register(y)]) => Instruction::SeReg(*x, *y),
+            ("SNE", [Register(x), Value(nn)]) => Instruction::SneByte(*x, byte(*nn)?),
+            ("SNE", [Register(x), Register(y)]) => Instruction::SneReg(*x, *y),
+            ("LD", [Register(x), Value(nn)]) => Instruction::LdByte(*x, byte(*nn)?),
+            ("LD", [Register(x), Register(y)]) => Instruction::LdReg(*x, *y),
+            ("LD", [I, Value(nnn)]) => Instruction::LdI(address(*nnn)?),
+            ("LD", [Register(x), DelayTimer]) => Instruction::LdVxDt(*x),
+            ("LD", [Register(x), Key]) => Instruction::LdVxK(*x),
+            ("LD", [DelayTimer, Register(x)]) => Instruction::LdDtVx(*x),
+            ("LD", [SoundTimer, Register(x)]) => Instruction::LdStVx(*x),
+            ("LD", [Font, Register(x)]) => Instruction::LdF(*x),
+            ("LD", [Bcd, Register(x)]) => Instruction::LdB(*x),
+            ("LD", [IndirectI, Register(x)]) => Instruction::LdIVx(*x),
+            ("LD", [Register(x), IndirectI]) => Instruction::LdVxI(*x),
+            ("ADD", [Register(x), Value(nn)]) => Instruction::AddByte(*x, byte(*nn)?),
+            ("ADD", [Register(x), Register(y)]) => Instruction::AddReg(*x, *y),
+            ("ADD", [I, Register(x)]) => Instruction::AddI(*x),
+            ("OR", [Register(x), Register(y)]) => Instruction::Or(*x, *y),
+            ("AND", [Register(x), Register(y)]) => Instruction::And(*x, *y),
+            ("XOR", [Register(x), Register(y)]) => Instruction::Xor(*x, *y),
+            ("SUB", [Register(x), Register(y)]) => Instruction::Sub(*x, *y),
+            ("SUBN", [Register(x), Register(y)]) => Instruction::Subn(*x, *y),
+            ("SHR", [Register(x)]) => Instruction::Shr(*x, *x),
+            ("SHR", [Register(x), Register(y)]) => Instruction::Shr(*x, *y),
+            ("SHL", [Register(x)]) => Instruction::Shl(*x, *x),
+            ("SHL", [Register(x), Register(y)]) => Instruction::Shl(*x, *y),
+            ("RND", [Register(x), Value(nn)]) => Instruction::Rnd(*x, byte(*nn)?),
+            ("DRW", [Register(x), Register(y), Value(n)]) if *n <= 0xF => {
+                Instruction::Drw(*x, *y, *n as u8)
+            }
+            ("SKP", [Register(x)]) => Instruction::Skp(*x),
+            ("SKNP", [Register(x)]) => Instruction::Sknp(*x),
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Unknown instruction '{}'", text),
+                ))
+            }
+        };
+        Ok(instruction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode() {
+        assert_eq!(Some(Instruction::Cls), Instruction::decode(0x00E0));
+        assert_eq!(Some(Instruction::Sys(0x123)), Instruction::decode(0x0123));
+        assert_eq!(Some(Instruction::Drw(1, 2, 5)), Instruction::decode(0xD125));
+        assert_eq!(Some(Instruction::LdVxI(0xA)), Instruction::decode(0xFA65));
+        assert_eq!(None, Instruction::decode(0x5121));
+        assert_eq!(None, Instruction::decode(0x8128));
+        assert_eq!(None, Instruction::decode(0xE100));
+        assert_eq!(None, Instruction::decode(0xF100));
+    }
+
+    #[test]
+    fn encode_round_trip() {
+        for opcode in 0..=0xFFFF_u16 {
+            if let Some(instruction) = Instruction::decode(opcode) {
+                assert_eq!(opcode, instruction.encode(), "{}", instruction);
+            }
+        }
+    }
+
+    #[test]
+    fn display_parse_round_trip() {
+        for opcode in 0..=0xFFFF_u16 {
+            if let Some(instruction) = Instruction::decode(opcode) {
+                assert_eq!(
+                    instruction,
+                    instruction.to_string().parse().unwrap(),
+                    "{}",
+                    instruction
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!("LD V1, 0x0A", Instruction::LdByte(1, 0xA).to_string());
+        assert_eq!("JP V0, 0x300", Instruction::JpV0(0x300).to_string());
+        assert_eq!("LD [I], VF", Instruction::LdIVx(0xF).to_string());
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(Instruction::LdByte(1, 10), "ld v1, 10".parse().unwrap());
+        assert_eq!(Instruction::LdI(0x2F0), "LD I, $2F0".parse().unwrap());
+        assert_eq!(Instruction::Shr(3, 3), "SHR V3".parse().unwrap());
+        assert_eq!(
+            Instruction::LdByte(0, 0x81),
+            "LD V0, 0b10000001".parse().unwrap()
+        );
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert!("LD V1, 0x100".parse::<Instruction>().is_err());
+        assert!("JP 0x1000".parse::<Instruction>().is_err());
+        assert!("DRW V1, V2, 16".parse::<Instruction>().is_err());
+        assert!("LD VG, 1".parse::<Instruction>().is_err());
+        assert!("MOV V1, V2".parse::<Instruction>().is_err());
+        assert!("CLS V1".parse::<Instruction>().is_err());
+    }
+}
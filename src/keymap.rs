@@ -0,0 +1,531 @@
+use crate::{Key, KeyState};
+use minifb::Key as HostKey;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+use std::vec::Vec;
+
+/// Every host key which can be bound, used to parse key names
+const HOST_KEYS: [HostKey; 105] = [
+    HostKey::Key0,
+    HostKey::Key1,
+    HostKey::Key2,
+    HostKey::Key3,
+    HostKey::Key4,
+    HostKey::Key5,
+    HostKey::Key6,
+    HostKey::Key7,
+    HostKey::Key8,
+    HostKey::Key9,
+    HostKey::A,
+    HostKey::B,
+    HostKey::C,
+    HostKey::D,
+    HostKey::E,
+    HostKey::F,
+    HostKey::G,
+    HostKey::H,
+    HostKey::I,
+    HostKey::J,
+    HostKey::K,
+    HostKey::L,
+    HostKey::M,
+    HostKey::N,
+    HostKey::O,
+    HostKey::P,
+    HostKey::Q,
+    HostKey::R,
+    HostKey::S,
+    HostKey::T,
+    HostKey::U,
+    HostKey::V,
+    HostKey::W,
+    HostKey::X,
+    HostKey::Y,
+    HostKey::Z,
+    HostKey::F1,
+    HostKey::F2,
+    HostKey::F3,
+    HostKey::F4,
+    HostKey::F5,
+    HostKey::F6,
+    HostKey::F7,
+    HostKey::F8,
+    HostKey::F9,
+    HostKey::F10,
+    HostKey::F11,
+    HostKey::F12,
+    HostKey::F13,
+    HostKey::F14,
+    HostKey::F15,
+    HostKey::Down,
+    HostKey::Left,
+    HostKey::Right,
+    HostKey::Up,
+    HostKey::Apostrophe,
+    HostKey::Backquote,
+    HostKey::Backslash,
+    HostKey::Comma,
+    HostKey::Equal,
+    HostKey::LeftBracket,
+    HostKey::Minus,
+    HostKey::Period,
+    HostKey::RightBracket,
+    HostKey::Semicolon,
+    HostKey::Slash,
+    HostKey::Backspace,
+    HostKey::Delete,
+    HostKey::End,
+    HostKey::Enter,
+    HostKey::Escape,
+    HostKey::Home,
+    HostKey::Insert,
+    HostKey::Menu,
+    HostKey::PageDown,
+    HostKey::PageUp,
+    HostKey::Pause,
+    HostKey::Space,
+    HostKey::Tab,
+    HostKey::NumLock,
+    HostKey::CapsLock,
+    HostKey::ScrollLock,
+    HostKey::LeftShift,
+    HostKey::RightShift,
+    HostKey::LeftCtrl,
+    HostKey::RightCtrl,
+    HostKey::NumPad0,
+    HostKey::NumPad1,
+    HostKey::NumPad2,
+    HostKey::NumPad3,
+    HostKey::NumPad4,
+    HostKey::NumPad5,
+    HostKey::NumPad6,
+    HostKey::NumPad7,
+    HostKey::NumPad8,
+    HostKey::NumPad9,
+    HostKey::NumPadDot,
+    HostKey::NumPadSlash,
+    HostKey::NumPadAsterisk,
+    HostKey::NumPadMinus,
+    HostKey::NumPadPlus,
+    HostKey::NumPadEnter,
+    HostKey::LeftAlt,
+    HostKey::RightAlt,
+    HostKey::LeftSuper,
+];
+
+/// Host keys the emulator window uses for its own controls, which can't be bound to chip-8 keys
+pub const RESERVED_KEYS: [HostKey; 11] = [
+    HostKey::F1,
+    HostKey::F2,
+    HostKey::F3,
+    HostKey::F4,
+    HostKey::F5,
+    HostKey::F6,
+    HostKey::F7,
+    HostKey::F8,
+    HostKey::F9,
+    HostKey::Tab,
+    HostKey::Escape,
+];
+
+/// Parse a host key from its name, e.g. "Q", "Key1" or "NumPad5". Names are case insensitive.
+pub fn host_key_from_name(name: &str) -> Option<HostKey> {
+    HOST_KEYS
+        .iter()
+        .copied()
+        .find(|key| format!("{:?}", key).eq_ignore_ascii_case(name))
+}
+
+/// Maps host keys to the 16 chip-8 keys. Each chip-8 key may be bound to any number of host
+/// keys.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Keymap {
+    bindings: Vec<Vec<HostKey>>,
+}
+
+impl Default for Keymap {
+    /// The classic layout, with the left hand side of a qwerty keyboard standing in for the
+    /// chip-8 hex keypad
+    /// _________________
+    /// | 1 / 2 / 3 / c |
+    /// | 4 / 5 / 6 / D |
+    /// | 7 / 8 / 9 / E |
+    /// | A / 0 / B / F |
+    /// -----------------
+    fn default() -> Keymap {
+        let layout = [
+            HostKey::X,    // 0
+            HostKey::Key1, // 1
+            HostKey::Key2, // 2
+            HostKey::Key3, // 3
+            HostKey::Q,    // 4
+            HostKey::W,    // 5
+            HostKey::E,    // 6
+            HostKey::A,    // 7
+            HostKey::S,    // 8
+            HostKey::D,    // 9
+            HostKey::Z,    // A
+            HostKey::C,    // B
+            HostKey::Key4, // C
+            HostKey::R,    // D
+            HostKey::F,    // E
+            HostKey::V,    // F
+        ];
+        Keymap {
+            bindings: layout.iter().map(|key| vec![*key]).collect(),
+        }
+    }
+}
+
+impl Keymap {
+    /// A keymap with nothing bound
+    pub fn empty() -> Keymap {
+        Keymap {
+            bindings: vec![Vec::new(); 16],
+        }
+    }
+
+    /// Get the host keys bound to a chip-8 key, or None if the key isn't 0 - F
+    pub fn bindings(&self, chip_key: usize) -> Option<&[HostKey]> {
+        self.bindings.get(chip_key).map(Vec::as_slice)
+    }
+
+    /// Replace the host keys bound to a chip-8 key
+    ///
+    /// # Panics
+    ///
+    /// If chip_key isn't 0 - F
+    pub fn bind(&mut self, chip_key: usize, host_keys: Vec<HostKey>) {
+        assert!(chip_key < 16, "{:#X} is not a chip-8 key", chip_key);
+        self.bindings[chip_key] = host_keys;
+    }
+
+    /// Get the chip-8 key a host key is bound to
+    pub fn chip_key(&self, host_key: HostKey) -> Option<usize> {
+        self.bindings
+            .iter()
+            .position(|bound| bound.contains(&host_key))
+    }
+
+    /// Map the host keys currently held down to the state of the 16 chip-8 keys
+    pub fn map(&self, host_keys: &[HostKey]) -> Vec<Key> {
+        let mut chip_keys = vec![
+            Key {
+                state: KeyState::NotPressed,
+            };
+            16
+        ];
+
+        for key in host_keys.iter() {
+            for (index, bound) in self.bindings.iter().enumerate() {
+                if bound.contains(key) {
+                    chip_keys[index].state = KeyState::Pressed;
+                }
+            }
+        }
+        chip_keys
+    }
+
+    /// Write the keymap as a TOML table of hex key to host key names
+    fn write_table(&self, toml: &mut String, header: &str) {
+        writeln!(toml, "[{}]", header).unwrap();
+        for (index, bound) in self.bindings.iter().enumerate() {
+            let names: Vec<String> = bound.iter().map(|key| format!("\"{:?}\"", key)).collect();
+            writeln!(toml, "{:X} = [{}]", index, names.join(", ")).unwrap();
+        }
+    }
+
+    /// Replace the bindings of every chip-8 key named in a table of hex key to host key names
+    fn apply(&mut self, table: &HashMap<String, Vec<String>>) -> Result<(), Error> {
+        for (chip_key, names) in table.iter() {
+            let index = usize::from_str_radix(chip_key, 16)
+                .ok()
+                .filter(|index| *index < 16)
+                .ok_or_else(|| {
+                    Error::new(
+                        ErrorKind::InvalidData,
+                        format!("'{}' is not a chip-8 key, expected 0 - F", chip_key),
+                    )
+                })?;
+
+            let mut host_keys = Vec::new();
+            for name in names.iter() {
+                let host_key = host_key_from_name(name).ok_or_else(|| {
+                    Error::new(ErrorKind::InvalidData, format!("Unknown key '{}'", name))
+                })?;
+                if RESERVED_KEYS.contains(&host_key) {
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        format!("'{}' is reserved for the emulator's controls", name),
+                    ));
+                }
+                host_keys.push(host_key);
+            }
+            self.bindings[index] = host_keys;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeysTable {
+    #[serde(default)]
+    keys: HashMap<String, Vec<String>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeymapFile {
+    #[serde(default)]
+    keys: HashMap<String, Vec<String>>,
+    #[serde(default)]
+    rom: HashMap<String, KeysTable>,
+}
+
+/// A keymap file: a default keymap plus overrides for individual roms, e.g.
+///
+/// ```toml
+/// [keys]
+/// 5 = ["W", "Up"]
+///
+/// [rom."Maze [David Winter, 199x]".keys]
+/// 5 = ["Space"]
+/// ```
+///
+/// Roms are named by the SHA-1 of the rom or by the rom file name without extension. Keys not
+/// listed keep their classic binding.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct KeymapConfig {
+    pub keymap: Keymap,
+    pub roms: HashMap<String, Keymap>,
+}
+
+impl KeymapConfig {
+    /// Parse a keymap file from TOML
+    pub fn parse(text: &str) -> Result<KeymapConfig, Error> {
+        let file: KeymapFile =
+            toml::from_str(text).map_err(|error| Error::new(ErrorKind::InvalidData, error))?;
+
+        let mut config = KeymapConfig::default();
+        config.keymap.apply(&file.keys)?;
+        for (rom, table) in file.rom.iter() {
+            let mut keymap = config.keymap.clone();
+            keymap.apply(&table.keys)?;
+            config.roms.insert(rom.to_ascii_lowercase(), keymap);
+        }
+        Ok(config)
+    }
+
+    /// Load a keymap file
+    pub fn load_file<P: AsRef<Path>>(path: P) -> Result<KeymapConfig, Error> {
+        KeymapConfig::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Get the keymap for a rom, given the rom's SHA-1 and file name without extension
+    pub fn for_rom(&self, sha1: &str, name: &str) -> &Keymap {
+        self.roms
+            .get(&sha1.to_ascii_lowercase())
+            .or_else(|| self.roms.get(&name.to_ascii_lowercase()))
+            .unwrap_or(&self.keymap)
+    }
+
+    /// Set the keymap for a rom, named by SHA-1 or file name without extension
+    pub fn set_rom(&mut self, rom: &str, keymap: Keymap) {
+        self.roms.insert(rom.to_ascii_lowercase(), keymap);
+    }
+
+    /// Write the keymap file as TOML
+    pub fn to_toml(&self) -> String {
+        let mut toml = String::new();
+        self.keymap.write_table(&mut toml, "keys");
+
+        let mut roms: Vec<_> = self.roms.iter().collect();
+        roms.sort_by(|a, b| a.0.cmp(b.0));
+        for (rom, keymap) in roms {
+            toml.push('\n');
+            let key = toml::Value::String(rom.to_string());
+            keymap.write_table(&mut toml, &format!("rom.{}.keys", key));
+        }
+        toml
+    }
+}
+
+/// Builds a new keymap by prompting for each of the 16 chip-8 keys in turn
+#[derive(Debug)]
+pub struct Remapper {
+    keymap: Keymap,
+    next: usize,
+    held: Vec<HostKey>,
+}
+
+impl Remapper {
+    /// Start remapping. Keys already held down are ignored until they are released.
+    pub fn new(held: &[HostKey]) -> Remapper {
+        Remapper {
+            keymap: Keymap::empty(),
+            next: 0,
+            held: held.to_vec(),
+        }
+    }
+
+    /// The chip-8 key waiting to be bound, or None once every key is bound
+    pub fn waiting_for(&self) -> Option<usize> {
+        if self.next < 16 {
+            Some(self.next)
+        } else {
+            None
+        }
+    }
+
+    /// Feed the host keys currently held down. The first newly pressed key is bound to the chip-8
+    /// key being prompted for, unless it's reserved. Returns true if a key was bound.
+    pub fn update(&mut self, host_keys: &[HostKey]) -> bool {
+        let pressed = host_keys.iter().copied().find(|key| {
+            !self.held.contains(key)
+                && !RESERVED_KEYS.contains(key)
+                && self.keymap.chip_key(*key).is_none()
+        });
+        self.held = host_keys.to_vec();
+
+        match (pressed, self.waiting_for()) {
+            (Some(key), Some(chip_key)) => {
+                self.keymap.bind(chip_key, vec![key]);
+                self.next += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// The finished keymap, once every key is bound
+    pub fn finish(self) -> Option<Keymap> {
+        match self.waiting_for() {
+            Some(_) => None,
+            None => Some(self.keymap),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn host_key_names() {
+        assert_eq!(Some(HostKey::Key1), host_key_from_name("Key1"));
+        assert_eq!(Some(HostKey::NumPad5), host_key_from_name("numpad5"));
+        assert_eq!(Some(HostKey::Q), host_key_from_name("q"));
+        assert_eq!(None, host_key_from_name("Hyper"));
+    }
+
+    #[test]
+    fn default_layout() {
+        let keymap = Keymap::default();
+        assert_eq!(Some(0x0), keymap.chip_key(HostKey::X));
+        assert_eq!(Some(0xC), keymap.chip_key(HostKey::Key4));
+        assert_eq!(Some(0xF), keymap.chip_key(HostKey::V));
+        assert_eq!(None, keymap.chip_key(HostKey::Space));
+    }
+
+    #[test]
+    fn map() {
+        let mut keymap = Keymap::default();
+        keymap.bind(0x5, vec![HostKey::W, HostKey::Up]);
+
+        let keys = keymap.map(&[HostKey::Up, HostKey::Key1, HostKey::Space]);
+        assert_eq!(16, keys.len());
+        assert!(keys[0x5].is_pressed());
+        assert!(keys[0x1].is_pressed());
+        assert_eq!(2, keys.iter().filter(|key| key.is_pressed()).count());
+    }
+
+    #[test]
+    fn parse() {
+        let config = KeymapConfig::parse(
+            r#"
+            [keys]
+            5 = ["W", "Up"]
+            a = []
+
+            [rom."Maze".keys]
+            5 = ["Space"]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            &[HostKey::W, HostKey::Up],
+            config.keymap.bindings(0x5).unwrap()
+        );
+        assert!(config.keymap.bindings(0xA).unwrap().is_empty());
+        assert_eq!(&[HostKey::Key1], config.keymap.bindings(0x1).unwrap());
+
+        let maze = config.for_rom("0000", "maze");
+        assert_eq!(&[HostKey::Space], maze.bindings(0x5).unwrap());
+        assert!(maze.bindings(0xA).unwrap().is_empty());
+        assert_eq!(&config.keymap, config.for_rom("0000", "pong"));
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert!(KeymapConfig::parse("[keys]\n10 = [\"Q\"]").is_err());
+        assert!(KeymapConfig::parse("[keys]\n1 = [\"Hyper\"]").is_err());
+        assert!(KeymapConfig::parse("[buttons]").is_err());
+        assert!(KeymapConfig::parse("[keys]\n1 = [\"F2\"]").is_err());
+        assert!(KeymapConfig::parse("[rom.maze.keys]\n1 = [\"tab\"]").is_err());
+    }
+
+    #[test]
+    fn to_toml_round_trip() {
+        let mut config = KeymapConfig::default();
+        config.keymap.bind(0x5, vec![HostKey::W, HostKey::Up]);
+        let mut maze = Keymap::default();
+        maze.bind(0xA, Vec::new());
+        config.set_rom("Maze [David Winter]", maze);
+
+        assert_eq!(config, KeymapConfig::parse(&config.to_toml()).unwrap());
+    }
+
+    #[test]
+    fn to_toml_escapes_rom_names() {
+        let mut config = KeymapConfig::default();
+        config.set_rom("del\u{7f} \"quoted\" back\\slash\ttab", Keymap::empty());
+        assert_eq!(config, KeymapConfig::parse(&config.to_toml()).unwrap());
+    }
+
+    #[test]
+    fn remapper() {
+        let mut remapper = Remapper::new(&[HostKey::F1]);
+        assert_eq!(Some(0), remapper.waiting_for());
+
+        // Keys held when remapping starts are ignored
+        assert!(!remapper.update(&[HostKey::F1]));
+        assert!(remapper.update(&[HostKey::Space]));
+        assert_eq!(Some(1), remapper.waiting_for());
+
+        // Holding a key doesn't bind it twice, and keys can't be bound to two chip-8 keys
+        assert!(!remapper.update(&[HostKey::Space]));
+        assert!(!remapper.update(&[]));
+        assert!(!remapper.update(&[HostKey::Space]));
+
+        // Reserved keys are left for the emulator's controls
+        assert!(!remapper.update(&[HostKey::Tab]));
+        assert!(!remapper.update(&[HostKey::F6]));
+        assert_eq!(Some(1), remapper.waiting_for());
+
+        for key in HOST_KEYS[10..25].iter() {
+            assert!(remapper.update(&[*key]));
+        }
+        assert_eq!(None, remapper.waiting_for());
+
+        let keymap = remapper.finish().unwrap();
+        assert_eq!(&[HostKey::Space], keymap.bindings(0).unwrap());
+        assert_eq!(&[HostKey::O], keymap.bindings(0xF).unwrap());
+        assert_eq!(None, keymap.bindings(0x10));
+    }
+}
@@ -1,18 +1,38 @@
+use memory::Bus;
 use minifb::{Window, WindowOptions};
-use std::fs;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
 use std::io;
 use std::io::Read;
 use std::vec::Vec;
+pub mod asm;
+pub mod config;
+pub mod coverage;
 pub mod debugger;
+pub mod description;
+pub mod filter;
+pub mod framebuffer;
+pub mod instruction;
+pub mod keymap;
+pub mod memory;
 pub mod opcode;
+pub mod palette;
+#[cfg(test)]
+mod reference;
+pub mod render;
+pub mod rom;
+pub mod romdb;
 pub mod stack;
+pub mod state;
+pub mod timing;
 
 #[derive(Debug)]
 pub struct RomWindow {
     pub window: minifb::Window,
     pub scale_factor: u8,
-    screen_width: usize,
-    screen_height: usize,
+    palette: palette::Palette,
+    filter: filter::DisplayFilter,
+    renderer: render::Renderer,
 }
 
 pub trait DisplayWindow {
@@ -21,58 +41,72 @@ pub trait DisplayWindow {
 
 impl RomWindow {
     pub fn new(scale_factor: u8, filename: &str, chip: &Chip) -> RomWindow {
+        let renderer = render::Renderer::new(
+            chip.screen_buffer.width(),
+            chip.screen_buffer.height(),
+            scale_factor,
+        );
         RomWindow {
             scale_factor,
-            screen_width: chip.screen_width,
-            screen_height: chip.screen_height,
+            palette: palette::Palette::default(),
+            filter: filter::DisplayFilter::default(),
             window: Window::new(
                 filename,
-                chip.screen_width * scale_factor as usize,
-                chip.screen_height * scale_factor as usize,
+                renderer.width(),
+                renderer.height(),
                 WindowOptions::default(),
             )
             .expect("Unable to create RomWindow"),
+            renderer,
         }
     }
 
-    /// Convert our monochrome bit to a 32 bit integer
-    fn bit_to_u32(bit: u8) -> u32 {
-        match bit {
-            0 => 0x0010_1010, // dark gray
-            _ => 0x00EE_EEEE, // light gray
-        }
+    /// Set the colors pixels are drawn with
+    pub fn set_palette(&mut self, palette: palette::Palette) {
+        self.palette = palette;
     }
 
-    /// Expand a single byte to a partial screen buffer
-    fn expand_byte(&self, byte: u8) -> Vec<u32> {
-        let mut partial_buffer: Vec<u32> = Vec::with_capacity(8 * self.scale_factor as usize);
-        for i in (0..=7).rev() {
-            let bit = (byte >> i) & 0x1;
-            for _ in 0..self.scale_factor {
-                partial_buffer.push(RomWindow::bit_to_u32(bit));
-            }
-        }
-        partial_buffer
+    pub fn palette(&self) -> &palette::Palette {
+        &self.palette
     }
 
-    fn expand_screen_buffer(&self, buffer: &[u8]) -> Vec<u32> {
-        let mut screen_buffer: Vec<u32> = Vec::with_capacity(buffer.len() * 8);
-        for y in 0..self.screen_height {
-            for _ in 0..self.scale_factor {
-                for x in 0..(self.screen_width / 8) {
-                    screen_buffer
-                        .append(&mut self.expand_byte(buffer[x + (y * self.screen_width / 8)]));
-                }
-            }
+    /// Set how frames are blended before they're drawn, to hide flicker
+    pub fn set_filter(&mut self, mode: filter::FilterMode) {
+        self.filter = filter::DisplayFilter::new(mode);
+    }
+
+    pub fn filter(&self) -> filter::FilterMode {
+        self.filter.mode()
+    }
+
+    /// Forget the frames the filter is blending, so a reset or reloaded rom starts without the
+    /// old screen's afterglow
+    pub fn clear_filter(&mut self) {
+        self.filter.clear();
+    }
+
+    /// Show a frame. The screen is only drawn again if it changed since the last frame, or a
+    /// filter is blending frames, otherwise the window just polls for input. Call this once per
+    /// frame rather than after every instruction.
+    pub fn present(&mut self, chip: &mut Chip) {
+        if chip.take_screen_changed() || self.filter.mode() != filter::FilterMode::Off {
+            self.update(chip.screen_buffer.as_bytes());
+        } else {
+            self.window.update();
         }
-        screen_buffer
     }
 }
 
 impl DisplayWindow for RomWindow {
     fn update(&mut self, buffer: &[u8]) {
+        let pixels = match self.filter.apply(buffer) {
+            filter::Output::Bits(bits) => self.renderer.render(&[bits], &self.palette),
+            filter::Output::Levels(levels, max) => {
+                self.renderer.render_levels(levels, max, &self.palette)
+            }
+        };
         self.window
-            .update_with_buffer(&self.expand_screen_buffer(&buffer))
+            .update_with_buffer(pixels)
             .expect("Error updating the display\n");
     }
 }
@@ -97,20 +131,51 @@ impl Key {
     }
 }
 
+/// The number of bytes used by the built in hex font
+const FONT_SIZE: usize = 16 * 5;
+
+/// A rom and where it was loaded, kept so the chip can be reset
+#[derive(Clone, Debug)]
+struct LoadedRom {
+    bytes: Vec<u8>,
+    load_address: usize,
+    start_address: usize,
+}
+
 /// This represents the state of the chip-8 system including memory,
 /// call stack, general purpose registers, program counter, and screen buffer
 pub struct Chip {
-    memory: Vec<u8>,
+    memory: memory::Memory,
     stack: stack::Stack<u16>,
     registers: Vec<u8>,
     address: u16,
     program_counter: u16,
     pub keys: Vec<Key>,
-    pub screen_buffer: Vec<u8>,
-    screen_width: usize,
-    screen_height: usize,
+    pub screen_buffer: framebuffer::FrameBuffer,
     sound_timer: u8,
     delay_timer: u8,
+    config: config::ChipConfig,
+    rom_database: Option<romdb::RomDatabase>,
+    rom_info: Option<romdb::RomInfo>,
+    auto_configure: bool,
+    rng: StdRng,
+    seed: Option<u64>,
+
+    // Set when 00E0 or DXYN change the screen, cleared once the frame is shown
+    screen_changed: bool,
+
+    // Set at the start of each frame, cleared when DXYN draws with the display_wait quirk
+    vblank: bool,
+
+    timing: timing::Timing,
+
+    // Machine cycles the last frame overran by with VIP timing, taken from the next frame
+    cycle_debt: u32,
+
+    // Instructions executed since the chip was created
+    instructions: u64,
+
+    rom: Option<LoadedRom>,
 }
 
 impl Default for Chip {
@@ -122,33 +187,157 @@ impl Default for Chip {
 impl Chip {
     /// Create a new, default initialized Chip struct
     pub fn new(screen_width: usize, screen_height: usize) -> Chip {
+        Chip::with_config(config::ChipConfig {
+            screen_width,
+            screen_height,
+            ..Default::default()
+        })
+    }
+
+    /// Create a new Chip with the given memory layout and screen size
+    pub fn with_config(config: config::ChipConfig) -> Chip {
         let mut chip = Chip {
-            memory: vec![0; 0xFFF],
+            memory: memory::Memory::new(config.memory_size, FONT_SIZE, config.load_address),
             stack: stack::Stack::new(16),
             registers: vec![0; 16],
             address: 0,
-            program_counter: 0x200,
+            program_counter: config.start_address as u16,
             keys: vec![
                 Key {
                     state: KeyState::NotPressed
                 };
                 16
             ],
-            screen_buffer: vec![
-                0;
-                screen_width
-                    * screen_height
-                    / 8 // size of u8
-            ],
-            screen_width,
-            screen_height,
+            screen_buffer: framebuffer::FrameBuffer::new(config.screen_width, config.screen_height),
             delay_timer: 0,
             sound_timer: 0,
+            config,
+            rom_database: None,
+            rom_info: None,
+            auto_configure: true,
+            rng: StdRng::from_entropy(),
+            seed: None,
+            screen_changed: true,
+            vblank: false,
+            timing: timing::Timing::Fixed,
+            cycle_debt: 0,
+            instructions: 0,
+            rom: None,
         };
         chip.init_fonts();
         chip
     }
 
+    /// Get the configuration the chip was created with
+    pub fn config(&self) -> &config::ChipConfig {
+        &self.config
+    }
+
+    /// Replace the quirks the chip was created with, e.g. to override the rom database
+    pub fn set_quirks(&mut self, quirks: config::Quirks) {
+        self.config.quirks = quirks;
+    }
+
+    /// Seed the random number generator used by CXNN, so runs can be reproduced. The seed is
+    /// kept across resets.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = Some(seed);
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    /// Use a rom database other than the builtin one, e.g. the builtin database merged with a
+    /// local override file
+    pub fn set_rom_database(&mut self, database: romdb::RomDatabase) {
+        self.rom_database = Some(database);
+    }
+
+    /// Enable or disable configuring the platform and quirks from the rom database on load
+    pub fn set_auto_configure(&mut self, auto_configure: bool) {
+        self.auto_configure = auto_configure;
+    }
+
+    /// Get the database entry of the loaded rom, if it was found
+    pub fn rom_info(&self) -> Option<&romdb::RomInfo> {
+        self.rom_info.as_ref()
+    }
+
+    /// Look up a rom in the database, returning its entry and the configuration to run it with.
+    /// The configuration is the current one unless auto configuration is on and the rom is found.
+    fn rom_config(&self, rom: &[u8]) -> (Option<romdb::RomInfo>, config::ChipConfig) {
+        let database = self
+            .rom_database
+            .as_ref()
+            .unwrap_or_else(|| romdb::RomDatabase::builtin());
+        let rom_info = database.lookup(rom).cloned();
+
+        let info = match (&rom_info, self.auto_configure) {
+            (Some(info), true) => info,
+            _ => return (rom_info, self.config.clone()),
+        };
+        let mut config = match info.platform {
+            Some(platform) => config::ChipConfig {
+                screen_width: self.config.screen_width,
+                screen_height: self.config.screen_height,
+                ..config::ChipConfig::from(platform)
+            },
+            None => self.config.clone(),
+        };
+        if let Some(quirks) = info.quirks {
+            config.quirks = quirks;
+        }
+        (rom_info, config)
+    }
+
+    /// Apply a new configuration. If the memory layout changes the chip is recreated, keeping
+    /// memory observers, the rom database and the loaded rom's info.
+    fn reconfigure(&mut self, config: config::ChipConfig) {
+        let same_layout = config.memory_size == self.config.memory_size
+            && config.load_address == self.config.load_address
+            && config.start_address == self.config.start_address
+            && config.screen_width == self.config.screen_width
+            && config.screen_height == self.config.screen_height;
+        if same_layout {
+            self.config = config;
+            return;
+        }
+
+        self.recreate(config);
+    }
+
+    /// Replace the chip with a new one, keeping memory observers, write protection, the rom
+    /// database, the loaded rom and the settings made after the chip was created. Coverage is
+    /// resized to the new memory.
+    fn recreate(&mut self, config: config::ChipConfig) {
+        let observers = self.memory.take_observers();
+        let write_protected = self.memory.write_protected_kinds();
+        let rom_database = self.rom_database.take();
+        let rom_info = self.rom_info.take();
+        let rom = self.rom.take();
+        let auto_configure = self.auto_configure;
+        let seed = self.seed;
+        let timing = self.timing;
+
+        *self = Chip::with_config(config);
+        for observer in observers {
+            self.memory.add_observer(observer);
+        }
+        let memory_size = self.memory.len();
+        if let Some(coverage) = self.memory.observer_mut::<coverage::Coverage>() {
+            coverage.resize(memory_size);
+        }
+        for kind in write_protected {
+            self.memory.set_write_protected(kind, true);
+        }
+        self.rom_database = rom_database;
+        self.rom_info = rom_info;
+        self.rom = rom;
+        self.auto_configure = auto_configure;
+        self.timing = timing;
+        if let Some(seed) = seed {
+            self.set_seed(seed);
+        }
+    }
+
     pub fn update_keys(&mut self, mut keys: Vec<Key>) {
         // keys is expected to have a length of 16. Anything longer is truncated, anything shorter
         // is padded with unpressed
@@ -162,23 +351,67 @@ impl Chip {
         self.keys = keys;
     }
 
+    /// Load a rom file into memory at the configured load address. Gzip compressed roms and zip
+    /// archives holding a single rom are supported.
     pub fn load_rom(&mut self, file: &str) -> Result<(), io::Error> {
-        static MAX_ROM_SIZE: usize = 0x400;
-        let mut f = fs::File::open(&file)?;
-
-        let bytes_read = f.read(&mut self.memory[0x200..])?;
-        if bytes_read > MAX_ROM_SIZE {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                "Rom file too large",
-            ));
-        }
+        self.load_rom_bytes(&rom::read_file(file)?)
+    }
+
+    /// Load a rom from any reader, e.g. a network stream or a file inside a rom pack
+    pub fn load_rom_reader<R: Read>(&mut self, reader: R) -> Result<(), io::Error> {
+        self.load_rom_bytes(&rom::read(reader)?)
+    }
+
+    /// Load a rom already in memory, e.g. one embedded with include_bytes!(). If the rom is in
+    /// the rom database, the chip is configured for it first. A rom which doesn't fit leaves the
+    /// chip unchanged.
+    pub fn load_rom_bytes(&mut self, rom: &[u8]) -> Result<(), io::Error> {
+        let (rom_info, config) = self.rom_config(rom);
+        let (load_address, start_address) = (config.load_address, config.start_address);
+        check_rom_fits(&config, rom, load_address, start_address)?;
+
+        self.rom_info = rom_info;
+        self.reconfigure(config);
+        self.load_rom_bytes_at(rom, load_address, start_address)
+    }
+
+    /// Load a rom file into memory at a given address and start executing from another
+    ///
+    /// # Arguments
+    ///
+    /// file The rom file to load
+    /// load_address The address the first byte of the rom is loaded to
+    /// start_address The initial value of the program counter
+    pub fn load_rom_at(
+        &mut self,
+        file: &str,
+        load_address: usize,
+        start_address: usize,
+    ) -> Result<(), io::Error> {
+        self.load_rom_bytes_at(&rom::read_file(file)?, load_address, start_address)
+    }
+
+    /// Load rom bytes into memory at a given address and start executing from another
+    pub fn load_rom_bytes_at(
+        &mut self,
+        rom: &[u8],
+        load_address: usize,
+        start_address: usize,
+    ) -> Result<(), io::Error> {
+        check_rom_fits(&self.config, rom, load_address, start_address)?;
+        self.memory.load(load_address, rom)?;
+        self.program_counter = start_address as u16;
+        self.rom = Some(LoadedRom {
+            bytes: rom.to_vec(),
+            load_address,
+            start_address,
+        });
         Ok(())
     }
 
     /// Initialize fonts
     fn init_fonts(&mut self) {
-        let fonts: [u8; 16 * 5] = [
+        let fonts: [u8; FONT_SIZE] = [
             0xF0, 0x90, 0x90, 0x90, 0xf0, // 0
             0x20, 0x60, 0x20, 0x20, 0x70, // 1
             0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
@@ -197,54 +430,225 @@ impl Chip {
             0xF0, 0x80, 0xF0, 0x80, 0x80, // F
         ];
 
-        self.memory[0..16 * 5].copy_from_slice(&fonts);
+        self.memory
+            .load(0, &fonts)
+            .expect("Memory is too small for the font");
     }
 
-    /// Reset the Chip
+    /// Start recording which bytes of memory are executed, read and written. Any coverage
+    /// recorded so far is discarded.
+    pub fn enable_coverage(&mut self) {
+        self.memory.remove_observer::<coverage::Coverage>();
+        let coverage = coverage::Coverage::new(self.memory.len());
+        self.memory.add_observer(Box::new(coverage));
+    }
+
+    /// Stop recording coverage and return everything recorded so far
+    pub fn disable_coverage(&mut self) -> Option<coverage::Coverage> {
+        self.memory.remove_observer::<coverage::Coverage>()
+    }
+
+    /// Get the coverage recorded so far, if coverage is enabled
+    pub fn coverage(&self) -> Option<&coverage::Coverage> {
+        self.memory.observer::<coverage::Coverage>()
+    }
+
+    /// The addresses the loaded rom occupies, e.g. to report its coverage
+    pub fn rom_range(&self) -> Option<std::ops::Range<usize>> {
+        self.rom
+            .as_ref()
+            .map(|rom| rom.load_address..rom.load_address + rom.bytes.len())
+    }
+
+    /// Get the chip's memory, e.g. for annotating coverage
+    pub fn memory(&self) -> &memory::Memory {
+        &self.memory
+    }
+
+    /// Get the chip's memory mutably, e.g. to add observers or write protect regions
+    pub fn memory_mut(&mut self) -> &mut memory::Memory {
+        &mut self.memory
+    }
+
+    /// Hard reset: clear memory and load the rom again, as if the chip had just been created.
+    /// The configuration, quirks, seed and timing are kept.
     pub fn reset(&mut self) {
-        *self = Chip::new(self.screen_width, self.screen_height);
+        self.recreate(self.config.clone());
+        if let Some(rom) = self.rom.clone() {
+            self.load_rom_bytes_at(&rom.bytes, rom.load_address, rom.start_address)
+                .expect("The rom fitted when it was first loaded");
+        }
+    }
+
+    /// Soft reset, like the reset switch of the COSMAC VIP: registers, the stack, timers and the
+    /// screen are cleared and the program starts again, but memory is left as the program
+    /// changed it
+    pub fn soft_reset(&mut self) {
+        self.registers.iter_mut().for_each(|register| *register = 0);
+        self.address = 0;
+        self.program_counter = match &self.rom {
+            Some(rom) => rom.start_address as u16,
+            None => self.config.start_address as u16,
+        };
+        self.stack = stack::Stack::new(self.stack.size);
+        self.delay_timer = 0;
+        self.sound_timer = 0;
+        self.screen_buffer.clear();
+        self.screen_changed = true;
+        self.vblank = false;
+        self.cycle_debt = 0;
+        if let Some(seed) = self.seed {
+            self.set_seed(seed);
+        }
     }
 
-    /// Execute a single instruction
-    pub fn tick(&mut self) {
+    /// Replace the loaded rom, e.g. after the file changed, and hard reset. The chip isn't
+    /// reconfigured for the new rom.
+    pub fn reload_rom_bytes(&mut self, rom: &[u8]) -> Result<(), io::Error> {
+        let (load_address, start_address) = match &self.rom {
+            Some(loaded) => (loaded.load_address, loaded.start_address),
+            None => (self.config.load_address, self.config.start_address),
+        };
+        let mut chip = Chip::with_config(self.config.clone());
+        chip.load_rom_bytes_at(rom, load_address, start_address)?;
+        self.rom = chip.rom;
+        self.reset();
+        Ok(())
+    }
+
+    /// Whether the screen changed since the last call, so frames with nothing new to show can
+    /// skip rendering
+    pub fn take_screen_changed(&mut self) -> bool {
+        std::mem::replace(&mut self.screen_changed, false)
+    }
+
+    /// Signal the vertical blank interrupt at the start of a 60Hz frame, which counts the delay
+    /// and sound timers down. With the display_wait quirk, DXYN doesn't draw until this is
+    /// called.
+    pub fn vblank(&mut self) {
+        self.vblank = true;
         self.delay_timer = self.delay_timer.saturating_sub(1);
         self.sound_timer = self.sound_timer.saturating_sub(1);
+    }
+
+    /// Whether the next instruction is DXYN waiting for the vertical blank interrupt
+    pub fn waiting_for_vblank(&self) -> bool {
+        self.config.quirks.display_wait
+            && !self.vblank
+            && self
+                .memory
+                .peek(usize::from(self.program_counter))
+                .is_ok_and(|byte| byte & 0xF0 == 0xD0)
+    }
+
+    /// Run one 60Hz frame: signal the vertical blank interrupt, then execute instructions until
+    /// the count is reached or the chip is waiting for the next frame. With VIP timing the count
+    /// is ignored and instructions run until the frame's machine cycles are spent. Stops at the
+    /// first instruction which fails.
+    pub fn run_frame(&mut self, instructions_per_frame: u32) -> Result<(), io::Error> {
+        self.vblank();
+        if self.timing == timing::Timing::Vip {
+            return self.run_vip_frame();
+        }
+        for _ in 0..instructions_per_frame {
+            if self.waiting_for_vblank() {
+                break;
+            }
+            self.tick()?;
+        }
+        Ok(())
+    }
 
-        let opcode = self.get_next_opcode();
-        opcode.decode_execute(self);
+    /// The number of instructions executed since the chip was created or reset
+    pub fn instruction_count(&self) -> u64 {
+        self.instructions
     }
 
-    /// Read the next opcode from memory
-    fn get_next_opcode(&mut self) -> opcode::Opcode {
-        // @todo bounds checking
-        // @todo figure out how to handle out of bounds
+    /// Execute a single instruction. Returns an error if it touches memory it can't, e.g. a
+    /// write to a write protected region.
+    pub fn tick(&mut self) -> Result<(), io::Error> {
+        self.instructions += 1;
 
-        let ms_byte = self.memory[usize::from(self.program_counter)];
-        let ls_byte = self.memory[usize::from(self.program_counter + 1)];
+        let opcode = self.get_next_opcode()?;
+        opcode.decode_execute(self)
+    }
+
+    /// Whether the next instruction can run, rather than tick returning an error for an illegal
+    /// opcode or a call or return which overflows or underflows the stack
+    pub fn next_instruction_is_valid(&self) -> bool {
+        let address = usize::from(self.program_counter);
+        let opcode = [address, address + 1]
+            .iter()
+            .map(|&address| self.memory.peek(address % self.memory.len()))
+            .collect::<Result<Vec<u8>, io::Error>>();
+        match opcode {
+            Ok(bytes) => {
+                opcode::Opcode::new(u16::from_be_bytes([bytes[0], bytes[1]])).is_valid(self)
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Read the next opcode from memory. The program counter wraps around the end of memory.
+    fn get_next_opcode(&mut self) -> Result<opcode::Opcode, io::Error> {
+        let address = usize::from(self.program_counter) % self.memory.len();
+        let ms_byte = self.memory.fetch(address)?;
+        let ls_byte = self.memory.fetch((address + 1) % self.memory.len())?;
         let opcode: u16 = u16::from(ms_byte) << 8;
-        opcode::Opcode::new(opcode | u16::from(ls_byte))
+        Ok(opcode::Opcode::new(opcode | u16::from(ls_byte)))
     }
 
     /// Point the program counter to the next instruction.
     /// Each instruction is 2 bytes
     fn increment_program_counter(&mut self, step: Option<u16>) {
-        match step {
-            Some(i) => self.program_counter += 2 * i,
-            None => self.program_counter += 2,
-        }
+        let step = 2 * step.unwrap_or(1);
+        self.program_counter = self.program_counter.wrapping_add(step);
+    }
+
+    /// The address offset bytes past I. Like the address lines of the original machine, it
+    /// wraps around the end of memory rather than running off it.
+    fn address_from_i(&self, offset: usize) -> usize {
+        (usize::from(self.address) + offset) % self.memory.len()
     }
 
     /// Get the first pressed key from the keyboard
     fn get_pressed_key(&self) -> Option<usize> {
-        for key in 0..self.keys.len() {
-            if self.keys[key].is_pressed() {
-                return Some(key);
-            }
-        }
-        None
+        (0..self.keys.len()).find(|&key| self.keys[key].is_pressed())
     }
 }
 
+/// Check that a rom fits in memory at the load address, and that the start address is in memory
+fn check_rom_fits(
+    config: &config::ChipConfig,
+    rom: &[u8],
+    load_address: usize,
+    start_address: usize,
+) -> Result<(), io::Error> {
+    let max_rom_size = config.memory_size.saturating_sub(load_address);
+    if rom.len() > max_rom_size {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "Rom is {} bytes, but only {} bytes fit at {:#05X} in {} bytes of memory",
+                rom.len(),
+                max_rom_size,
+                load_address,
+                config.memory_size
+            ),
+        ));
+    }
+    if start_address + 1 >= config.memory_size {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "Start address {:#05X} is outside of {} bytes of memory",
+                start_address, config.memory_size
+            ),
+        ));
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -252,82 +656,399 @@ mod tests {
     use std::io::Write;
 
     #[test]
-    fn bit_to_u32() {
-        for i in 0..=255 {
-            if i == 0 {
-                assert_eq!(0x00101010, RomWindow::bit_to_u32(i));
-            } else {
-                assert_eq!(0x00EEEEEE, RomWindow::bit_to_u32(i));
-            }
-        }
+    #[should_panic]
+    fn load_rom_too_big() {
+        let mut c = Chip::default();
+        let filename = std::env::temp_dir().join("chip8_rs_load_rom_too_big.c8");
+        let mut f = File::create(&filename).unwrap();
+        let data = vec![1; c.config().max_rom_size() + 1];
+
+        f.write_all(&data[..]).unwrap();
+        c.load_rom(filename.to_str().unwrap()).unwrap();
     }
 
     #[test]
-    fn expand_byte() {
-        let byte = 0;
-        let expected: Vec<u32> = vec![
-            0x00101010, 0x00101010, 0x00101010, 0x00101010, 0x00101010, 0x00101010, 0x00101010,
-            0x00101010,
-        ];
-        assert_eq!(
-            expected,
-            RomWindow::new(1, "", &Chip::default()).expand_byte(byte)
-        );
+    fn load_rom_fills_memory() {
+        let config = config::ChipConfig::from(config::Platform::CosmacVip2K);
+        let mut c = Chip::with_config(config.clone());
+        let filename = std::env::temp_dir().join("chip8_rs_load_rom_fills_memory.c8");
+        let mut f = File::create(&filename).unwrap();
+        let data = vec![1; config.max_rom_size()];
 
-        let byte = 0b10000000;
-        let expected: Vec<u32> = vec![
-            0x00EEEEEE, 0x00101010, 0x00101010, 0x00101010, 0x00101010, 0x00101010, 0x00101010,
-            0x00101010,
-        ];
+        f.write_all(&data[..]).unwrap();
+        c.load_rom(filename.to_str().unwrap()).unwrap();
+        assert_eq!(0x800, c.memory().len());
+        assert_eq!(1, c.memory().peek(0x7FF).unwrap());
+        assert_eq!(0x200, c.program_counter);
+    }
 
-        assert_eq!(
-            expected,
-            RomWindow::new(1, "", &Chip::default()).expand_byte(byte)
-        );
+    #[test]
+    fn load_rom_eti660() {
+        let mut c = Chip::with_config(config::ChipConfig::from(config::Platform::Eti660));
+        assert_eq!(0x600, c.program_counter);
+
+        let filename = std::env::temp_dir().join("chip8_rs_load_rom_eti660.c8");
+        let mut f = File::create(&filename).unwrap();
+        f.write_all(&[0x12, 0x34]).unwrap();
+
+        c.load_rom(filename.to_str().unwrap()).unwrap();
+        assert_eq!(0x12, c.memory().peek(0x600).unwrap());
+        assert_eq!(0x600, c.program_counter);
+    }
+
+    #[test]
+    fn load_rom_at() {
+        let mut c = Chip::default();
+        let filename = std::env::temp_dir().join("chip8_rs_load_rom_at.c8");
+        let mut f = File::create(&filename).unwrap();
+        f.write_all(&[0x12, 0x34, 0x56, 0x78]).unwrap();
+
+        c.load_rom_at(filename.to_str().unwrap(), 0x300, 0x302)
+            .unwrap();
+        assert_eq!(0x12, c.memory().peek(0x300).unwrap());
+        assert_eq!(0x302, c.program_counter);
+
+        let error = c
+            .load_rom_at(filename.to_str().unwrap(), 0x200, 0x1000)
+            .unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidInput, error.kind());
+    }
+
+    #[test]
+    fn load_rom_bytes() {
+        let mut c = Chip::default();
+        c.load_rom_bytes(&[0x12, 0x34]).unwrap();
+        assert_eq!(0x12, c.memory().peek(0x200).unwrap());
+        assert_eq!(0x34, c.memory().peek(0x201).unwrap());
+        assert!(c.load_rom_bytes(&vec![0; 0xE01]).is_err());
+    }
+
+    #[test]
+    fn load_rom_reader() {
+        let mut c = Chip::default();
+        c.load_rom_reader(io::Cursor::new(vec![0x12, 0x34]))
+            .unwrap();
+        assert_eq!(0x12, c.memory().peek(0x200).unwrap());
+    }
+
+    #[test]
+    fn load_rom_auto_configure() {
+        let rom = [0x12, 0x00];
+        let database = romdb::RomDatabase::parse(&format!(
+            "[[rom]]\nsha1 = \"{}\"\ntitle = \"Loop\"\nplatform = \"eti660\"\nquirks = \"chip8\"",
+            romdb::sha1_hex(&rom)
+        ))
+        .unwrap();
+
+        let mut c = Chip::default();
+        c.enable_coverage();
+        c.set_rom_database(database.clone());
+        c.load_rom_bytes(&rom).unwrap();
+        assert_eq!(Some("Loop".to_string()), c.rom_info().unwrap().title);
+        assert_eq!(0x600, c.program_counter);
+        assert_eq!(0x12, c.memory().peek(0x600).unwrap());
+        assert!(c.config().quirks.vf_reset);
+        assert!(c.coverage().is_some());
 
-        let byte = 0b00000001;
-        let expected: Vec<u32> = vec![
-            0x00101010, 0x00101010, 0x00101010, 0x00101010, 0x00101010, 0x00101010, 0x00101010,
-            0x00EEEEEE,
+        let mut c = Chip::default();
+        c.set_rom_database(database);
+        c.set_auto_configure(false);
+        c.load_rom_bytes(&rom).unwrap();
+        assert!(c.rom_info().is_some());
+        assert_eq!(0x200, c.program_counter);
+        assert!(!c.config().quirks.vf_reset);
+    }
+
+    #[test]
+    fn failed_load_leaves_chip_unchanged() {
+        // Fits in 4 KiB, but not on the 2 KiB VIP the database asks for
+        let rom = vec![0x12; 0x700];
+        let database = romdb::RomDatabase::parse(&format!(
+            "[[rom]]\nsha1 = \"{}\"\nplatform = \"vip2k\"\nquirks = \"chip8\"",
+            romdb::sha1_hex(&rom)
+        ))
+        .unwrap();
+
+        let mut c = Chip::default();
+        c.set_rom_database(database);
+        c.load_rom_bytes(&[0x00, 0xE0]).unwrap();
+        c.tick().unwrap();
+        assert!(c.load_rom_bytes(&rom).is_err());
+
+        assert_eq!(&config::ChipConfig::default(), c.config());
+        assert_eq!(0x00, c.memory().peek(0x200).unwrap());
+        assert_eq!(0xE0, c.memory().peek(0x201).unwrap());
+        assert_eq!(0x202, c.program_counter);
+        assert!(c.rom_info().is_none());
+    }
+
+    #[test]
+    fn load_rom_builtin_database() {
+        // Maze [David Winter, 199x].ch8
+        let maze = [
+            0xA2, 0x1E, 0xC2, 0x01, 0x32, 0x01, 0xA2, 0x1A, 0xD0, 0x14, 0x70, 0x04, 0x30, 0x40,
+            0x12, 0x00, 0x60, 0x00, 0x71, 0x04, 0x31, 0x20, 0x12, 0x00, 0x12, 0x18, 0x80, 0x40,
+            0x20, 0x10, 0x20, 0x40, 0x80, 0x10,
         ];
+        let mut c = Chip::default();
+        c.load_rom_bytes(&maze).unwrap();
 
+        let info = c.rom_info().unwrap();
+        assert_eq!(Some("Maze".to_string()), info.title);
+        assert_eq!(Some(11), info.instructions_per_frame);
         assert_eq!(
-            expected,
-            RomWindow::new(1, "", &Chip::default()).expand_byte(byte)
+            "chip8".parse::<config::Quirks>().unwrap(),
+            c.config().quirks
         );
+        assert!(c.config().quirks.vf_reset);
     }
 
     #[test]
-    fn expand_screen_buffer() {
-        let chip_buffer: Vec<u8> = vec![0, 0b10000000, 0b00000001];
-        let expected = vec![
-            0x00101010, 0x00101010, 0x00101010, 0x00101010, 0x00101010, 0x00101010, 0x00101010,
-            0x00101010, 0x00EEEEEE, 0x00101010, 0x00101010, 0x00101010, 0x00101010, 0x00101010,
-            0x00101010, 0x00101010, 0x00101010, 0x00101010, 0x00101010, 0x00101010, 0x00101010,
-            0x00101010, 0x00101010, 0x00EEEEEE,
-        ];
+    fn load_rom_unknown() {
+        let mut c = Chip::default();
+        c.load_rom_bytes(&[0x12, 0x00]).unwrap();
+        assert!(c.rom_info().is_none());
+    }
+
+    #[test]
+    fn load_rom_does_not_fit() {
+        let mut c = Chip::with_config(config::ChipConfig::from(config::Platform::CosmacVip2K));
+        let filename = std::env::temp_dir().join("chip8_rs_load_rom_does_not_fit.c8");
+        let mut f = File::create(&filename).unwrap();
+        f.write_all(&vec![1; 0x601]).unwrap();
 
-        let window = RomWindow::new(1, "", &Chip::new(24, 1));
+        let error = c.load_rom(filename.to_str().unwrap()).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, error.kind());
+        assert!(error.to_string().contains("1537 bytes"));
+    }
 
-        assert_eq!(expected, window.expand_screen_buffer(&chip_buffer));
+    #[test]
+    fn init_fonts() {
+        let c = Chip::default();
+        assert_eq!(c.memory.peek(0).unwrap(), 0xF0);
     }
 
     #[test]
-    #[should_panic]
-    fn load_rom_too_big() {
+    fn seed_is_reproducible() {
+        // CXFF repeated, v0 - v3 get random values
+        let rom = [0xC0, 0xFF, 0xC1, 0xFF, 0xC2, 0xFF, 0xC3, 0xFF];
+        let run = |chip: &mut Chip| {
+            chip.load_rom_bytes(&rom).unwrap();
+            for _ in 0..4 {
+                chip.tick().unwrap();
+            }
+            chip.registers[..4].to_vec()
+        };
+
         let mut c = Chip::default();
-        let filename = "./test.c8";
-        let mut f = File::create(filename).unwrap();
-        let data = vec![1; 0x401];
+        c.set_seed(42);
+        let first = run(&mut c);
+
+        c.reset();
+        assert_eq!(first, run(&mut c));
 
-        f.write(&data[..]).unwrap();
-        c.load_rom(filename).unwrap();
+        let mut other = Chip::default();
+        other.set_seed(42);
+        assert_eq!(first, run(&mut other));
     }
 
     #[test]
-    fn init_fonts() {
-        let c = Chip::default();
-        assert_eq!(c.memory[0], 0xF0);
+    fn coverage_records_execution() {
+        let mut c = Chip::default();
+        assert!(c.coverage().is_none());
+
+        c.enable_coverage();
+        c.memory.load(0x200, &[0x60, 0x55]).unwrap(); // V0 = 0x55
+        c.tick().unwrap();
+
+        let coverage = c.coverage().unwrap();
+        assert_eq!(1, coverage.get(0x200).unwrap().executed);
+        assert_eq!(1, coverage.get(0x201).unwrap().executed);
+        assert_eq!(2, coverage.covered_bytes(0, c.memory().len()));
+        assert!(c.disable_coverage().is_some());
+        assert!(c.coverage().is_none());
+    }
+
+    #[test]
+    fn rom_range() {
+        let mut c = Chip::default();
+        assert_eq!(None, c.rom_range());
+        c.load_rom_bytes(&[0x12, 0x00, 0x00]).unwrap();
+        assert_eq!(Some(0x200..0x203), c.rom_range());
+    }
+
+    #[test]
+    fn reset_keeps_the_rom() {
+        let mut chip = Chip::default();
+        chip.set_quirks(config::Quirks {
+            vf_reset: true,
+            ..Default::default()
+        });
+        chip.load_rom_bytes(&[0x60, 0x2A, 0xA3, 0x00, 0xF0, 0x55])
+            .unwrap();
+        for _ in 0..3 {
+            chip.tick().unwrap();
+        }
+        assert_eq!(0x2A, chip.memory.peek(0x300).unwrap());
+
+        chip.reset();
+        assert_eq!(0x200, chip.program_counter);
+        assert_eq!(0, chip.registers[0]);
+        assert_eq!(0, chip.memory.peek(0x300).unwrap());
+        assert_eq!(0x60, chip.memory.peek(0x200).unwrap());
+        assert!(chip.config().quirks.vf_reset);
+    }
+
+    #[test]
+    fn reset_keeps_write_protection_and_coverage() {
+        let mut chip = Chip::default();
+        chip.load_rom_bytes(&[0x60, 0x2A, 0xA0, 0x00, 0xF0, 0x55])
+            .unwrap();
+        chip.memory_mut()
+            .set_write_protected(memory::RegionKind::Font, true);
+        chip.enable_coverage();
+
+        let mut config = chip.config().clone();
+        config.memory_size = 0x2000;
+        chip.reconfigure(config);
+        chip.reset();
+        assert!(chip.coverage().unwrap().get(0x1FFF).is_some());
+        for _ in 0..2 {
+            chip.tick().unwrap();
+        }
+        let error = chip.tick().unwrap_err();
+        assert_eq!(io::ErrorKind::PermissionDenied, error.kind());
+        assert_eq!(0x204, chip.program_counter);
+        assert_ne!(0x2A, chip.memory.peek(0).unwrap());
+    }
+
+    #[test]
+    fn soft_reset_keeps_memory() {
+        let mut chip = Chip::default();
+        chip.load_rom_bytes(&[0x60, 0x2A, 0xA3, 0x00, 0xF0, 0x55])
+            .unwrap();
+        for _ in 0..3 {
+            chip.tick().unwrap();
+        }
+        chip.screen_buffer.set_pixel(0, 0, true);
+
+        chip.soft_reset();
+        assert_eq!(0x200, chip.program_counter);
+        assert_eq!(0, chip.registers[0]);
+        assert_eq!(0, chip.address);
+        assert_eq!(0x2A, chip.memory.peek(0x300).unwrap());
+        assert!(!chip.screen_buffer.get_pixel(0, 0));
+    }
+
+    #[test]
+    fn reload_rom() {
+        let mut chip = Chip::default();
+        chip.load_rom_bytes(&[0x60, 0x01]).unwrap();
+        chip.tick().unwrap();
+
+        chip.reload_rom_bytes(&[0x61, 0x02]).unwrap();
+        assert_eq!(0x200, chip.program_counter);
+        assert_eq!(0x61, chip.memory.peek(0x200).unwrap());
+        chip.tick().unwrap();
+        assert_eq!(2, chip.registers[1]);
+
+        // A rom which doesn't fit leaves the chip as it was
+        assert!(chip.reload_rom_bytes(&vec![0; 0x1000]).is_err());
+        assert_eq!(0x61, chip.memory.peek(0x200).unwrap());
+        assert_eq!(2, chip.registers[1]);
+    }
+
+    #[test]
+    fn screen_changed() {
+        let mut chip = Chip::default();
+        chip.load_rom_bytes(&[0x60, 0x00, 0x00, 0xE0]).unwrap();
+        assert!(chip.take_screen_changed());
+        assert!(!chip.take_screen_changed());
+
+        chip.tick().unwrap();
+        assert!(!chip.take_screen_changed());
+        chip.tick().unwrap();
+        assert!(chip.take_screen_changed());
+    }
+
+    #[test]
+    fn display_wait() {
+        let mut chip = Chip::default();
+        chip.set_quirks(config::Quirks {
+            display_wait: true,
+            ..Default::default()
+        });
+
+        // Draw the same sprite twice, then loop forever
+        chip.load_rom_bytes(&[0xD0, 0x01, 0xD0, 0x01, 0x12, 0x04])
+            .unwrap();
+        assert!(chip.waiting_for_vblank());
+        chip.tick().unwrap();
+        assert_eq!(0x200, chip.program_counter);
+
+        chip.run_frame(100).unwrap();
+        assert_eq!(0x202, chip.program_counter);
+        assert!(chip.waiting_for_vblank());
+
+        chip.run_frame(100).unwrap();
+        assert_eq!(0x204, chip.program_counter);
+        assert!(!chip.waiting_for_vblank());
+    }
+
+    #[test]
+    fn timers_count_down_once_per_frame() {
+        // Set both timers to 10, then loop forever
+        let mut chip = Chip::default();
+        chip.load_rom_bytes(&[0x60, 0x0A, 0xF0, 0x15, 0xF0, 0x18, 0x12, 0x06])
+            .unwrap();
+        for _ in 0..3 {
+            chip.tick().unwrap();
+        }
+        assert_eq!(10, chip.delay_timer);
+
+        chip.run_frame(20).unwrap();
+        assert_eq!(9, chip.delay_timer);
+        assert_eq!(9, chip.sound_timer);
+
+        // Running instructions on their own leaves the timers alone
+        chip.tick().unwrap();
+        assert_eq!(9, chip.delay_timer);
+    }
+
+    #[test]
+    fn next_instruction_is_valid() {
+        let mut chip = Chip::default();
+        chip.load_rom_bytes(&[0x00, 0xE0, 0x00, 0xEE, 0x01, 0x23, 0xF0, 0xFF])
+            .unwrap();
+        assert!(chip.next_instruction_is_valid());
+
+        // Returning with an empty stack
+        chip.tick().unwrap();
+        assert!(!chip.next_instruction_is_valid());
+        chip.stack.push(0x200).unwrap();
+        assert!(chip.next_instruction_is_valid());
+
+        // Illegal opcodes, which tick returns as errors rather than running
+        chip.program_counter = 0x204;
+        assert!(!chip.next_instruction_is_valid());
+        assert!(chip.tick().is_err());
+        assert_eq!(0x204, chip.program_counter);
+        chip.program_counter = 0x206;
+        assert!(!chip.next_instruction_is_valid());
+        assert!(chip.tick().is_err());
+    }
+
+    #[test]
+    fn program_counter_wraps() {
+        let mut chip = Chip::default();
+        chip.memory.load(0xFFE, &[0x60, 0x42]).unwrap();
+        chip.program_counter = 0xFFE;
+        chip.tick().unwrap();
+        assert_eq!(0x42, chip.registers[0]);
+        assert_eq!(0x1000, chip.program_counter);
+
+        // The next fetch reads the font at the start of memory, F0 90
+        assert!(!chip.next_instruction_is_valid());
     }
 
     #[test]
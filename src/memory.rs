@@ -0,0 +1,481 @@
+use std::any::Any;
+use std::collections::VecDeque;
+use std::io::{Error, ErrorKind};
+use std::vec::Vec;
+
+/// The ways a byte of memory can be touched by a running program
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Access {
+    // Fetched by the cpu as part of an instruction
+    Execute,
+
+    // Read as data, e.g. sprites for DXYN or registers for FX65
+    Read,
+
+    // Written as data, e.g. FX33 or FX55
+    Write,
+}
+
+/// Bounds checked access to the chip's address space
+pub trait Bus {
+    /// Fetch a byte as part of an instruction
+    fn fetch(&mut self, address: usize) -> Result<u8, Error>;
+
+    /// Read a byte as data
+    fn read(&mut self, address: usize) -> Result<u8, Error>;
+
+    /// Write a byte
+    fn write(&mut self, address: usize, value: u8) -> Result<(), Error>;
+
+    /// The size of the address space in bytes
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Notified of every access that goes through the bus. Used for coverage, tracing and
+/// watchpoints.
+pub trait MemoryObserver: Any {
+    fn on_access(&mut self, address: usize, value: u8, access: Access);
+}
+
+/// The well known areas of the chip-8 address space
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RegionKind {
+    // The built in hex font
+    Font,
+
+    // Reserved for the original interpreter
+    Interpreter,
+
+    // Where roms are loaded and run
+    Program,
+}
+
+/// A contiguous range of memory [start, end)
+#[derive(Clone, Debug, PartialEq)]
+pub struct Region {
+    pub kind: RegionKind,
+    pub start: usize,
+    pub end: usize,
+    pub write_protected: bool,
+}
+
+impl Region {
+    pub fn contains(&self, address: usize) -> bool {
+        address >= self.start && address < self.end
+    }
+}
+
+/// The chip's main memory
+pub struct Memory {
+    data: Vec<u8>,
+    regions: Vec<Region>,
+    observers: Vec<Box<dyn MemoryObserver>>,
+}
+
+impl Memory {
+    /// Create zeroed memory of the given size, split into font, interpreter and program regions.
+    /// No region is write protected.
+    ///
+    /// # Arguments
+    ///
+    /// size The size of memory in bytes
+    /// font_size The number of bytes reserved for the font at address 0
+    /// program_start The first address of program memory
+    pub fn new(size: usize, font_size: usize, program_start: usize) -> Memory {
+        let font_end = font_size.min(size);
+        let program_start = program_start.max(font_end).min(size);
+        Memory {
+            data: vec![0; size],
+            regions: vec![
+                Region {
+                    kind: RegionKind::Font,
+                    start: 0,
+                    end: font_end,
+                    write_protected: false,
+                },
+                Region {
+                    kind: RegionKind::Interpreter,
+                    start: font_end,
+                    end: program_start,
+                    write_protected: false,
+                },
+                Region {
+                    kind: RegionKind::Program,
+                    start: program_start,
+                    end: size,
+                    write_protected: false,
+                },
+            ],
+            observers: Vec::new(),
+        }
+    }
+
+    /// Get the regions making up memory
+    pub fn regions(&self) -> &[Region] {
+        &self.regions
+    }
+
+    /// Get the region containing an address
+    pub fn region_at(&self, address: usize) -> Option<&Region> {
+        self.regions.iter().find(|region| region.contains(address))
+    }
+
+    /// Enable or disable write protection for every region of the given kind
+    pub fn set_write_protected(&mut self, kind: RegionKind, write_protected: bool) {
+        for region in self.regions.iter_mut().filter(|region| region.kind == kind) {
+            region.write_protected = write_protected;
+        }
+    }
+
+    /// The kinds of region which are write protected, e.g. to protect the same regions of new
+    /// memory
+    pub fn write_protected_kinds(&self) -> Vec<RegionKind> {
+        self.regions
+            .iter()
+            .filter(|region| region.write_protected)
+            .map(|region| region.kind)
+            .collect()
+    }
+
+    /// Copy bytes into memory starting at address. This bypasses write protection and observers
+    /// and is intended for loading fonts and roms.
+    pub fn load(&mut self, address: usize, bytes: &[u8]) -> Result<(), Error> {
+        let end = address
+            .checked_add(bytes.len())
+            .filter(|end| *end <= self.data.len())
+            .ok_or_else(|| {
+                Error::new(
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "{} bytes at {:#05X} do not fit in {} bytes of memory",
+                        bytes.len(),
+                        address,
+                        self.data.len()
+                    ),
+                )
+            })?;
+        self.data[address..end].copy_from_slice(bytes);
+        Ok(())
+    }
+
+    /// Read a byte without notifying observers, e.g. for debuggers and disassembly
+    pub fn peek(&self, address: usize) -> Result<u8, Error> {
+        self.data
+            .get(address)
+            .copied()
+            .ok_or_else(|| self.out_of_bounds(address))
+    }
+
+    /// Get a read only view of all of memory, bypassing observers
+    pub fn as_slice(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Register an observer to be notified of every access through the bus
+    pub fn add_observer(&mut self, observer: Box<dyn MemoryObserver>) {
+        self.observers.push(observer);
+    }
+
+    /// Unregister and return every observer, e.g. to move them to new memory
+    pub fn take_observers(&mut self) -> Vec<Box<dyn MemoryObserver>> {
+        std::mem::take(&mut self.observers)
+    }
+
+    /// Get the first registered observer of a given type
+    pub fn observer<T: MemoryObserver>(&self) -> Option<&T> {
+        self.observers
+            .iter()
+            .find_map(|observer| (observer.as_ref() as &dyn Any).downcast_ref::<T>())
+    }
+
+    /// Get the first registered observer of a given type, mutably
+    pub fn observer_mut<T: MemoryObserver>(&mut self) -> Option<&mut T> {
+        self.observers
+            .iter_mut()
+            .find_map(|observer| (observer.as_mut() as &mut dyn Any).downcast_mut::<T>())
+    }
+
+    /// Unregister and return the first observer of a given type
+    pub fn remove_observer<T: MemoryObserver>(&mut self) -> Option<T> {
+        let index = self
+            .observers
+            .iter()
+            .position(|observer| (observer.as_ref() as &dyn Any).is::<T>())?;
+        let observer: Box<dyn Any> = self.observers.remove(index);
+        observer.downcast::<T>().ok().map(|observer| *observer)
+    }
+
+    fn notify(&mut self, address: usize, value: u8, access: Access) {
+        for observer in self.observers.iter_mut() {
+            observer.on_access(address, value, access);
+        }
+    }
+
+    fn out_of_bounds(&self, address: usize) -> Error {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "Address {:#05X} is outside of {} bytes of memory",
+                address,
+                self.data.len()
+            ),
+        )
+    }
+}
+
+impl Bus for Memory {
+    fn fetch(&mut self, address: usize) -> Result<u8, Error> {
+        let value = self.peek(address)?;
+        self.notify(address, value, Access::Execute);
+        Ok(value)
+    }
+
+    fn read(&mut self, address: usize) -> Result<u8, Error> {
+        let value = self.peek(address)?;
+        self.notify(address, value, Access::Read);
+        Ok(value)
+    }
+
+    fn write(&mut self, address: usize, value: u8) -> Result<(), Error> {
+        if address >= self.data.len() {
+            return Err(self.out_of_bounds(address));
+        }
+        if let Some(region) = self.region_at(address) {
+            if region.write_protected {
+                return Err(Error::new(
+                    ErrorKind::PermissionDenied,
+                    format!(
+                        "Address {:#05X} is in write protected {:?} memory",
+                        address, region.kind
+                    ),
+                ));
+            }
+        }
+
+        self.data[address] = value;
+        self.notify(address, value, Access::Write);
+        Ok(())
+    }
+
+    fn len(&self) -> usize {
+        self.data.len()
+    }
+}
+
+/// A single access recorded by a Trace or Watchpoints observer
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AccessRecord {
+    pub address: usize,
+    pub value: u8,
+    pub access: Access,
+}
+
+/// Keeps the most recent memory accesses, oldest first
+#[derive(Debug)]
+pub struct Trace {
+    capacity: usize,
+    records: VecDeque<AccessRecord>,
+}
+
+impl Trace {
+    /// Create a trace which keeps at most capacity accesses
+    pub fn new(capacity: usize) -> Trace {
+        Trace {
+            capacity,
+            records: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    pub fn records(&self) -> impl Iterator<Item = &AccessRecord> {
+        self.records.iter()
+    }
+
+    pub fn clear(&mut self) {
+        self.records.clear();
+    }
+}
+
+impl MemoryObserver for Trace {
+    fn on_access(&mut self, address: usize, value: u8, access: Access) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.records.len() == self.capacity {
+            self.records.pop_front();
+        }
+        self.records.push_back(AccessRecord {
+            address,
+            value,
+            access,
+        });
+    }
+}
+
+/// Watch a range of memory [start, end) for a kind of access
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Watchpoint {
+    pub start: usize,
+    pub end: usize,
+    pub access: Access,
+}
+
+/// Records every access matching one of its watchpoints until the hits are taken
+#[derive(Debug, Default)]
+pub struct Watchpoints {
+    watchpoints: Vec<Watchpoint>,
+    hits: Vec<AccessRecord>,
+}
+
+impl Watchpoints {
+    pub fn new() -> Watchpoints {
+        Default::default()
+    }
+
+    pub fn add(&mut self, watchpoint: Watchpoint) {
+        self.watchpoints.push(watchpoint);
+    }
+
+    pub fn remove(&mut self, watchpoint: &Watchpoint) {
+        self.watchpoints.retain(|w| w != watchpoint);
+    }
+
+    /// Return and forget every hit since the last call
+    pub fn take_hits(&mut self) -> Vec<AccessRecord> {
+        std::mem::take(&mut self.hits)
+    }
+}
+
+impl MemoryObserver for Watchpoints {
+    fn on_access(&mut self, address: usize, value: u8, access: Access) {
+        let hit = self
+            .watchpoints
+            .iter()
+            .any(|w| w.access == access && address >= w.start && address < w.end);
+        if hit {
+            self.hits.push(AccessRecord {
+                address,
+                value,
+                access,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memory() -> Memory {
+        Memory::new(0x1000, 0x50, 0x200)
+    }
+
+    #[test]
+    fn regions() {
+        let m = memory();
+        assert_eq!(RegionKind::Font, m.region_at(0x0).unwrap().kind);
+        assert_eq!(RegionKind::Interpreter, m.region_at(0x50).unwrap().kind);
+        assert_eq!(RegionKind::Program, m.region_at(0x200).unwrap().kind);
+        assert_eq!(RegionKind::Program, m.region_at(0xFFF).unwrap().kind);
+        assert!(m.region_at(0x1000).is_none());
+    }
+
+    #[test]
+    fn read_write() {
+        let mut m = memory();
+        m.write(0x300, 0x42).unwrap();
+        assert_eq!(0x42, m.read(0x300).unwrap());
+        assert_eq!(0x42, m.fetch(0x300).unwrap());
+        assert_eq!(0x42, m.peek(0x300).unwrap());
+    }
+
+    #[test]
+    fn out_of_bounds() {
+        let mut m = memory();
+        assert_eq!(ErrorKind::InvalidInput, m.read(0x1000).unwrap_err().kind());
+        assert_eq!(
+            ErrorKind::InvalidInput,
+            m.write(0x1000, 0).unwrap_err().kind()
+        );
+        assert_eq!(ErrorKind::InvalidInput, m.fetch(0x1000).unwrap_err().kind());
+    }
+
+    #[test]
+    fn write_protected() {
+        let mut m = memory();
+        m.set_write_protected(RegionKind::Font, true);
+        let error = m.write(0x10, 0xFF).unwrap_err();
+        assert_eq!(ErrorKind::PermissionDenied, error.kind());
+        assert_eq!(0, m.peek(0x10).unwrap());
+        assert!(m.write(0x50, 0xFF).is_ok());
+
+        m.set_write_protected(RegionKind::Font, false);
+        assert!(m.write(0x10, 0xFF).is_ok());
+    }
+
+    #[test]
+    fn load() {
+        let mut m = memory();
+        m.set_write_protected(RegionKind::Font, true);
+        m.load(0, &[1, 2, 3]).unwrap();
+        assert_eq!(&[1, 2, 3], &m.as_slice()[0..3]);
+        assert!(m.load(0xFFF, &[1, 2]).is_err());
+    }
+
+    #[test]
+    fn trace() {
+        let mut m = memory();
+        m.add_observer(Box::new(Trace::new(2)));
+        m.write(0x200, 1).unwrap();
+        m.read(0x200).unwrap();
+        m.fetch(0x201).unwrap();
+
+        let records: Vec<&AccessRecord> = m.observer::<Trace>().unwrap().records().collect();
+        assert_eq!(2, records.len());
+        assert_eq!(Access::Read, records[0].access);
+        assert_eq!(0x201, records[1].address);
+    }
+
+    #[test]
+    fn watchpoints() {
+        let mut m = memory();
+        let mut watchpoints = Watchpoints::new();
+        watchpoints.add(Watchpoint {
+            start: 0x300,
+            end: 0x302,
+            access: Access::Write,
+        });
+        m.add_observer(Box::new(watchpoints));
+
+        m.write(0x2FF, 1).unwrap();
+        m.write(0x301, 2).unwrap();
+        m.read(0x301).unwrap();
+
+        let hits = m.observer_mut::<Watchpoints>().unwrap().take_hits();
+        assert_eq!(
+            vec![AccessRecord {
+                address: 0x301,
+                value: 2,
+                access: Access::Write
+            }],
+            hits
+        );
+        assert!(m
+            .observer_mut::<Watchpoints>()
+            .unwrap()
+            .take_hits()
+            .is_empty());
+    }
+
+    #[test]
+    fn remove_observer() {
+        let mut m = memory();
+        m.add_observer(Box::new(Trace::new(1)));
+        assert!(m.observer::<Watchpoints>().is_none());
+        assert!(m.remove_observer::<Trace>().is_some());
+        assert!(m.observer::<Trace>().is_none());
+    }
+}
@@ -1,8 +1,12 @@
+use super::framebuffer::Edge;
+use super::instruction::Instruction;
+use super::memory::Bus;
 use super::Chip;
 #[allow(unused_imports)]
 use super::Key;
 #[allow(unused_imports)]
 use super::KeyState;
+use rand::Rng;
 use std::io::{Error, ErrorKind};
 
 /// Represents a single 2 byte opcode and provides convenient access to each
@@ -16,104 +20,75 @@ impl Opcode {
         Opcode { opcode }
     }
 
-    fn n1(&self) -> u16 {
-        (self.opcode & 0xF000) >> 12
-    }
-
-    fn n2(&self) -> u16 {
-        (self.opcode & 0x0f00) >> 8
-    }
-
-    fn n3(&self) -> u16 {
-        (self.opcode & 0x00f0) >> 4
-    }
-
-    fn n4(&self) -> u16 {
-        self.opcode & 0x000F
-    }
-
-    fn constant(&self) -> u8 {
-        (self.opcode & 0xFF) as u8
-    }
-
-    /// Decode and execute a single instruction
+    /// Decode and execute a single instruction. Returns an error for illegal opcodes, calls and
+    /// returns which overflow or underflow the stack, and instructions which touch memory they
+    /// can't, e.g. FX33 or FX55 writing to a write protected region.
     ///
     /// # Arguments
     ///
     /// opcode The opcode to be executed
-    pub fn decode_execute(&self, mut chip: &mut Chip) {
-        match self.n1() {
-            0x0 => match self.n4() {
-                0x0 => self.clear_screen(&mut chip),
-                0xE => self.return_from_subroutine(&mut chip),
-                _ => panic!("Illegal opcode! {}", self.opcode),
-            },
-            0x1 => self.jump_unconditional(&mut chip, self.opcode & 0x0FFF),
-            0x2 => self.call_subroutine(&mut chip, self.opcode & 0x0FFF),
-            0x3 => self.skip_if_equal(&mut chip, usize::from(self.n2()), self.constant()),
-            0x4 => self.skip_if_not_equal(&mut chip, usize::from(self.n2()), self.constant()),
-            0x5 => {
-                self.skip_equal_registers(&mut chip, usize::from(self.n2()), usize::from(self.n3()))
-            }
-            0x6 => self.load_constant(&mut chip, usize::from(self.n2()), self.constant()),
-            0x7 => self.add_constant(&mut chip, usize::from(self.n2()), self.constant()),
-            0x8 => match self.n4() {
-                0x0 => {
-                    self.set_vx_from_vy(&mut chip, usize::from(self.n2()), usize::from(self.n3()))
-                }
-                0x1 => self.vx_or_vy(&mut chip, usize::from(self.n2()), usize::from(self.n3())),
-                0x2 => self.vx_and_vy(&mut chip, usize::from(self.n2()), usize::from(self.n3())),
-                0x3 => self.vx_xor_vy(&mut chip, usize::from(self.n2()), usize::from(self.n3())),
-                0x4 => self.add_vx_vy(&mut chip, usize::from(self.n2()), usize::from(self.n3())),
-                0x5 => {
-                    self.subtract_vx_vy(&mut chip, usize::from(self.n2()), usize::from(self.n3()))
-                }
-                0x6 => self.shift_right_vx(&mut chip, usize::from(self.n2())),
-                0x7 => {
-                    self.subtract_vy_vx(&mut chip, usize::from(self.n2()), usize::from(self.n3()))
-                }
-                0xE => self.shift_left_vx(&mut chip, usize::from(self.n3())),
-                _ => (panic!("Illegal opcode! {}", self.opcode)),
-            },
-            0x9 => {
-                self.skip_vx_not_equal_vy(&mut chip, usize::from(self.n2()), usize::from(self.n3()))
-            }
-            0xA => self.set_address_register(&mut chip, self.opcode),
-            0xB => self.jump_addr_v0(&mut chip, self.opcode),
-            0xC => self.set_vx_rand(&mut chip, usize::from(self.n2()), self.constant()),
-            0xD => self.draw_sprite(
-                &mut chip,
-                usize::from(self.n2()),
-                usize::from(self.n3()),
-                (self.n1() & 0xFF) as u8,
-            ),
-            0xE => match self.n3() {
-                0x9 => self.skip_on_keypress(&mut chip, usize::from(self.n2())),
-                0xA => self.skip_not_keypress(&mut chip, usize::from(self.n2())),
-                _ => (panic!("Illegal opcode! {}", self.opcode)),
-            },
-            0xF => match self.n3() {
-                0x0 => match self.n4() {
-                    0x7 => self.get_delay_timer(&mut chip, usize::from(self.n2())),
-                    0xA => self.wait_for_key(&mut chip, usize::from(self.n2())),
-                    _ => (panic!("Illegal opcode! {}", self.opcode)),
-                },
-                0x1 => match self.n4() {
-                    0x5 => self.set_delay_timer(&mut chip, usize::from(self.n2())),
-                    0x8 => self.set_sound_timer(&mut chip, usize::from(self.n2())),
-                    0xE => self.add_vx_to_address_register(&mut chip, usize::from(self.n2())),
-                    _ => (panic!("Illegal opcode! {}", self.opcode)),
-                },
-                0x2 => self.get_font_sprite(&mut chip, usize::from(self.n2())),
-                0x3 => self.get_binary_coded_decimal(&mut chip, usize::from(self.n2())),
-                0x5 => self.register_dump(&mut chip, usize::from(self.n2())),
-                0x6 => self.register_load(&mut chip, usize::from(self.n2())),
-                _ => (panic!("Illegal opcode! {}", self.opcode)),
-            },
-            _ => (panic!("Illegal opcode! {}", self.opcode)),
+    pub fn decode_execute(&self, chip: &mut Chip) -> Result<(), Error> {
+        let instruction = Instruction::decode(self.opcode).ok_or_else(|| self.illegal())?;
+        let reg = usize::from;
+        match instruction {
+            // Machine code routines can't be run
+            Instruction::Sys(_) => return Err(self.illegal()),
+            Instruction::Cls => self.clear_screen(chip),
+            Instruction::Ret => self.return_from_subroutine(chip)?,
+            Instruction::Jp(nnn) => self.jump_unconditional(chip, nnn),
+            Instruction::Call(nnn) => self.call_subroutine(chip, nnn)?,
+            Instruction::SeByte(x, nn) => self.skip_if_equal(chip, reg(x), nn),
+            Instruction::SneByte(x, nn) => self.skip_if_not_equal(chip, reg(x), nn),
+            Instruction::SeReg(x, y) => self.skip_equal_registers(chip, reg(x), reg(y)),
+            Instruction::LdByte(x, nn) => self.load_constant(chip, reg(x), nn),
+            Instruction::AddByte(x, nn) => self.add_constant(chip, reg(x), nn),
+            Instruction::LdReg(x, y) => self.set_vx_from_vy(chip, reg(x), reg(y)),
+            Instruction::Or(x, y) => self.vx_or_vy(chip, reg(x), reg(y)),
+            Instruction::And(x, y) => self.vx_and_vy(chip, reg(x), reg(y)),
+            Instruction::Xor(x, y) => self.vx_xor_vy(chip, reg(x), reg(y)),
+            Instruction::AddReg(x, y) => self.add_vx_vy(chip, reg(x), reg(y)),
+            Instruction::Sub(x, y) => self.subtract_vx_vy(chip, reg(x), reg(y)),
+            Instruction::Shr(x, y) => self.shift_right_vx(chip, reg(x), reg(y)),
+            Instruction::Subn(x, y) => self.subtract_vy_vx(chip, reg(x), reg(y)),
+            Instruction::Shl(x, y) => self.shift_left_vx(chip, reg(x), reg(y)),
+            Instruction::SneReg(x, y) => self.skip_vx_not_equal_vy(chip, reg(x), reg(y)),
+            Instruction::LdI(nnn) => self.set_address_register(chip, nnn),
+            Instruction::JpV0(nnn) => self.jump_addr_v0(chip, nnn),
+            Instruction::Rnd(x, nn) => self.set_vx_rand(chip, reg(x), nn),
+            Instruction::Drw(x, y, n) => self.draw_sprite(chip, reg(x), reg(y), n)?,
+            Instruction::Skp(x) => self.skip_on_keypress(chip, reg(x)),
+            Instruction::Sknp(x) => self.skip_not_keypress(chip, reg(x)),
+            Instruction::LdVxDt(x) => self.get_delay_timer(chip, reg(x)),
+            Instruction::LdVxK(x) => self.wait_for_key(chip, reg(x)),
+            Instruction::LdDtVx(x) => self.set_delay_timer(chip, reg(x)),
+            Instruction::LdStVx(x) => self.set_sound_timer(chip, reg(x)),
+            Instruction::AddI(x) => self.add_vx_to_address_register(chip, reg(x)),
+            Instruction::LdF(x) => self.get_font_sprite(chip, reg(x)),
+            Instruction::LdB(x) => self.get_binary_coded_decimal(chip, reg(x))?,
+            Instruction::LdIVx(x) => self.register_dump(chip, reg(x))?,
+            Instruction::LdVxI(x) => self.register_load(chip, reg(x))?,
+        }
+        Ok(())
+    }
+
+    /// Whether decode_execute can run the opcode on the chip, rather than returning an error for
+    /// an illegal opcode or a stack overflow or underflow
+    pub fn is_valid(&self, chip: &Chip) -> bool {
+        match Instruction::decode(self.opcode) {
+            Some(Instruction::Sys(_)) | None => false,
+            Some(Instruction::Ret) => chip.stack.head > 0,
+            Some(Instruction::Call(_)) => chip.stack.head < chip.stack.size,
+            Some(_) => true,
         }
     }
 
+    fn illegal(&self) -> Error {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("Illegal opcode {:04X}", self.opcode),
+        )
+    }
+
     fn valid_registers(registers: &[usize], chip: &Chip) -> Result<(), Error> {
         if registers.is_empty() {
             return Err(Error::from(ErrorKind::Other));
@@ -132,17 +107,21 @@ impl Opcode {
     /// Clear the screen buffer
     fn clear_screen(&self, chip: &mut Chip) {
         chip.screen_buffer.clear();
+        chip.screen_changed = true;
         chip.increment_program_counter(None);
     }
 
     /// Function return
-    fn return_from_subroutine(&self, chip: &mut Chip) {
-        if let Ok(addr) = chip.stack.pop() {
-            chip.program_counter = addr;
-            chip.increment_program_counter(None);
-        } else {
-            panic!("Error popping value off the stack. Exiting...");
-        }
+    fn return_from_subroutine(&self, chip: &mut Chip) -> Result<(), Error> {
+        let addr = chip.stack.pop().map_err(|_| {
+            Error::new(
+                ErrorKind::InvalidData,
+                "Returned from a subroutine with an empty call stack",
+            )
+        })?;
+        chip.program_counter = addr;
+        chip.increment_program_counter(None);
+        Ok(())
     }
 
     /// Jump to the given address
@@ -154,15 +133,18 @@ impl Opcode {
     }
 
     /// Call a given subroutine
-    fn call_subroutine(&self, chip: &mut Chip, address: u16) {
+    fn call_subroutine(&self, chip: &mut Chip, address: u16) -> Result<(), Error> {
         if (address & 0xF000) != 0 {
             panic!("Invalid memory address provided to call_subroutine!");
         }
-        if chip.stack.push(chip.program_counter).is_ok() {
-            chip.program_counter = address;
-        } else {
-            panic!("The call stack has run out of space!");
-        }
+        chip.stack.push(chip.program_counter).map_err(|_| {
+            Error::new(
+                ErrorKind::InvalidData,
+                "The call stack has run out of space",
+            )
+        })?;
+        chip.program_counter = address;
+        Ok(())
     }
 
     /// Skip the next instruction if the value matches the given register
@@ -211,43 +193,52 @@ impl Opcode {
     ///
     /// note Carry flag is not changed
     fn add_constant(&self, chip: &mut Chip, vx: usize, value: u8) {
-        Opcode::valid_registers(&[vx], &chip).expect("Invalid register in add_constant");
-        chip.registers[vx] += chip.registers[vx].wrapping_add(value);
+        Opcode::valid_registers(&[vx], chip).expect("Invalid register in add_constant");
+        chip.registers[vx] = chip.registers[vx].wrapping_add(value);
         chip.increment_program_counter(None);
     }
 
     /// Set the value of vx to vy
     fn set_vx_from_vy(&self, chip: &mut Chip, vx: usize, vy: usize) {
-        Opcode::valid_registers(&[vx, vy], &chip).expect("Invalid register in set_vx_from_vy");
+        Opcode::valid_registers(&[vx, vy], chip).expect("Invalid register in set_vx_from_vy");
         chip.registers[vx] = chip.registers[vy];
         chip.increment_program_counter(None);
     }
 
     /// Sets vx = vx | vy
     fn vx_or_vy(&self, chip: &mut Chip, vx: usize, vy: usize) {
-        Opcode::valid_registers(&[vx, vy], &chip).expect("Invalid register in vx_or_vy");
+        Opcode::valid_registers(&[vx, vy], chip).expect("Invalid register in vx_or_vy");
         chip.registers[vx] |= chip.registers[vy];
+        if chip.config.quirks.vf_reset {
+            chip.registers[0xF] = 0;
+        }
         chip.increment_program_counter(None);
     }
 
     /// Sets vx = vx & vy
     fn vx_and_vy(&self, chip: &mut Chip, vx: usize, vy: usize) {
-        Opcode::valid_registers(&[vx, vy], &chip).expect("Invalid register in vx_and_vy");
+        Opcode::valid_registers(&[vx, vy], chip).expect("Invalid register in vx_and_vy");
         chip.registers[vx] &= chip.registers[vy];
+        if chip.config.quirks.vf_reset {
+            chip.registers[0xF] = 0;
+        }
         chip.increment_program_counter(None);
     }
 
     /// Sets vx = vx ^ vy
     fn vx_xor_vy(&self, chip: &mut Chip, vx: usize, vy: usize) {
-        Opcode::valid_registers(&[vx, vy], &chip).expect("Invalid register in vx_xor_vy");
+        Opcode::valid_registers(&[vx, vy], chip).expect("Invalid register in vx_xor_vy");
         chip.registers[vx] ^= chip.registers[vy];
+        if chip.config.quirks.vf_reset {
+            chip.registers[0xF] = 0;
+        }
         chip.increment_program_counter(None);
     }
 
     /// vx = vx + vy
     /// vf is set to 1 if overflow occurs
     fn add_vx_vy(&self, chip: &mut Chip, vx: usize, vy: usize) {
-        Opcode::valid_registers(&[vx, vy], &chip).expect("Invalid register in add_vx_vy");
+        Opcode::valid_registers(&[vx, vy], chip).expect("Invalid register in add_vx_vy");
 
         let (x, overflow) = chip.registers[vx].overflowing_add(chip.registers[vy]);
         chip.registers[vx] = x;
@@ -257,7 +248,7 @@ impl Opcode {
 
     /// Subtract vy from vx
     fn subtract_vx_vy(&self, chip: &mut Chip, vx: usize, vy: usize) {
-        Opcode::valid_registers(&[vx, vy], &chip).expect("Invalid register in subtract_vx_vy");
+        Opcode::valid_registers(&[vx, vy], chip).expect("Invalid register in subtract_vx_vy");
 
         let (x, overflow) = chip.registers[vx].overflowing_sub(chip.registers[vy]);
         chip.registers[vx] = x;
@@ -265,17 +256,20 @@ impl Opcode {
         chip.increment_program_counter(None);
     }
 
-    /// shift vx once to the right. Store lsb in vf
-    fn shift_right_vx(&self, chip: &mut Chip, vx: usize) {
-        Opcode::valid_registers(&[vx], &chip).expect("Invalid register in shift_right_vx");
+    /// shift vx once to the right. Store lsb in vf. With the shift_uses_vy quirk, vy is shifted
+    /// into vx instead.
+    fn shift_right_vx(&self, chip: &mut Chip, vx: usize, vy: usize) {
+        Opcode::valid_registers(&[vx, vy], chip).expect("Invalid register in shift_right_vx");
 
-        chip.registers[0xF] = chip.registers[vx] & 0x1;
-        chip.registers[vx] >>= 1;
+        let value = Opcode::shift_source(chip, vx, vy);
+        chip.registers[vx] = value >> 1;
+        chip.registers[0xF] = value & 0x1;
+        chip.increment_program_counter(None);
     }
 
     /// Subtract vx from vy
     fn subtract_vy_vx(&self, chip: &mut Chip, vx: usize, vy: usize) {
-        Opcode::valid_registers(&[vx, vy], &chip).expect("Invalid register in subtract_vy_vx");
+        Opcode::valid_registers(&[vx, vy], chip).expect("Invalid register in subtract_vy_vx");
 
         let (x, overflow) = chip.registers[vy].overflowing_sub(chip.registers[vx]);
         chip.registers[vx] = x;
@@ -283,18 +277,29 @@ impl Opcode {
         chip.increment_program_counter(None);
     }
 
-    /// Left shift vx, store ms_bit in vf
-    fn shift_left_vx(&self, chip: &mut Chip, vx: usize) {
-        Opcode::valid_registers(&[vx], &chip).expect("Invalid register in shift_left_vx");
+    /// Left shift vx, store ms_bit in vf. With the shift_uses_vy quirk, vy is shifted into vx
+    /// instead.
+    fn shift_left_vx(&self, chip: &mut Chip, vx: usize, vy: usize) {
+        Opcode::valid_registers(&[vx, vy], chip).expect("Invalid register in shift_left_vx");
 
-        chip.registers[0xF] = ((chip.registers[vx] & 0x80) >> 7) & 0x1;
-        chip.registers[vx] <<= 1;
+        let value = Opcode::shift_source(chip, vx, vy);
+        chip.registers[vx] = value << 1;
+        chip.registers[0xF] = ((value & 0x80) >> 7) & 0x1;
+        chip.increment_program_counter(None);
+    }
+
+    /// Get the register value shifted by 8XY6 and 8XYE
+    fn shift_source(chip: &Chip, vx: usize, vy: usize) -> u8 {
+        if chip.config.quirks.shift_uses_vy {
+            chip.registers[vy]
+        } else {
+            chip.registers[vx]
+        }
     }
 
     /// Skip next instruction if vx != vy
     fn skip_vx_not_equal_vy(&self, chip: &mut Chip, vx: usize, vy: usize) {
-        Opcode::valid_registers(&[vx, vy], &chip)
-            .expect("Invalid register in skip_vx_not_equal_vy");
+        Opcode::valid_registers(&[vx, vy], chip).expect("Invalid register in skip_vx_not_equal_vy");
 
         if chip.registers[vx] != chip.registers[vy] {
             chip.increment_program_counter(Some(2));
@@ -309,71 +314,77 @@ impl Opcode {
         chip.increment_program_counter(None);
     }
 
-    /// Jump to address + v0
+    /// Jump to address + v0. With the jump_uses_vx quirk, BXNN jumps to XNN + vx instead.
     fn jump_addr_v0(&self, chip: &mut Chip, addr: u16) {
         let mask_addr = addr & 0x0FFF;
-        chip.program_counter += mask_addr + u16::from(chip.registers[0]);
+        let register = if chip.config.quirks.jump_uses_vx {
+            usize::from((mask_addr & 0x0F00) >> 8)
+        } else {
+            0
+        };
+        chip.program_counter = mask_addr + u16::from(chip.registers[register]);
     }
 
     /// Set vx to a random value (0..255)
     fn set_vx_rand(&self, chip: &mut Chip, vx: usize, constant: u8) {
-        Opcode::valid_registers(&[vx], &chip).expect("Invalid register in shift_right_vx");
-        let random_byte = rand::random::<u8>();
+        Opcode::valid_registers(&[vx], chip).expect("Invalid register in shift_right_vx");
+        let random_byte = chip.rng.gen::<u8>();
         chip.registers[vx] = random_byte & constant;
         chip.increment_program_counter(None);
     }
 
-    /// Determine if a pixel has switched from 1 to 0
-    fn pixel_collision(b1: u8, b2: u8) -> bool {
-        for shift in 0..0x8 {
-            let mask = 1 << shift;
-
-            if b1 & mask == 0 {
-                continue;
-            } else if b2 & mask != 0 {
-                return true;
+    /// Draw a sprite of the given height from I at (vx, vy). vf is set if any pixel was erased.
+    /// With the clip_sprites quirk, sprites are clipped at the edge of the screen rather than
+    /// wrapping around. With the display_wait quirk, the instruction is repeated until the next
+    /// vertical blank interrupt.
+    fn draw_sprite(&self, chip: &mut Chip, vx: usize, vy: usize, height: u8) -> Result<(), Error> {
+        Opcode::valid_registers(&[vx, vy], chip).expect("Invalid register in draw_sprite");
+        if chip.config.quirks.display_wait {
+            if !chip.vblank {
+                return Ok(());
             }
+            chip.vblank = false;
         }
-        false
-    }
-
-    /// Draw a sprite to the screen
-    fn draw_sprite(&self, chip: &mut Chip, vx: usize, vy: usize, height: u8) {
-        Opcode::valid_registers(&[vx, vy], &chip).expect("Invalid register in draw_sprite");
 
-        chip.registers[0xF] = 0;
-
-        for row in 0..height {
-            let chunk_index: usize = ((chip.registers[vy] + row) as usize * chip.screen_width)
-                + chip.registers[vx] as usize;
-            let chunk: u8 = chip.screen_buffer[chunk_index];
-            let new = chip.memory[usize::from(chip.address) + usize::from(row)];
-
-            chip.screen_buffer[chunk_index] = chunk ^ new;
-            if Opcode::pixel_collision(chunk, new) {
-                chip.registers[0xF] = 1;
-                break;
-            }
-        }
+        let sprite = (0..usize::from(height))
+            .map(|row| {
+                let address = chip.address_from_i(row);
+                chip.memory.read(address)
+            })
+            .collect::<Result<Vec<u8>, Error>>()?;
+        let edge = if chip.config.quirks.clip_sprites {
+            Edge::Clip
+        } else {
+            Edge::Wrap
+        };
+        let collisions = chip.screen_buffer.draw_sprite(
+            usize::from(chip.registers[vx]),
+            usize::from(chip.registers[vy]),
+            &sprite,
+            edge,
+        );
+        chip.registers[0xF] = u8::from(collisions > 0);
+        chip.screen_changed = true;
         chip.increment_program_counter(None);
+        Ok(())
     }
 
-    /// Skip the next instruction if a given key is pressed
+    /// Skip the next instruction if the key in the low nibble of vx is pressed
     fn skip_on_keypress(&self, chip: &mut Chip, vx: usize) {
-        Opcode::valid_registers(&[vx], &chip).expect("Invalid register in skip_on_keypress");
+        Opcode::valid_registers(&[vx], chip).expect("Invalid register in skip_on_keypress");
 
-        if chip.keys[usize::from(chip.registers[vx])].is_pressed() {
+        if chip.keys[usize::from(chip.registers[vx] & 0xF)].is_pressed() {
             chip.increment_program_counter(Some(2));
         } else {
             chip.increment_program_counter(None);
         }
     }
 
-    /// Skip the next instruction if a given key is not pressed
+    /// Skip the next instruction if the key in the low nibble of vx is not pressed
     fn skip_not_keypress(&self, chip: &mut Chip, vx: usize) {
-        Opcode::valid_registers(&[vx], &chip).expect("Invalid register in skip_not_keypress");
+        Opcode::valid_registers(&[vx], chip).expect("Invalid register in skip_not_keypress");
 
-        if chip.keys[usize::from(chip.registers[vx])].is_pressed() {
+        if chip.keys[usize::from(chip.registers[vx] & 0xF)].is_pressed() {
             chip.increment_program_counter(None);
         } else {
             chip.increment_program_counter(Some(2));
@@ -388,7 +399,7 @@ impl Opcode {
 
     /// Block execution until any key is pressed
     fn wait_for_key(&self, chip: &mut Chip, vx: usize) {
-        Opcode::valid_registers(&[vx], &chip).expect("Invalid register in wait_for_key");
+        Opcode::valid_registers(&[vx], chip).expect("Invalid register in wait_for_key");
 
         if let Some(key) = chip.get_pressed_key() {
             chip.registers[vx] = key as u8;
@@ -398,87 +409,81 @@ impl Opcode {
 
     /// Set the delay timer
     fn set_delay_timer(&self, chip: &mut Chip, vx: usize) {
-        Opcode::valid_registers(&[vx], &chip).expect("Invalid register in set_delay_timer");
+        Opcode::valid_registers(&[vx], chip).expect("Invalid register in set_delay_timer");
         chip.delay_timer = chip.registers[vx];
         chip.increment_program_counter(None);
     }
 
     /// Set the sound timer
     fn set_sound_timer(&self, chip: &mut Chip, vx: usize) {
-        Opcode::valid_registers(&[vx], &chip).expect("Invalid register in set_sound_timer");
+        Opcode::valid_registers(&[vx], chip).expect("Invalid register in set_sound_timer");
         chip.sound_timer = chip.registers[vx];
         chip.increment_program_counter(None);
     }
 
-    /// Add vx to the address register I. If overflow occurs, vf is set to 1.
-    /// vf is set to 0 otherwise.
+    /// Add vx to the address register I. vf is left alone, as on the COSMAC VIP.
     fn add_vx_to_address_register(&self, chip: &mut Chip, vx: usize) {
-        Opcode::valid_registers(&[vx], &chip)
+        Opcode::valid_registers(&[vx], chip)
             .expect("Invalid register in add_vx_to_address_register");
 
-        let (result, overflow) = chip.address.overflowing_add(u16::from(chip.registers[vx]));
-        chip.address = result;
-        if overflow {
-            chip.registers[0xF] = 1;
-        } else {
-            chip.registers[0xF] = 0;
-        }
+        chip.address = chip.address.wrapping_add(u16::from(chip.registers[vx]));
         chip.increment_program_counter(None);
     }
 
-    /// Set address to the sprite in vx
+    /// Set address to the font sprite for the digit in the low nibble of vx
     fn get_font_sprite(&self, chip: &mut Chip, vx: usize) {
-        Opcode::valid_registers(&[vx], &chip).expect("Invalid register in get_font_sprite");
+        Opcode::valid_registers(&[vx], chip).expect("Invalid register in get_font_sprite");
 
-        chip.address = u16::from(chip.registers[vx] * 5);
+        chip.address = u16::from(chip.registers[vx] & 0xF) * 5;
         chip.increment_program_counter(None);
     }
 
-    /// Convert vx to a binary coded decimal value
-    fn get_binary_coded_decimal(&self, chip: &mut Chip, vx: usize) {
-        Opcode::valid_registers(&[vx], &chip)
-            .expect("Invalid register in get_binary_coded_decimal");
+    /// Store the hundreds, tens and ones digits of vx at I, I + 1 and I + 2
+    fn get_binary_coded_decimal(&self, chip: &mut Chip, vx: usize) -> Result<(), Error> {
+        Opcode::valid_registers(&[vx], chip).expect("Invalid register in get_binary_coded_decimal");
 
-        let mut result: u8 = 0;
-        let mut binary_value = chip.registers[vx];
-        for exponent in 0..8 {
-            let bit = (binary_value & 0x1) == 1;
-            if bit {
-                result += 2u8.pow(exponent);
-            }
-            binary_value >>= 1;
+        let value = chip.registers[vx];
+        for (offset, digit) in [value / 100, value / 10 % 10, value % 10]
+            .iter()
+            .enumerate()
+        {
+            let address = chip.address_from_i(offset);
+            chip.memory.write(address, *digit)?;
         }
-
-        chip.address += 2;
-        chip.memory[usize::from(chip.address)] = result % 10;
-        result /= 10;
-        chip.address -= 1;
-        chip.memory[usize::from(chip.address)] = result % 10;
-        result /= 10;
-        chip.address -= 1;
-        chip.memory[usize::from(chip.address)] = result % 10;
         chip.increment_program_counter(None);
+        Ok(())
     }
 
     /// Store v0 - vx inclusive into the address register. The address register
-    /// is unchanged.
-    fn register_dump(&self, chip: &mut Chip, vx: usize) {
-        Opcode::valid_registers(&[vx], &chip).expect("Invalid register in register_dump");
+    /// is unchanged, unless the memory_increments_i quirk is set.
+    fn register_dump(&self, chip: &mut Chip, vx: usize) -> Result<(), Error> {
+        Opcode::valid_registers(&[vx], chip).expect("Invalid register in register_dump");
 
-        for i in 0..=vx as usize {
-            chip.memory[usize::from(chip.address) + i] = chip.registers[i];
+        for i in 0..=vx {
+            let address = chip.address_from_i(i);
+            chip.memory.write(address, chip.registers[i])?;
+        }
+        if chip.config.quirks.memory_increments_i {
+            chip.address = chip.address.wrapping_add(vx as u16 + 1);
         }
         chip.increment_program_counter(None);
+        Ok(())
     }
 
-    /// Load v0 - vx inclusive from memory
-    fn register_load(&self, chip: &mut Chip, vx: usize) {
-        Opcode::valid_registers(&[vx], &chip).expect("Invalid register in register_load");
+    /// Load v0 - vx inclusive from memory. The address register is unchanged, unless the
+    /// memory_increments_i quirk is set.
+    fn register_load(&self, chip: &mut Chip, vx: usize) -> Result<(), Error> {
+        Opcode::valid_registers(&[vx], chip).expect("Invalid register in register_load");
 
         for i in 0..=vx {
-            chip.registers[i] = chip.memory[usize::from(chip.address) + i];
+            let address = chip.address_from_i(i);
+            chip.registers[i] = chip.memory.read(address)?;
+        }
+        if chip.config.quirks.memory_increments_i {
+            chip.address = chip.address.wrapping_add(vx as u16 + 1);
         }
         chip.increment_program_counter(None);
+        Ok(())
     }
 }
 
@@ -491,9 +496,9 @@ mod tests {
         let (mut chip, opcode) = chip_opcode();
         chip.program_counter = 0x200;
         chip.address = 0x300;
-        chip.memory[usize::from(chip.address)] = 0x44;
+        chip.memory.write(usize::from(chip.address), 0x44).unwrap();
         let vx = 0;
-        opcode.register_load(&mut chip, vx);
+        opcode.register_load(&mut chip, vx).unwrap();
         assert_eq!(0x202, chip.program_counter);
         assert_eq!(chip.registers[vx], 0x44);
     }
@@ -504,9 +509,20 @@ mod tests {
         chip.program_counter = 0x200;
         chip.address = 0x300;
         chip.registers[0] = 33;
-        opcode.register_dump(&mut chip, 0);
+        opcode.register_dump(&mut chip, 0).unwrap();
         assert_eq!(0x202, chip.program_counter);
-        assert_eq!(33, chip.memory[usize::from(chip.address)]);
+        assert_eq!(33, chip.memory.peek(usize::from(chip.address)).unwrap());
+    }
+
+    #[test]
+    fn memory_increments_i_quirk() {
+        let (mut chip, opcode) = chip_opcode();
+        chip.config.quirks.memory_increments_i = true;
+        chip.address = 0x300;
+        opcode.register_dump(&mut chip, 2).unwrap();
+        assert_eq!(0x303, chip.address);
+        opcode.register_load(&mut chip, 0).unwrap();
+        assert_eq!(0x304, chip.address);
     }
 
     #[test]
@@ -517,24 +533,45 @@ mod tests {
         for i in 0..0x10_u8 {
             chip.registers[usize::from(i)] = i;
         }
-        opcode.register_dump(&mut chip, 0xF);
+        opcode.register_dump(&mut chip, 0xF).unwrap();
         assert_eq!(0x202, chip.program_counter);
         for i in 0..0x10_u16 {
-            assert_eq!(i as u8, chip.memory[usize::from(chip.address + i)]);
+            assert_eq!(
+                i as u8,
+                chip.memory.peek(usize::from(chip.address + i)).unwrap()
+            );
         }
     }
 
+    #[test]
+    fn memory_wraps_past_the_end() {
+        let (mut chip, opcode) = chip_opcode();
+        chip.config.quirks.memory_increments_i = true;
+        chip.address = 0xFFE;
+        chip.registers[..4].copy_from_slice(&[1, 2, 3, 4]);
+        opcode.register_dump(&mut chip, 3).unwrap();
+        assert_eq!(&[1, 2], &chip.memory.as_slice()[0xFFE..]);
+        assert_eq!(&[3, 4], &chip.memory.as_slice()[..2]);
+        assert_eq!(0x1002, chip.address);
+
+        chip.address = 0xFFF;
+        chip.registers[0] = 123;
+        opcode.get_binary_coded_decimal(&mut chip, 0).unwrap();
+        assert_eq!(1, chip.memory.peek(0xFFF).unwrap());
+        assert_eq!(&[2, 3], &chip.memory.as_slice()[..2]);
+    }
+
     #[test]
     fn get_binary_coded_decimal_0() {
         let (mut chip, opcode) = chip_opcode();
         chip.program_counter = 0x200;
         chip.address = 0x300;
         chip.registers[0] = 0;
-        opcode.get_binary_coded_decimal(&mut chip, 0);
+        opcode.get_binary_coded_decimal(&mut chip, 0).unwrap();
         assert_eq!(0x202, chip.program_counter);
-        assert_eq!(chip.memory[usize::from(chip.address)], 0);
-        assert_eq!(chip.memory[usize::from(chip.address + 1)], 0);
-        assert_eq!(chip.memory[usize::from(chip.address + 2)], 0);
+        assert_eq!(chip.memory.peek(usize::from(chip.address)).unwrap(), 0);
+        assert_eq!(chip.memory.peek(usize::from(chip.address + 1)).unwrap(), 0);
+        assert_eq!(chip.memory.peek(usize::from(chip.address + 2)).unwrap(), 0);
     }
 
     #[test]
@@ -543,11 +580,23 @@ mod tests {
         chip.program_counter = 0x200;
         chip.address = 0x300;
         chip.registers[0] = 255;
-        opcode.get_binary_coded_decimal(&mut chip, 0);
+        opcode.get_binary_coded_decimal(&mut chip, 0).unwrap();
         assert_eq!(0x202, chip.program_counter);
-        assert_eq!(chip.memory[usize::from(chip.address)], 2);
-        assert_eq!(chip.memory[usize::from(chip.address + 1)], 5);
-        assert_eq!(chip.memory[usize::from(chip.address + 2)], 5);
+        assert_eq!(chip.memory.peek(usize::from(chip.address)).unwrap(), 2);
+        assert_eq!(chip.memory.peek(usize::from(chip.address + 1)).unwrap(), 5);
+        assert_eq!(chip.memory.peek(usize::from(chip.address + 2)).unwrap(), 5);
+    }
+
+    #[test]
+    fn get_binary_coded_decimal_write_protected() {
+        let (mut chip, opcode) = chip_opcode();
+        chip.program_counter = 0x200;
+        chip.address = 0x10;
+        chip.memory_mut()
+            .set_write_protected(crate::memory::RegionKind::Font, true);
+        let error = opcode.get_binary_coded_decimal(&mut chip, 0).unwrap_err();
+        assert_eq!(ErrorKind::PermissionDenied, error.kind());
+        assert_eq!(0x200, chip.program_counter);
     }
 
     #[test]
@@ -567,29 +616,59 @@ mod tests {
     }
 
     #[test]
-    fn add_to_address_no_overflow() {
+    fn get_font_sprite_ignores_high_nibble() {
+        let (mut chip, opcode) = chip_opcode();
+        chip.registers[0] = 0xFA;
+        opcode.get_font_sprite(&mut chip, 0);
+        assert_eq!(0xA * 5, chip.address);
+    }
+
+    #[test]
+    fn skip_on_keypress_ignores_high_nibble() {
+        let (mut chip, opcode) = chip_opcode();
+        chip.program_counter = 0x200;
+        chip.registers[0] = 0x13;
+        chip.keys[3] = Key {
+            state: KeyState::Pressed,
+        };
+        opcode.skip_on_keypress(&mut chip, 0);
+        assert_eq!(0x204, chip.program_counter);
+        opcode.skip_not_keypress(&mut chip, 0);
+        assert_eq!(0x206, chip.program_counter);
+    }
+
+    #[test]
+    fn add_to_address() {
         let (mut chip, opcode) = chip_opcode();
         chip.program_counter = 0x200;
         chip.registers[0] = 0x55;
-        chip.registers[0xF] = 0x1;
+        chip.registers[0xF] = 0x7;
         chip.address = 0x100;
         opcode.add_vx_to_address_register(&mut chip, 0);
         assert_eq!(0x155, chip.address);
-        assert_eq!(0x0, chip.registers[0xF]);
+        assert_eq!(0x7, chip.registers[0xF]);
         assert_eq!(0x202, chip.program_counter);
     }
 
     #[test]
-    fn add_to_address_overflow() {
+    fn add_to_address_leaves_vf_alone() {
         let (mut chip, opcode) = chip_opcode();
         chip.program_counter = 0x200;
         chip.registers[0] = 0x1;
         chip.registers[0xF] = 0x0;
+
+        // Past the 4 KiB address space, where some interpreters set vf
+        chip.address = 0xFFF;
+        opcode.add_vx_to_address_register(&mut chip, 0);
+        assert_eq!(0x1000, chip.address);
+        assert_eq!(0x0, chip.registers[0xF]);
+
+        // Past 16 bits I wraps
         chip.address = 0xFFFF;
         opcode.add_vx_to_address_register(&mut chip, 0);
         assert_eq!(0x0, chip.address);
-        assert_eq!(0x1, chip.registers[0xF]);
-        assert_eq!(0x202, chip.program_counter);
+        assert_eq!(0x0, chip.registers[0xF]);
+        assert_eq!(0x204, chip.program_counter);
     }
 
     #[test]
@@ -699,13 +778,12 @@ mod tests {
         chip.registers[1] = 0;
 
         chip.address = 0;
-        chip.memory[usize::from(chip.address)] = 0xFF;
-        chip.screen_buffer[usize::from(chip.address)] = 0;
+        chip.memory.write(usize::from(chip.address), 0xFF).unwrap();
 
-        opcode.draw_sprite(&mut chip, 0, 1, 1);
+        opcode.draw_sprite(&mut chip, 0, 1, 1).unwrap();
         assert_eq!(0, chip.registers[0xF]);
         assert_eq!(0x202, chip.program_counter);
-        assert_eq!(0xFF, chip.screen_buffer[usize::from(chip.address)]);
+        assert_eq!(0xFF, chip.screen_buffer.row(0)[0]);
     }
 
     #[test]
@@ -716,13 +794,13 @@ mod tests {
         chip.registers[1] = 0;
 
         chip.address = 0;
-        chip.memory[usize::from(chip.address)] = 0xA5;
-        chip.screen_buffer[usize::from(chip.address)] = 0xA5;
+        chip.memory.write(usize::from(chip.address), 0xA5).unwrap();
+        chip.screen_buffer.as_bytes_mut()[0] = 0xA5;
 
-        opcode.draw_sprite(&mut chip, 0, 1, 1);
+        opcode.draw_sprite(&mut chip, 0, 1, 1).unwrap();
         assert_eq!(1, chip.registers[0xF]);
         assert_eq!(0x202, chip.program_counter);
-        assert_eq!(0, chip.screen_buffer[usize::from(chip.address)]);
+        assert_eq!(0, chip.screen_buffer.row(0)[0]);
     }
 
     #[test]
@@ -733,35 +811,62 @@ mod tests {
         chip.registers[1] = 0;
 
         chip.address = 0;
-        chip.memory[usize::from(chip.address)] = 0x00;
-        chip.memory[usize::from(chip.address) + 1] = 0x01;
-        chip.screen_buffer[usize::from(chip.address)] = 0xA5;
-        chip.screen_buffer[usize::from(chip.address) + chip.screen_width] = 0x01;
-
-        opcode.draw_sprite(&mut chip, 0, 1, 2);
+        chip.memory.write(usize::from(chip.address), 0x00).unwrap();
+        chip.memory
+            .write(usize::from(chip.address) + 1, 0x01)
+            .unwrap();
+        chip.screen_buffer.as_bytes_mut()[0] = 0xA5;
+        chip.screen_buffer.set_pixel(7, 1, true);
+
+        opcode.draw_sprite(&mut chip, 0, 1, 2).unwrap();
         assert_eq!(1, chip.registers[0xF]);
         assert_eq!(0x202, chip.program_counter);
-        assert_eq!(0xA5, chip.screen_buffer[usize::from(chip.address)]);
-        assert_eq!(0x00, chip.screen_buffer[usize::from(chip.address) + 1]);
+        assert_eq!(0xA5, chip.screen_buffer.row(0)[0]);
+        assert_eq!(0x00, chip.screen_buffer.row(1)[0]);
     }
 
     #[test]
-    fn pixel_collision() {
-        let mut b1 = 0;
-        let mut b2 = 0;
-        assert!(!Opcode::pixel_collision(b1, b2));
+    fn draw_sprite_unaligned() {
+        let (mut chip, _) = chip_opcode();
+        chip.registers[2] = 12;
+        chip.registers[3] = 5;
+        chip.address = 0x300;
+        chip.memory.write(0x300, 0xF0).unwrap();
 
-        b1 = 1;
-        b2 = 1;
-        assert!(Opcode::pixel_collision(b1, b2));
+        Opcode::new(0xD231).decode_execute(&mut chip).unwrap();
+        assert_eq!(0, chip.registers[0xF]);
+        assert_eq!(&[0x0F, 0x00], &chip.screen_buffer.row(5)[1..3]);
+        assert!(chip.screen_buffer.get_pixel(15, 5));
 
-        b1 = 0x10;
-        b2 = 0x1F;
-        assert!(Opcode::pixel_collision(b1, b2));
+        Opcode::new(0xD231).decode_execute(&mut chip).unwrap();
+        assert_eq!(1, chip.registers[0xF]);
+        assert!(chip.screen_buffer.as_bytes().iter().all(|byte| *byte == 0));
+    }
 
-        b1 = 0x80;
-        b2 = 0x80;
-        assert!(Opcode::pixel_collision(b1, b2));
+    #[test]
+    fn draw_sprite_edges() {
+        let (mut chip, opcode) = chip_opcode();
+        chip.registers[0] = 60;
+        chip.registers[1] = 31;
+        chip.address = 0x300;
+        chip.memory.load(0x300, &[0xFF, 0xFF]).unwrap();
+
+        opcode.draw_sprite(&mut chip, 0, 1, 2).unwrap();
+        assert!(chip.screen_buffer.get_pixel(63, 31));
+        assert!(chip.screen_buffer.get_pixel(0, 31));
+        assert!(chip.screen_buffer.get_pixel(0, 0));
+
+        let (mut chip, opcode) = chip_opcode();
+        chip.config.quirks.clip_sprites = true;
+        chip.registers[0] = 60;
+        chip.registers[1] = 31;
+        chip.address = 0x300;
+        chip.memory.load(0x300, &[0xFF, 0xFF]).unwrap();
+
+        opcode.draw_sprite(&mut chip, 0, 1, 2).unwrap();
+        assert!(chip.screen_buffer.get_pixel(63, 31));
+        assert!(!chip.screen_buffer.get_pixel(0, 31));
+        assert!(!chip.screen_buffer.get_pixel(0, 0));
     }
 
     #[test]
@@ -770,7 +875,18 @@ mod tests {
         chip.program_counter = 0x200;
         chip.registers[0] = 0x34;
         opcode.jump_addr_v0(&mut chip, 0x100);
-        assert_eq!(0x334, chip.program_counter);
+        assert_eq!(0x134, chip.program_counter);
+    }
+
+    #[test]
+    fn jump_uses_vx_quirk() {
+        let (mut chip, opcode) = chip_opcode();
+        chip.config.quirks.jump_uses_vx = true;
+        chip.program_counter = 0;
+        chip.registers[0] = 0x34;
+        chip.registers[3] = 0x10;
+        opcode.jump_addr_v0(&mut chip, 0x300);
+        assert_eq!(0x310, chip.program_counter);
     }
 
     #[test]
@@ -818,9 +934,10 @@ mod tests {
         let (mut chip, opcode) = chip_opcode();
         chip.program_counter = 0x200;
         chip.registers[0] = 0x7F;
-        opcode.shift_left_vx(&mut chip, 0);
+        opcode.shift_left_vx(&mut chip, 0, 0);
         assert_eq!(0xFE, chip.registers[0]);
         assert_eq!(0, chip.registers[0xF]);
+        assert_eq!(0x202, chip.program_counter);
     }
 
     #[test]
@@ -828,7 +945,7 @@ mod tests {
         let (mut chip, opcode) = chip_opcode();
         chip.program_counter = 0x200;
         chip.registers[0] = 0xFF;
-        opcode.shift_left_vx(&mut chip, 0);
+        opcode.shift_left_vx(&mut chip, 0, 0);
         assert_eq!(0xFE, chip.registers[0]);
         assert_eq!(1, chip.registers[0xF]);
     }
@@ -837,7 +954,7 @@ mod tests {
     #[should_panic]
     fn shift_left_vx_invalid() {
         let (mut chip, opcode) = chip_opcode();
-        opcode.shift_left_vx(&mut chip, 0x10);
+        opcode.shift_left_vx(&mut chip, 0x10, 0x10);
     }
 
     #[test]
@@ -845,9 +962,10 @@ mod tests {
         let (mut chip, opcode) = chip_opcode();
         chip.program_counter = 0x200;
         chip.registers[0] = 0x2;
-        opcode.shift_right_vx(&mut chip, 0);
+        opcode.shift_right_vx(&mut chip, 0, 0);
         assert_eq!(1, chip.registers[0]);
         assert_eq!(0, chip.registers[0xF]);
+        assert_eq!(0x202, chip.program_counter);
     }
 
     #[test]
@@ -855,7 +973,7 @@ mod tests {
         let (mut chip, opcode) = chip_opcode();
         chip.program_counter = 0x200;
         chip.registers[0] = 0x3;
-        opcode.shift_right_vx(&mut chip, 0);
+        opcode.shift_right_vx(&mut chip, 0, 0);
         assert_eq!(1, chip.registers[0]);
         assert_eq!(1, chip.registers[0xF]);
     }
@@ -864,7 +982,40 @@ mod tests {
     #[should_panic]
     fn shift_right_vx_invalid() {
         let (mut chip, opcode) = chip_opcode();
-        opcode.shift_right_vx(&mut chip, 0x10);
+        opcode.shift_right_vx(&mut chip, 0x10, 0x10);
+    }
+
+    #[test]
+    fn shift_uses_vy_quirk() {
+        let (mut chip, opcode) = chip_opcode();
+        chip.config.quirks.shift_uses_vy = true;
+        chip.registers[0] = 0x0;
+        chip.registers[1] = 0x81;
+        opcode.shift_right_vx(&mut chip, 0, 1);
+        assert_eq!(0x40, chip.registers[0]);
+        assert_eq!(1, chip.registers[0xF]);
+        assert_eq!(0x81, chip.registers[1]);
+
+        opcode.shift_left_vx(&mut chip, 0, 1);
+        assert_eq!(0x02, chip.registers[0]);
+        assert_eq!(1, chip.registers[0xF]);
+    }
+
+    #[test]
+    fn vf_reset_quirk() {
+        let (mut chip, opcode) = chip_opcode();
+        chip.config.quirks.vf_reset = true;
+        chip.registers[0xF] = 1;
+        opcode.vx_or_vy(&mut chip, 0, 1);
+        assert_eq!(0, chip.registers[0xF]);
+
+        chip.registers[0xF] = 1;
+        opcode.vx_and_vy(&mut chip, 0, 1);
+        assert_eq!(0, chip.registers[0xF]);
+
+        chip.registers[0xF] = 1;
+        opcode.vx_xor_vy(&mut chip, 0, 1);
+        assert_eq!(0, chip.registers[0xF]);
     }
 
     #[test]
@@ -1070,7 +1221,7 @@ mod tests {
         chip.program_counter = 0x200;
         chip.registers[0] = 1;
         opcode.add_constant(&mut chip, 0, 255);
-        assert_eq!(1, chip.registers[0]);
+        assert_eq!(0, chip.registers[0]);
         assert_eq!(0x202, chip.program_counter);
     }
 
@@ -1185,9 +1336,13 @@ mod tests {
     #[test]
     fn clear_screen_buffer() {
         let (mut chip, opcode) = chip_opcode();
-        chip.screen_buffer = vec![1; chip.screen_buffer.len()];
+        chip.screen_buffer
+            .as_bytes_mut()
+            .iter_mut()
+            .for_each(|byte| *byte = 1);
         opcode.clear_screen(&mut chip);
-        assert_eq!(chip.screen_buffer, vec![0; chip.screen_buffer.len()]);
+        assert_eq!(256, chip.screen_buffer.as_bytes().len());
+        assert!(chip.screen_buffer.as_bytes().iter().all(|byte| *byte == 0));
         assert_eq!(0x202, chip.program_counter);
     }
 
@@ -1195,15 +1350,14 @@ mod tests {
     fn function_return() {
         let (mut chip, opcode) = chip_opcode();
         chip.stack.push(0x123).unwrap();
-        opcode.return_from_subroutine(&mut chip);
+        opcode.return_from_subroutine(&mut chip).unwrap();
         assert_eq!(0x125, chip.program_counter);
     }
 
     #[test]
-    #[should_panic]
     fn return_empty_stack() {
         let (mut chip, opcode) = chip_opcode();
-        opcode.return_from_subroutine(&mut chip);
+        assert!(opcode.return_from_subroutine(&mut chip).is_err());
     }
 
     #[test]
@@ -1223,7 +1377,7 @@ mod tests {
     #[test]
     fn call_subroutine() {
         let (mut chip, opcode) = chip_opcode();
-        opcode.call_subroutine(&mut chip, 0x2);
+        opcode.call_subroutine(&mut chip, 0x2).unwrap();
         assert_eq!(0x2, chip.program_counter);
     }
 
@@ -1231,57 +1385,38 @@ mod tests {
     #[should_panic]
     fn call_invalid() {
         let (mut chip, opcode) = chip_opcode();
-        opcode.call_subroutine(&mut chip, 0xFFFF);
+        opcode.call_subroutine(&mut chip, 0xFFFF).unwrap();
     }
 
     #[test]
-    #[should_panic]
     fn call_full_stack() {
         let (mut chip, opcode) = chip_opcode();
         chip.stack.size = 0;
         chip.stack.head = 0;
-        opcode.call_subroutine(&mut chip, 0xFFF);
+        let program_counter = chip.program_counter;
+        assert!(opcode.call_subroutine(&mut chip, 0xFFF).is_err());
+        assert_eq!(program_counter, chip.program_counter);
     }
 
     #[test]
     fn valid_registers_trivial() {
         let registers = vec![0x0];
         let chip = Chip::default();
-        if let Ok(_) = Opcode::valid_registers(&registers, &chip) {
-            assert!(true);
-        } else {
-            assert!(false);
-        }
+        assert!(Opcode::valid_registers(&registers, &chip).is_ok());
 
         let registers = vec![0xF];
-        if let Ok(_) = Opcode::valid_registers(&registers, &chip) {
-            assert!(true);
-        } else {
-            assert!(false);
-        }
+        assert!(Opcode::valid_registers(&registers, &chip).is_ok());
 
         let mut registers: Vec<usize> = Vec::new();
-        if let Ok(_) = Opcode::valid_registers(&registers, &chip) {
-            assert!(false);
-        } else {
-            assert!(true);
-        }
+        assert!(Opcode::valid_registers(&registers, &chip).is_err());
 
         for item in 0..0xF {
             registers.push(item);
         }
-        if let Ok(_) = Opcode::valid_registers(&registers, &chip) {
-            assert!(true);
-        } else {
-            assert!(false);
-        }
+        assert!(Opcode::valid_registers(&registers, &chip).is_ok());
 
         registers.push(0x10);
-        if let Ok(_) = Opcode::valid_registers(&registers, &chip) {
-            assert!(false);
-        } else {
-            assert!(true);
-        }
+        assert!(Opcode::valid_registers(&registers, &chip).is_err());
     }
 
     #[test]
@@ -1291,21 +1426,22 @@ mod tests {
     }
 
     #[test]
-    fn trivial_split() {
-        let o = Opcode::new(0);
-        assert_eq!(o.n1(), 0);
-        assert_eq!(o.n2(), 0);
-        assert_eq!(o.n3(), 0);
-        assert_eq!(o.n4(), 0);
+    fn decodes_whole_patterns() {
+        let chip = Chip::default();
+        for opcode in [
+            0x0230, 0x00E1, 0x5121, 0x912F, 0x8128, 0xE19F, 0xE1A2, 0xF108, 0xF175,
+        ] {
+            assert!(!Opcode::new(opcode).is_valid(&chip), "{:04X}", opcode);
+        }
+        assert!(Opcode::new(0x00E0).is_valid(&chip));
+        assert!(Opcode::new(0xE19E).is_valid(&chip));
     }
 
     #[test]
-    fn split_opcode() {
-        let o = Opcode::new(0x1234);
-        assert_eq!(o.n1(), 1);
-        assert_eq!(o.n2(), 2);
-        assert_eq!(o.n3(), 3);
-        assert_eq!(o.n4(), 4);
+    fn partial_pattern_is_illegal() {
+        let (mut chip, _) = chip_opcode();
+        let error = Opcode::new(0x0230).decode_execute(&mut chip).unwrap_err();
+        assert_eq!(ErrorKind::InvalidData, error.kind());
     }
 
     fn chip_opcode() -> (Chip, Opcode) {
@@ -0,0 +1,169 @@
+use std::io::{Error, ErrorKind};
+use std::str::FromStr;
+use std::vec::Vec;
+
+/// The names of the built in themes
+pub const THEMES: [&str; 5] = ["classic", "green", "amber", "lcd", "high-contrast"];
+
+/// The colors pixels are drawn with, as 0RGB. Colors are indexed by pixel value: 0 is the
+/// background and 1 is a lit pixel. Multi-plane extensions combine one bit per plane into the
+/// pixel value, so 4 colors cover 2 planes and 16 colors cover 4 planes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Palette {
+    colors: Vec<u32>,
+}
+
+impl Default for Palette {
+    /// Dark gray and light gray
+    fn default() -> Palette {
+        Palette::theme("classic").expect("The classic theme is missing")
+    }
+}
+
+impl Palette {
+    /// Create a palette of 2, 4 or 16 colors
+    pub fn new(colors: Vec<u32>) -> Result<Palette, Error> {
+        match colors.len() {
+            2 | 4 | 16 => Ok(Palette { colors }),
+            len => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("A palette needs 2, 4 or 16 colors, not {}", len),
+            )),
+        }
+    }
+
+    /// Get a built in theme by name
+    pub fn theme(name: &str) -> Option<Palette> {
+        let colors = match name.to_ascii_lowercase().as_str() {
+            "classic" => vec![0x0010_1010, 0x00EE_EEEE, 0x0077_7777, 0x00BB_BBBB],
+            "green" | "phosphor" => vec![0x0000_1100, 0x0033_FF33, 0x0011_6611, 0x0099_FF99],
+            "amber" => vec![0x001A_0F00, 0x00FF_B000, 0x0080_5800, 0x00FF_D680],
+            "lcd" => vec![0x009B_BC0F, 0x000F_380F, 0x008B_AC0F, 0x0030_6230],
+            "high-contrast" => vec![0x0000_0000, 0x00FF_FFFF, 0x00FF_FF00, 0x0000_FFFF],
+            _ => return None,
+        };
+        Some(Palette { colors })
+    }
+
+    /// Create a palette from hex colors, e.g. ["#101010", "EEEEEE"]
+    pub fn from_hex<S: AsRef<str>>(colors: &[S]) -> Result<Palette, Error> {
+        let colors = colors
+            .iter()
+            .map(|color| parse_color(color.as_ref()))
+            .collect::<Result<Vec<u32>, Error>>()?;
+        Palette::new(colors)
+    }
+
+    pub fn colors(&self) -> &[u32] {
+        &self.colors
+    }
+
+    /// Get the color of a pixel value. Values past the end of the palette use the last color, so
+    /// a 2 color palette draws any lit pixel in its foreground color.
+    pub fn color(&self, value: usize) -> u32 {
+        match self.colors.get(value) {
+            Some(color) => *color,
+            None => self.colors[self.colors.len() - 1],
+        }
+    }
+}
+
+impl FromStr for Palette {
+    type Err = Error;
+
+    /// Parse a theme name, or 2, 4 or 16 colors separated by ':' in pixel value order, background
+    /// first, e.g. "101010:EEEEEE"
+    fn from_str(text: &str) -> Result<Palette, Error> {
+        if let Some(palette) = Palette::theme(text) {
+            return Ok(palette);
+        }
+        if !text.contains(':') {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Unknown theme '{}', expected one of {} or colors such as 101010:EEEEEE",
+                    text,
+                    THEMES.join(", ")
+                ),
+            ));
+        }
+        let colors: Vec<&str> = text.split(':').collect();
+        Palette::from_hex(&colors)
+    }
+}
+
+/// Parse a single RRGGBB color, optionally starting with '#'
+fn parse_color(text: &str) -> Result<u32, Error> {
+    let hex = text.trim().trim_start_matches('#');
+    match u32::from_str_radix(hex, 16) {
+        Ok(color) if hex.len() == 6 => Ok(color),
+        _ => Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("'{}' is not a RRGGBB color", text),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn themes() {
+        for name in THEMES.iter() {
+            let palette: Palette = name.parse().unwrap();
+            assert_eq!(4, palette.colors().len(), "{}", name);
+        }
+        assert_eq!(0x0010_1010, Palette::default().color(0));
+        assert_eq!(0x00EE_EEEE, Palette::default().color(1));
+        assert!(Palette::theme("sepia").is_none());
+    }
+
+    #[test]
+    fn parse_colors() {
+        // Every length is in pixel value order, background first
+        let palette: Palette = "000000:#FFB000".parse().unwrap();
+        assert_eq!(&[0x0000_0000, 0x00FF_B000], palette.colors());
+
+        let palette: Palette = "000000:FF0000:00FF00:FFFFFF".parse().unwrap();
+        assert_eq!(
+            &[0x0000_0000, 0x00FF_0000, 0x0000_FF00, 0x00FF_FFFF],
+            palette.colors()
+        );
+
+        let palette: Palette = (0..16)
+            .map(|i| format!("{:06X}", i))
+            .collect::<Vec<String>>()
+            .join(":")
+            .parse()
+            .unwrap();
+        assert_eq!(16, palette.colors().len());
+        assert_eq!(15, palette.color(15));
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert!("sepia".parse::<Palette>().is_err());
+        assert!("FFFFFF:000000:FF0000".parse::<Palette>().is_err());
+        assert!("FFFFFF:red".parse::<Palette>().is_err());
+        assert!("FFFFFFF:000000".parse::<Palette>().is_err());
+        assert!(Palette::new(Vec::new()).is_err());
+    }
+
+    #[test]
+    fn from_hex() {
+        let palette = Palette::from_hex(&["#101010", "#EEEEEE"]).unwrap();
+        assert_eq!(
+            Palette::new(vec![0x0010_1010, 0x00EE_EEEE]).unwrap(),
+            palette
+        );
+    }
+
+    #[test]
+    fn color_past_the_end() {
+        let palette = Palette::new(vec![1, 2]).unwrap();
+        assert_eq!(1, palette.color(0));
+        assert_eq!(2, palette.color(1));
+        assert_eq!(2, palette.color(3));
+    }
+}
@@ -0,0 +1,403 @@
+//! A reference model of the chip-8 instructions, written straight from the instruction set
+//! rather than from opcode.rs, and property tests which run random instructions on random chip
+//! states through both and compare the results.
+
+use crate::config::Quirks;
+use crate::opcode::Opcode;
+use crate::Chip;
+use proptest::array::uniform16;
+use proptest::bool::weighted;
+use proptest::collection::vec;
+use proptest::prelude::*;
+use proptest::sample::select;
+
+const WIDTH: usize = 64;
+const HEIGHT: usize = 32;
+
+/// The observable state of a chip
+#[derive(Clone, Debug, PartialEq)]
+struct Model {
+    v: [u8; 16],
+    i: u16,
+    pc: u16,
+
+    // Return addresses, i.e. the address after each call
+    stack: Vec<u16>,
+    memory: Vec<u8>,
+    delay_timer: u8,
+    sound_timer: u8,
+
+    // One bit per pixel, eight pixels to a byte
+    screen: Vec<u8>,
+    keys: [bool; 16],
+    vblank: bool,
+}
+
+impl Model {
+    fn from_chip(chip: &Chip) -> Model {
+        let mut v = [0; 16];
+        v.copy_from_slice(&chip.registers);
+        let mut keys = [false; 16];
+        for (key, state) in keys.iter_mut().zip(chip.keys.iter()) {
+            *key = state.is_pressed();
+        }
+        Model {
+            v,
+            i: chip.address,
+            pc: chip.program_counter,
+            // The chip keeps the address of the call itself and skips it on return
+            stack: chip.stack.data.iter().map(|address| address + 2).collect(),
+            memory: chip.memory.as_slice().to_vec(),
+            delay_timer: chip.delay_timer,
+            sound_timer: chip.sound_timer,
+            screen: chip.screen_buffer.as_bytes().to_vec(),
+            keys,
+            vblank: chip.vblank,
+        }
+    }
+
+    /// Run one instruction
+    fn execute(&mut self, opcode: u16, quirks: &Quirks) {
+        let x = usize::from((opcode >> 8) & 0xF);
+        let y = usize::from((opcode >> 4) & 0xF);
+        let n = (opcode & 0xF) as u8;
+        let nn = (opcode & 0xFF) as u8;
+        let nnn = opcode & 0xFFF;
+        let mut next = self.pc + 2;
+
+        match opcode >> 12 {
+            0x0 if opcode == 0x00E0 => self.screen.iter_mut().for_each(|byte| *byte = 0),
+            0x0 if opcode == 0x00EE => next = self.stack.pop().unwrap(),
+            0x1 => next = nnn,
+            0x2 => {
+                self.stack.push(next);
+                next = nnn;
+            }
+            0x3 if self.v[x] == nn => next += 2,
+            0x4 if self.v[x] != nn => next += 2,
+            0x5 if self.v[x] == self.v[y] => next += 2,
+            0x3..=0x5 => {}
+            0x6 => self.v[x] = nn,
+            0x7 => self.v[x] = self.v[x].wrapping_add(nn),
+            0x8 => {
+                let (vx, vy) = (self.v[x], self.v[y]);
+                let shifted = if quirks.shift_uses_vy { vy } else { vx };
+                let (result, flag) = match n {
+                    0x0 => (vy, None),
+                    0x1 => (vx | vy, quirks.vf_reset.then_some(0)),
+                    0x2 => (vx & vy, quirks.vf_reset.then_some(0)),
+                    0x3 => (vx ^ vy, quirks.vf_reset.then_some(0)),
+                    0x4 => (
+                        vx.wrapping_add(vy),
+                        Some(u8::from(u16::from(vx) + u16::from(vy) > 0xFF)),
+                    ),
+                    0x5 => (vx.wrapping_sub(vy), Some(u8::from(vx >= vy))),
+                    0x6 => (shifted >> 1, Some(shifted & 1)),
+                    0x7 => (vy.wrapping_sub(vx), Some(u8::from(vy >= vx))),
+                    0xE => (shifted << 1, Some(shifted >> 7)),
+                    _ => panic!("Invalid opcode {:04X}", opcode),
+                };
+                // The flag is written last, so it wins when VF is the destination
+                self.v[x] = result;
+                if let Some(flag) = flag {
+                    self.v[0xF] = flag;
+                }
+            }
+            0x9 if self.v[x] != self.v[y] => next += 2,
+            0x9 => {}
+            0xA => self.i = nnn,
+            0xB => {
+                let register = if quirks.jump_uses_vx { x } else { 0 };
+                next = nnn + u16::from(self.v[register]);
+            }
+            0xD => {
+                // With display_wait, each sprite waits for and uses up a vertical blank
+                if quirks.display_wait {
+                    if !self.vblank {
+                        return;
+                    }
+                    self.vblank = false;
+                }
+                self.draw(self.v[x], self.v[y], n, quirks.clip_sprites);
+            }
+            0xE if nn == 0x9E && self.keys[usize::from(self.v[x] & 0xF)] => next += 2,
+            0xE if nn == 0xA1 && !self.keys[usize::from(self.v[x] & 0xF)] => next += 2,
+            0xE => {}
+            0xF => match nn {
+                0x07 => self.v[x] = self.delay_timer,
+                0x0A => match self.keys.iter().position(|pressed| *pressed) {
+                    Some(key) => self.v[x] = key as u8,
+                    None => next = self.pc,
+                },
+                0x15 => self.delay_timer = self.v[x],
+                0x18 => self.sound_timer = self.v[x],
+                // The COSMAC VIP interpreter adds to I without touching VF
+                0x1E => self.i = self.i.wrapping_add(u16::from(self.v[x])),
+                0x29 => self.i = u16::from(self.v[x] & 0xF) * 5,
+                0x33 => {
+                    let i = usize::from(self.i);
+                    self.memory[i] = self.v[x] / 100;
+                    self.memory[i + 1] = self.v[x] / 10 % 10;
+                    self.memory[i + 2] = self.v[x] % 10;
+                }
+                0x55 | 0x65 => {
+                    for register in 0..=x {
+                        let address = usize::from(self.i) + register;
+                        if nn == 0x55 {
+                            self.memory[address] = self.v[register];
+                        } else {
+                            self.v[register] = self.memory[address];
+                        }
+                    }
+                    if quirks.memory_increments_i {
+                        self.i += x as u16 + 1;
+                    }
+                }
+                _ => panic!("Invalid opcode {:04X}", opcode),
+            },
+            _ => panic!("Invalid opcode {:04X}", opcode),
+        }
+        self.pc = next;
+    }
+
+    /// XOR a sprite from I onto the screen, setting VF if any pixel is erased
+    fn draw(&mut self, x: u8, y: u8, height: u8, clip: bool) {
+        let (x, y) = (usize::from(x) % WIDTH, usize::from(y) % HEIGHT);
+        let mut erased = false;
+        for row in 0..usize::from(height) {
+            let byte = self.memory[usize::from(self.i) + row];
+            for column in 0..8 {
+                if byte & (0x80 >> column) == 0 {
+                    continue;
+                }
+                let (px, py) = (x + column, y + row);
+                if clip && (px >= WIDTH || py >= HEIGHT) {
+                    continue;
+                }
+                let pixel = (py % HEIGHT) * WIDTH + px % WIDTH;
+                let mask = 0x80 >> (pixel % 8);
+                erased |= self.screen[pixel / 8] & mask != 0;
+                self.screen[pixel / 8] ^= mask;
+            }
+        }
+        self.v[0xF] = u8::from(erased);
+    }
+}
+
+/// Whether an opcode is an instruction which can run with the given stack, straight from the
+/// instruction set
+fn is_valid(opcode: u16, stack_depth: usize, stack_size: usize) -> bool {
+    let n = opcode & 0xF;
+    let nn = opcode & 0xFF;
+    match opcode >> 12 {
+        0x0 => opcode == 0x00E0 || (opcode == 0x00EE && stack_depth > 0),
+        0x2 => stack_depth < stack_size,
+        0x5 | 0x9 => n == 0,
+        0x8 => matches!(n, 0x0..=0x7 | 0xE),
+        0xE => nn == 0x9E || nn == 0xA1,
+        0xF => matches!(
+            nn,
+            0x07 | 0x0A | 0x15 | 0x18 | 0x1E | 0x29 | 0x33 | 0x55 | 0x65
+        ),
+        _ => true,
+    }
+}
+
+/// A chip state for the properties to start from
+#[derive(Debug)]
+struct State {
+    quirks: Quirks,
+    registers: [u8; 16],
+    address: u16,
+    program_counter: u16,
+
+    // Addresses of the calls on the stack
+    stack: Vec<u16>,
+
+    // The memory from I on, the only memory instructions read
+    sprite: Vec<u8>,
+    screen: Vec<u8>,
+    keys: [bool; 16],
+    delay_timer: u8,
+    sound_timer: u8,
+    vblank: bool,
+}
+
+impl State {
+    fn chip(&self) -> Chip {
+        let mut chip = Chip::default();
+        chip.config.quirks = self.quirks;
+        chip.registers.copy_from_slice(&self.registers);
+        chip.address = self.address;
+        chip.program_counter = self.program_counter;
+        for address in &self.stack {
+            chip.stack.push(*address).unwrap();
+        }
+        chip.memory
+            .load(usize::from(self.address), &self.sprite)
+            .unwrap();
+        chip.screen_buffer
+            .as_bytes_mut()
+            .copy_from_slice(&self.screen);
+        for (key, pressed) in chip.keys.iter_mut().zip(self.keys.iter()) {
+            key.state = if *pressed {
+                crate::KeyState::Pressed
+            } else {
+                crate::KeyState::NotPressed
+            };
+        }
+        chip.delay_timer = self.delay_timer;
+        chip.sound_timer = self.sound_timer;
+        chip.vblank = self.vblank;
+        chip
+    }
+}
+
+fn quirks() -> impl Strategy<Value = Quirks> {
+    any::<[bool; 6]>().prop_map(|bits| Quirks {
+        vf_reset: bits[0],
+        memory_increments_i: bits[1],
+        shift_uses_vy: bits[2],
+        jump_uses_vx: bits[3],
+        clip_sprites: bits[4],
+        display_wait: bits[5],
+    })
+}
+
+/// Chip states with random registers, memory, screen, keys and quirks. I and the program
+/// counter stay far enough from the end of memory for any instruction to be valid.
+fn state() -> impl Strategy<Value = State> {
+    let screen_size = Chip::default().screen_buffer.as_bytes().len();
+    let registers = (
+        quirks(),
+        uniform16(any::<u8>()),
+        0x200u16..0xFE0,
+        (0x100u16..0x780).prop_map(|word| word * 2),
+        vec((0x100u16..0x7FF).prop_map(|word| word * 2), 0..=16),
+    );
+    let peripherals = (
+        vec(any::<u8>(), 0x20),
+        vec(any::<u8>(), screen_size),
+        uniform16(weighted(0.1)),
+        any::<u8>(),
+        any::<u8>(),
+        any::<bool>(),
+    );
+    (registers, peripherals).prop_map(
+        |(
+            (quirks, registers, address, program_counter, stack),
+            (sprite, screen, keys, delay_timer, sound_timer, vblank),
+        )| State {
+            quirks,
+            registers,
+            address,
+            program_counter,
+            stack,
+            sprite,
+            screen,
+            keys,
+            delay_timer,
+            sound_timer,
+            vblank,
+        },
+    )
+}
+
+/// Opcodes from the given families, i.e. with the given top nibbles
+fn family(families: &'static [u16]) -> impl Strategy<Value = u16> {
+    (select(families), 0u16..0x1000).prop_map(|(family, rest)| family << 12 | rest)
+}
+
+/// The chip must agree with the model on whether the opcode is valid from the state, and a
+/// valid instruction must change the state the same way
+fn check(state: &State, opcode: u16) -> Result<(), TestCaseError> {
+    let mut chip = state.chip();
+    let valid = is_valid(opcode, chip.stack.data.len(), chip.stack.size);
+    prop_assert_eq!(
+        valid,
+        Opcode::new(opcode).is_valid(&chip),
+        "{:04X} validity differs from the model with {} calls on the stack",
+        opcode,
+        chip.stack.data.len()
+    );
+
+    // CXNN is random, so it's checked by random_is_masked instead
+    if !valid || opcode >> 12 == 0xC {
+        return Ok(());
+    }
+    let mut model = Model::from_chip(&chip);
+    model.execute(opcode, &chip.config.quirks);
+    Opcode::new(opcode).decode_execute(&mut chip).unwrap();
+    let actual = Model::from_chip(&chip);
+    prop_assert_eq!(
+        (&model.v, model.i, model.pc, &model.stack),
+        (&actual.v, actual.i, actual.pc, &actual.stack),
+        "{:04X} registers, I, pc or stack differ from the model",
+        opcode
+    );
+    prop_assert_eq!(model, actual, "{:04X} differs from the model", opcode);
+    Ok(())
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(2_000))]
+
+    #[test]
+    fn any_instruction(state in state(), opcode in any::<u16>()) {
+        check(&state, opcode)?;
+    }
+
+    #[test]
+    fn arithmetic(state in state(), opcode in family(&[0x8])) {
+        check(&state, opcode)?;
+    }
+
+    #[test]
+    fn add_constant(state in state(), opcode in family(&[0x7])) {
+        check(&state, opcode)?;
+    }
+
+    #[test]
+    fn jumps_and_skips(
+        state in state(),
+        opcode in family(&[0x0, 0x1, 0x2, 0x3, 0x4, 0x5, 0x9, 0xB]),
+    ) {
+        check(&state, opcode)?;
+    }
+
+    #[test]
+    fn sprites(state in state(), opcode in family(&[0xD])) {
+        check(&state, opcode)?;
+    }
+
+    #[test]
+    fn keys_timers_and_memory(state in state(), opcode in family(&[0xE, 0xF])) {
+        check(&state, opcode)?;
+    }
+
+    #[test]
+    fn random_is_masked(state in state(), x in 0u16..16, mask in any::<u8>()) {
+        let mut chip = state.chip();
+        let pc = chip.program_counter;
+        Opcode::new(0xC000 | (x << 8) | u16::from(mask)).decode_execute(&mut chip).unwrap();
+        prop_assert_eq!(0, chip.registers[usize::from(x)] & !mask);
+        prop_assert_eq!(pc + 2, chip.program_counter);
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(16))]
+
+    #[test]
+    fn every_opcode_validity(state in state()) {
+        let chip = state.chip();
+        for opcode in 0..=0xFFFF {
+            prop_assert_eq!(
+                is_valid(opcode, chip.stack.data.len(), chip.stack.size),
+                Opcode::new(opcode).is_valid(&chip),
+                "{:04X}",
+                opcode
+            );
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use crate::palette::Palette;
+use std::vec::Vec;
+
+/// Expands byte packed bit planes into a scaled buffer of 0RGB pixels, ready to hand to the
+/// window. The buffer is allocated once and reused for every frame.
+#[derive(Clone, Debug)]
+pub struct Renderer {
+    screen_width: usize,
+    screen_height: usize,
+    scale_factor: usize,
+    pixels: Vec<u32>,
+}
+
+impl Renderer {
+    pub fn new(screen_width: usize, screen_height: usize, scale_factor: u8) -> Renderer {
+        let scale_factor = usize::from(scale_factor);
+        Renderer {
+            screen_width,
+            screen_height,
+            scale_factor,
+            pixels: vec![0; screen_width * screen_height * scale_factor * scale_factor],
+        }
+    }
+
+    /// The width of the rendered buffer in window pixels
+    pub fn width(&self) -> usize {
+        self.screen_width * self.scale_factor
+    }
+
+    /// The height of the rendered buffer in window pixels
+    pub fn height(&self) -> usize {
+        self.screen_height * self.scale_factor
+    }
+
+    /// The most recently rendered frame
+    pub fn pixels(&self) -> &[u32] {
+        &self.pixels
+    }
+
+    /// Render a frame made of one or more bit planes. Each plane contributes one bit to the
+    /// pixel value, the first plane being the least significant.
+    pub fn render(&mut self, planes: &[&[u8]], palette: &Palette) -> &[u32] {
+        let bytes_per_row = self.screen_width / 8;
+        self.fill(|x, y| {
+            let index = x / 8 + y * bytes_per_row;
+            let bit = 7 - (x % 8);
+            let value = planes.iter().enumerate().fold(0, |value, (plane, bytes)| {
+                value | usize::from((bytes[index] >> bit) & 0x1) << plane
+            });
+            bit_to_u32(value, palette)
+        })
+    }
+
+    /// Render a frame of pixel brightnesses, one byte per pixel. Each pixel is mixed from the
+    /// background color at 0 to the foreground color at max.
+    pub fn render_levels(&mut self, levels: &[u8], max: u8, palette: &Palette) -> &[u32] {
+        let (background, foreground) = (palette.color(0), palette.color(1));
+        let screen_width = self.screen_width;
+        self.fill(|x, y| mix(background, foreground, levels[x + y * screen_width], max))
+    }
+
+    /// Fill the buffer with the color of each chip-8 pixel, scaled up
+    fn fill<F: Fn(usize, usize) -> u32>(&mut self, color: F) -> &[u32] {
+        let width = self.width();
+        for y in 0..self.screen_height {
+            let start = y * self.scale_factor * width;
+
+            // Draw the first line of the row, then copy it for the rest of the scale factor
+            let line = &mut self.pixels[start..start + width];
+            for x in 0..self.screen_width {
+                let pixel = x * self.scale_factor;
+                line[pixel..pixel + self.scale_factor].fill(color(x, y));
+            }
+            for copy in 1..self.scale_factor {
+                self.pixels
+                    .copy_within(start..start + width, start + copy * width);
+            }
+        }
+        &self.pixels
+    }
+}
+
+/// Convert a pixel value to a 32 bit integer
+fn bit_to_u32(bit: usize, palette: &Palette) -> u32 {
+    palette.color(bit)
+}
+
+/// Mix two 0RGB colors, level / max of the way from the background to the foreground
+fn mix(background: u32, foreground: u32, level: u8, max: u8) -> u32 {
+    let (level, max) = (u32::from(level.min(max)), u32::from(max.max(1)));
+    [16, 8, 0].iter().fold(0, |color, shift| {
+        let background = (background >> shift) & 0xFF;
+        let foreground = (foreground >> shift) & 0xFF;
+        color | ((background * (max - level) + foreground * level) / max) << shift
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OFF: u32 = 0x00101010;
+    const ON: u32 = 0x00EEEEEE;
+
+    #[test]
+    fn bit_to_u32() {
+        let classic = Palette::default();
+        assert_eq!(OFF, super::bit_to_u32(0, &classic));
+        assert_eq!(ON, super::bit_to_u32(1, &classic));
+
+        // A two color palette draws every lit pixel in the foreground color
+        let monochrome = Palette::new(vec![OFF, ON]).unwrap();
+        for i in 1..=255 {
+            assert_eq!(ON, super::bit_to_u32(i, &monochrome));
+        }
+    }
+
+    #[test]
+    fn render_byte() {
+        let palette = Palette::default();
+        let mut renderer = Renderer::new(8, 1, 1);
+        assert_eq!(&[OFF; 8], renderer.render(&[&[0]], &palette));
+
+        let mut expected = [OFF; 8];
+        expected[0] = ON;
+        assert_eq!(&expected, renderer.render(&[&[0b10000000]], &palette));
+
+        let mut expected = [OFF; 8];
+        expected[7] = ON;
+        assert_eq!(&expected, renderer.render(&[&[0b00000001]], &palette));
+    }
+
+    #[test]
+    fn render_screen_buffer() {
+        let mut expected = vec![OFF; 24];
+        expected[8] = ON;
+        expected[23] = ON;
+
+        let mut renderer = Renderer::new(24, 1, 1);
+        assert_eq!(
+            expected,
+            renderer.render(&[&[0, 0b10000000, 0b00000001]], &Palette::default())
+        );
+    }
+
+    #[test]
+    fn render_scaled() {
+        let mut renderer = Renderer::new(8, 2, 2);
+        assert_eq!(16, renderer.width());
+        assert_eq!(4, renderer.height());
+
+        let pixels = renderer.render(&[&[0b01000000, 0b00000000]], &Palette::default());
+        for line in 0..2 {
+            assert_eq!(
+                &[OFF, OFF, ON, ON, OFF, OFF],
+                &pixels[line * 16..line * 16 + 6]
+            );
+        }
+        assert!(pixels[32..].iter().all(|pixel| *pixel == OFF));
+    }
+
+    #[test]
+    fn mix() {
+        assert_eq!(0x0000_0000, super::mix(0, 0x00FF_FFFF, 0, 3));
+        assert_eq!(0x0055_5555, super::mix(0, 0x00FF_FFFF, 1, 3));
+        assert_eq!(0x00FF_FFFF, super::mix(0, 0x00FF_FFFF, 3, 3));
+        assert_eq!(0x007F_4000, super::mix(0x0000_8000, 0x00FF_0000, 1, 2));
+    }
+
+    #[test]
+    fn render_levels() {
+        let palette = Palette::new(vec![0, 0x00FF_FFFF]).unwrap();
+        let mut renderer = Renderer::new(8, 1, 1);
+        let pixels = renderer.render_levels(&[4, 2, 0, 0, 0, 0, 0, 1], 4, &palette);
+        assert_eq!(0x00FF_FFFF, pixels[0]);
+        assert_eq!(0x007F_7F7F, pixels[1]);
+        assert_eq!(0, pixels[2]);
+        assert_eq!(0x003F_3F3F, pixels[7]);
+    }
+
+    #[test]
+    fn render_planes() {
+        let palette = Palette::theme("classic").unwrap();
+        let mut renderer = Renderer::new(8, 1, 1);
+        let pixels = renderer.render(&[&[0b11000000], &[0b10100000]], &palette);
+        assert_eq!(palette.color(3), pixels[0]);
+        assert_eq!(palette.color(1), pixels[1]);
+        assert_eq!(palette.color(2), pixels[2]);
+        assert_eq!(palette.color(0), pixels[3]);
+    }
+}
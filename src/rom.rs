@@ -0,0 +1,171 @@
+use flate2::read::GzDecoder;
+use std::fs;
+use std::io::{self, Cursor, Read};
+use std::path::Path;
+use std::vec::Vec;
+
+const GZIP_MAGIC: [u8; 2] = [0x1F, 0x8B];
+const ZIP_MAGIC: [u8; 4] = [b'P', b'K', 0x03, 0x04];
+
+/// The largest memory of any platform. Compressed roms are never decompressed past this, so a
+/// small archive can't expand to fill the host's memory.
+pub const MAX_ROM_SIZE: usize = 0x10000;
+
+/// Read a rom from a file. Gzip compressed roms are decompressed, and zip archives holding a
+/// single file are extracted.
+pub fn read_file<P: AsRef<Path>>(path: P) -> Result<Vec<u8>, io::Error> {
+    read(fs::File::open(path)?)
+}
+
+/// Read a rom from any reader. Gzip compressed roms are decompressed, and zip archives holding a
+/// single file are extracted. A raw rom can start with either magic number, e.g. 1F8B is
+/// JP 0xF8B, so bytes which don't decompress or parse as an archive are the rom itself.
+pub fn read<R: Read>(mut reader: R) -> Result<Vec<u8>, io::Error> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+
+    if bytes.starts_with(&GZIP_MAGIC) {
+        let mut rom = Vec::new();
+        let mut decoder = GzDecoder::new(&bytes[..]).take(MAX_ROM_SIZE as u64 + 1);
+        if decoder.read_to_end(&mut rom).is_ok() {
+            return within_limit(rom);
+        }
+    } else if bytes.starts_with(&ZIP_MAGIC) {
+        if let Ok(entries) = zip_entries(&bytes) {
+            return match entries.as_slice() {
+                [entry] => read_zip_entry(&bytes, entry),
+                _ => Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "Archive holds {} files, choose one of: {}",
+                        entries.len(),
+                        entries.join(", ")
+                    ),
+                )),
+            };
+        }
+    }
+    Ok(bytes)
+}
+
+/// List the files in a zip archive, e.g. a rom pack
+pub fn zip_entries(archive: &[u8]) -> Result<Vec<String>, io::Error> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(archive))?;
+    let mut entries = Vec::new();
+    for i in 0..archive.len() {
+        let file = archive.by_index(i)?;
+        if file.is_file() {
+            entries.push(file.name()?.to_string());
+        }
+    }
+    Ok(entries)
+}
+
+/// Extract a single file from a zip archive
+pub fn read_zip_entry(archive: &[u8], name: &str) -> Result<Vec<u8>, io::Error> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(archive))?;
+    let file = archive.by_name(name)?;
+    read_limited(file)
+}
+
+/// Decompress a rom, failing once it's larger than MAX_ROM_SIZE
+fn read_limited<R: Read>(reader: R) -> Result<Vec<u8>, io::Error> {
+    let mut rom = Vec::new();
+    reader.take(MAX_ROM_SIZE as u64 + 1).read_to_end(&mut rom)?;
+    within_limit(rom)
+}
+
+/// Fail if a decompressed rom, read up to one byte past the limit, is larger than MAX_ROM_SIZE
+fn within_limit(rom: Vec<u8>) -> Result<Vec<u8>, io::Error> {
+    if rom.len() > MAX_ROM_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Decompressed rom is larger than {} bytes", MAX_ROM_SIZE),
+        ));
+    }
+    Ok(rom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    const ROM: [u8; 4] = [0x60, 0x01, 0x12, 0x00];
+
+    fn zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, data) in files {
+            writer
+                .start_file(*name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(data).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn read_plain() {
+        assert_eq!(ROM.to_vec(), read(&ROM[..]).unwrap());
+    }
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn read_gzip() {
+        assert_eq!(ROM.to_vec(), read(&gzip(&ROM)[..]).unwrap());
+    }
+
+    #[test]
+    fn decompressed_size_is_limited() {
+        let largest = vec![0; MAX_ROM_SIZE];
+        assert_eq!(largest, read(&gzip(&largest)[..]).unwrap());
+        assert_eq!(largest, read(&zip(&[("big.ch8", &largest)])[..]).unwrap());
+
+        // A megabyte of zeros compresses to about a kilobyte
+        let bomb = vec![0; 0x100000];
+        let error = read(&gzip(&bomb)[..]).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, error.kind());
+        let error = read(&zip(&[("bomb.ch8", &bomb)])[..]).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, error.kind());
+    }
+
+    #[test]
+    fn raw_rom_with_magic_number() {
+        // JP 0xF8B
+        let rom = [0x1F, 0x8B, 0x60, 0x01];
+        assert_eq!(rom.to_vec(), read(&rom[..]).unwrap());
+
+        let rom = [b'P', b'K', 0x03, 0x04, 0x12, 0x00];
+        assert_eq!(rom.to_vec(), read(&rom[..]).unwrap());
+    }
+
+    #[test]
+    fn read_zip_single_file() {
+        let archive = zip(&[("maze.ch8", &ROM)]);
+        assert_eq!(ROM.to_vec(), read(&archive[..]).unwrap());
+    }
+
+    #[test]
+    fn read_zip_many_files() {
+        let archive = zip(&[("maze.ch8", &ROM), ("zero.ch8", &[0x00, 0xE0])]);
+        let error = read(&archive[..]).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, error.kind());
+
+        assert_eq!(
+            vec!["maze.ch8".to_string(), "zero.ch8".to_string()],
+            zip_entries(&archive).unwrap()
+        );
+        assert_eq!(
+            vec![0x00, 0xE0],
+            read_zip_entry(&archive, "zero.ch8").unwrap()
+        );
+        assert!(read_zip_entry(&archive, "missing.ch8").is_err());
+    }
+}
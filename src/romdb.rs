@@ -0,0 +1,242 @@
+use crate::config::{Platform, Quirks};
+use crate::filter::FilterMode;
+use crate::palette::Palette;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+use std::sync::OnceLock;
+use std::vec::Vec;
+
+/// The database shipped with the emulator
+const BUILTIN_DATABASE: &str = include_str!("romdb.toml");
+
+/// Everything known about a rom, used to configure the emulator before it runs
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RomInfo {
+    pub sha1: String,
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub platform: Option<Platform>,
+    pub quirks: Option<Quirks>,
+    pub instructions_per_frame: Option<u32>,
+    pub keys: Vec<String>,
+    pub palette: Option<Palette>,
+    pub filter: Option<FilterMode>,
+}
+
+/// Quirks are either the name of a preset or individual settings
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum QuirkSetting {
+    Preset(String),
+    Custom(Quirks),
+}
+
+/// A single [[rom]] table in a database file
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RomEntry {
+    sha1: String,
+    title: Option<String>,
+    author: Option<String>,
+    platform: Option<String>,
+    quirks: Option<QuirkSetting>,
+    instructions_per_frame: Option<u32>,
+    #[serde(default)]
+    keys: Vec<String>,
+    #[serde(default)]
+    palette: Vec<String>,
+    filter: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DatabaseFile {
+    #[serde(default)]
+    rom: Vec<RomEntry>,
+}
+
+/// Rom metadata keyed by the SHA-1 of the rom's bytes
+#[derive(Clone, Debug, Default)]
+pub struct RomDatabase {
+    roms: HashMap<String, RomInfo>,
+}
+
+impl RomDatabase {
+    /// The database shipped with the emulator
+    pub fn builtin() -> &'static RomDatabase {
+        static DATABASE: OnceLock<RomDatabase> = OnceLock::new();
+        DATABASE.get_or_init(|| {
+            RomDatabase::parse(BUILTIN_DATABASE).expect("The builtin rom database is invalid")
+        })
+    }
+
+    /// Parse a database from TOML
+    pub fn parse(text: &str) -> Result<RomDatabase, Error> {
+        let file: DatabaseFile =
+            toml::from_str(text).map_err(|error| Error::new(ErrorKind::InvalidData, error))?;
+
+        let mut database = RomDatabase::default();
+        for entry in file.rom {
+            let sha1 = entry.sha1.to_ascii_lowercase();
+            let info = RomDatabase::parse_entry(entry).map_err(|error| {
+                Error::new(
+                    error.kind(),
+                    format!("Rom {} in the database: {}", sha1, error),
+                )
+            })?;
+            database.roms.insert(info.sha1.clone(), info);
+        }
+        Ok(database)
+    }
+
+    /// Check and convert the fields of a single [[rom]] table
+    fn parse_entry(entry: RomEntry) -> Result<RomInfo, Error> {
+        Ok(RomInfo {
+            sha1: entry.sha1.to_ascii_lowercase(),
+            title: entry.title,
+            author: entry.author,
+            platform: entry.platform.map(|p| p.parse()).transpose()?,
+            quirks: match entry.quirks {
+                Some(QuirkSetting::Preset(name)) => Some(name.parse()?),
+                Some(QuirkSetting::Custom(quirks)) => Some(quirks),
+                None => None,
+            },
+            instructions_per_frame: entry.instructions_per_frame,
+            keys: entry.keys,
+            palette: if entry.palette.is_empty() {
+                None
+            } else {
+                Some(Palette::from_hex(&entry.palette)?)
+            },
+            filter: entry.filter.map(|f| f.parse()).transpose()?,
+        })
+    }
+
+    /// Load a database from a TOML file
+    pub fn load_file<P: AsRef<Path>>(path: P) -> Result<RomDatabase, Error> {
+        RomDatabase::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Add every entry of another database, replacing entries for the same rom. Used to layer a
+    /// local override file on top of the builtin database.
+    pub fn merge(&mut self, other: RomDatabase) {
+        self.roms.extend(other.roms);
+    }
+
+    /// Find a rom by its SHA-1, given as a hex string
+    pub fn get(&self, sha1: &str) -> Option<&RomInfo> {
+        self.roms.get(&sha1.to_ascii_lowercase())
+    }
+
+    /// Find a rom by its contents
+    pub fn lookup(&self, rom: &[u8]) -> Option<&RomInfo> {
+        self.get(&sha1_hex(rom))
+    }
+
+    pub fn len(&self) -> usize {
+        self.roms.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.roms.is_empty()
+    }
+}
+
+/// Get the SHA-1 of some bytes as a lowercase hex string
+pub fn sha1_hex(bytes: &[u8]) -> String {
+    sha1_smol::Sha1::from(bytes).digest().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DATABASE: &str = r##"
+        [[rom]]
+        sha1 = "DA39A3EE5E6B4B0D3255BFEF95601890AFD80709"
+        title = "Empty"
+        author = "Nobody"
+        platform = "eti660"
+        quirks = "schip"
+        instructions_per_frame = 15
+        keys = ["5: fire"]
+        palette = ["#000000", "#FFFFFF"]
+        filter = "decay:4"
+
+        [[rom]]
+        sha1 = "0000000000000000000000000000000000000000"
+        quirks = { shift_uses_vy = true }
+    "##;
+
+    #[test]
+    fn sha1() {
+        assert_eq!("da39a3ee5e6b4b0d3255bfef95601890afd80709", sha1_hex(&[]));
+        assert_eq!("a9993e364706816aba3e25717850c26c9cd0d89d", sha1_hex(b"abc"));
+    }
+
+    #[test]
+    fn builtin_parses() {
+        RomDatabase::builtin();
+    }
+
+    #[test]
+    fn parse() {
+        let database = RomDatabase::parse(DATABASE).unwrap();
+        assert_eq!(2, database.len());
+
+        let info = database.lookup(&[]).unwrap();
+        assert_eq!(Some("Empty".to_string()), info.title);
+        assert_eq!(Some(Platform::Eti660), info.platform);
+        assert_eq!(Some("schip".parse().unwrap()), info.quirks);
+        assert_eq!(Some(15), info.instructions_per_frame);
+        assert_eq!(vec!["5: fire".to_string()], info.keys);
+        assert_eq!(
+            Some(Palette::new(vec![0x000000, 0xFFFFFF]).unwrap()),
+            info.palette
+        );
+        assert_eq!(Some(FilterMode::Decay(4)), info.filter);
+
+        let info = database
+            .get("0000000000000000000000000000000000000000")
+            .unwrap();
+        let quirks = info.quirks.unwrap();
+        assert!(quirks.shift_uses_vy);
+        assert!(!quirks.vf_reset);
+        assert_eq!(None, info.title);
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert!(RomDatabase::parse("[[rom]]\ntitle = \"no hash\"").is_err());
+        assert!(RomDatabase::parse("[[rom]]\nsha1 = \"00\"\nplatform = \"nes\"").is_err());
+        assert!(RomDatabase::parse("[[rom]]\nsha1 = \"00\"\nquirks = \"nes\"").is_err());
+        assert!(RomDatabase::parse("[[rom]]\nsha1 = \"00\"\nspeed = 1").is_err());
+        assert!(RomDatabase::parse("[[rom]]\nsha1 = \"00\"\nfilter = \"blur\"").is_err());
+
+        // Errors in an entry's fields name the entry
+        let error =
+            RomDatabase::parse("[[rom]]\nsha1 = \"AB\"\npalette = [\"#000000\"]").unwrap_err();
+        assert!(error.to_string().contains("Rom ab"), "{}", error);
+        let error = RomDatabase::parse("[[rom]]\nsha1 = \"ab\"\npalette = [\"red\", \"blue\"]")
+            .unwrap_err();
+        assert!(error.to_string().contains("Rom ab"), "{}", error);
+    }
+
+    #[test]
+    fn merge() {
+        let mut database = RomDatabase::parse(DATABASE).unwrap();
+        let overrides = RomDatabase::parse(
+            "[[rom]]\nsha1 = \"da39a3ee5e6b4b0d3255bfef95601890afd80709\"\ntitle = \"Mine\"",
+        )
+        .unwrap();
+        database.merge(overrides);
+
+        assert_eq!(2, database.len());
+        let info = database.lookup(&[]).unwrap();
+        assert_eq!(Some("Mine".to_string()), info.title);
+        assert_eq!(None, info.platform);
+    }
+}
@@ -105,13 +105,13 @@ mod tests {
     fn push_and_pop() {
         let mut empty: Stack<u8> = Stack::new(1);
         let result = empty.push(1);
-        assert!(!result.is_err());
+        assert!(result.is_ok());
 
         let result = empty.push(2);
         assert!(result.is_err());
 
         let result = empty.pop();
-        assert!(!result.is_err());
+        assert!(result.is_ok());
         assert_eq!(1, result.unwrap());
     }
 }
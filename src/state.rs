@@ -0,0 +1,205 @@
+use crate::stack::Stack;
+use crate::Chip;
+use std::convert::TryFrom;
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+use std::vec::Vec;
+
+/// Identifies a save state file
+const MAGIC: [u8; 4] = *b"C8ST";
+
+/// Bumped whenever the layout of a save state changes
+const VERSION: u8 = 1;
+
+/// Reads the fields of a save state in order
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if len > self.bytes.len() {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "Save state is truncated",
+            ));
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, Error> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+}
+
+impl Chip {
+    /// Snapshot memory, registers, the stack, timers and the screen. The configuration, keys and
+    /// rom database are not part of the state.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut state = Vec::new();
+        state.extend_from_slice(&MAGIC);
+        state.push(VERSION);
+
+        state.extend_from_slice(&(self.memory.as_slice().len() as u32).to_be_bytes());
+        state.extend_from_slice(self.memory.as_slice());
+        state.extend_from_slice(&self.registers);
+        state.extend_from_slice(&self.address.to_be_bytes());
+        state.extend_from_slice(&self.program_counter.to_be_bytes());
+
+        state.push(self.stack.data.len() as u8);
+        for address in self.stack.data.iter() {
+            state.extend_from_slice(&address.to_be_bytes());
+        }
+
+        state.push(self.delay_timer);
+        state.push(self.sound_timer);
+        let screen = self.screen_buffer.as_bytes();
+        state.extend_from_slice(&(screen.len() as u32).to_be_bytes());
+        state.extend_from_slice(screen);
+        state
+    }
+
+    /// Restore a snapshot taken by save_state. The chip must have the same memory size and
+    /// screen size the snapshot was taken with. The chip is unchanged if the state is invalid.
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), Error> {
+        let mut reader = Reader { bytes: state };
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(Error::new(ErrorKind::InvalidData, "Not a save state"));
+        }
+        let version = reader.u8()?;
+        if version != VERSION {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("Unsupported save state version {}", version),
+            ));
+        }
+
+        let memory_size = reader.u32()? as usize;
+        if memory_size != self.memory.as_slice().len() {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "Save state has {} bytes of memory, but the chip has {}",
+                    memory_size,
+                    self.memory.as_slice().len()
+                ),
+            ));
+        }
+        let memory = reader.take(memory_size)?;
+        let registers = reader.take(self.registers.len())?;
+        let address = reader.u16()?;
+        let program_counter = reader.u16()?;
+
+        let depth = usize::from(reader.u8()?);
+        let mut stack = Stack::new(self.stack.size);
+        for _ in 0..depth {
+            stack
+                .push(reader.u16()?)
+                .map_err(|_| Error::new(ErrorKind::InvalidData, "Save state stack is too deep"))?;
+        }
+
+        let delay_timer = reader.u8()?;
+        let sound_timer = reader.u8()?;
+        let screen_size = usize::try_from(reader.u32()?).unwrap_or(usize::MAX);
+        if screen_size != self.screen_buffer.as_bytes().len() {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "Save state was taken with a different screen size",
+            ));
+        }
+        let screen_buffer = reader.take(screen_size)?;
+
+        self.memory.load(0, memory)?;
+        self.registers.copy_from_slice(registers);
+        self.address = address;
+        self.program_counter = program_counter;
+        self.stack = stack;
+        self.delay_timer = delay_timer;
+        self.sound_timer = sound_timer;
+        self.screen_buffer
+            .as_bytes_mut()
+            .copy_from_slice(screen_buffer);
+        self.screen_changed = true;
+        Ok(())
+    }
+
+    /// Write a save state to a file
+    pub fn save_state_file<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        fs::write(path, self.save_state())
+    }
+
+    /// Restore a save state from a file
+    pub fn load_state_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Error> {
+        self.load_state(&fs::read(path)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{ChipConfig, Platform};
+    use crate::memory::Bus;
+    use crate::Chip;
+
+    #[test]
+    fn save_load_round_trip() {
+        let mut chip = Chip::default();
+        chip.load_rom_bytes(&[0x60, 0x2A, 0x22, 0x06, 0x00, 0x00, 0xA3, 0x21])
+            .unwrap();
+        for _ in 0..3 {
+            chip.tick().unwrap();
+        }
+        chip.screen_buffer.set_pixel(24, 0, true);
+        let state = chip.save_state();
+
+        let mut restored = Chip::default();
+        restored.load_state(&state).unwrap();
+        assert_eq!(0x2A, restored.registers[0]);
+        assert_eq!(0x321, restored.address);
+        assert_eq!(chip.program_counter, restored.program_counter);
+        assert_eq!(chip.stack, restored.stack);
+        assert_eq!(chip.screen_buffer, restored.screen_buffer);
+        assert_eq!(0x22, restored.memory.peek(0x202).unwrap());
+        assert_eq!(state, restored.save_state());
+    }
+
+    #[test]
+    fn load_invalid() {
+        let mut chip = Chip::default();
+        let state = chip.save_state();
+
+        assert!(chip.load_state(b"nope").is_err());
+        assert!(chip.load_state(&state[..state.len() - 1]).is_err());
+
+        let mut small = Chip::with_config(ChipConfig::from(Platform::CosmacVip2K));
+        assert!(small.load_state(&state).is_err());
+
+        chip.memory.write(0x300, 1).unwrap();
+        chip.load_state(&state).unwrap();
+        assert_eq!(0, chip.memory.peek(0x300).unwrap());
+    }
+
+    #[test]
+    fn save_load_file() {
+        let path = std::env::temp_dir().join("chip8_rs_state_file.c8s");
+        let mut chip = Chip::default();
+        chip.registers[5] = 5;
+        chip.save_state_file(&path).unwrap();
+
+        let mut restored = Chip::default();
+        restored.load_state_file(&path).unwrap();
+        assert_eq!(5, restored.registers[5]);
+    }
+}
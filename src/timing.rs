@@ -0,0 +1,221 @@
+use crate::instruction::Instruction;
+use crate::memory::Bus;
+use crate::Chip;
+use std::io::{Error, ErrorKind};
+use std::str::FromStr;
+
+/// Machine cycles in a 60Hz frame on the COSMAC VIP. The CDP1802 runs at 1.76MHz and takes 8
+/// clock cycles per machine cycle.
+pub const MACHINE_CYCLES_PER_FRAME: u32 = 3668;
+
+/// Machine cycles taken from the interpreter every frame by the 1861's display DMA, 8 bytes for
+/// each of the 128 lines shown
+pub(crate) const DISPLAY_CYCLES_PER_FRAME: u32 = 1024;
+
+/// Machine cycles the interpreter spends fetching and decoding every instruction
+const FETCH_CYCLES: u32 = 40;
+
+/// How the number of instructions run each frame is decided
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Timing {
+    // A fixed number of instructions every frame
+    #[default]
+    Fixed,
+
+    // As many instructions as fit in a frame on the COSMAC VIP, using each instruction's cost in
+    // machine cycles
+    Vip,
+}
+
+impl FromStr for Timing {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<Timing, Error> {
+        match name.to_ascii_lowercase().as_str() {
+            "fixed" => Ok(Timing::Fixed),
+            "vip" | "cosmac" => Ok(Timing::Vip),
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Unknown timing '{}', expected fixed or vip", name),
+            )),
+        }
+    }
+}
+
+impl Chip {
+    /// Choose how many instructions run_frame executes
+    pub fn set_timing(&mut self, timing: Timing) {
+        self.timing = timing;
+        self.cycle_debt = 0;
+    }
+
+    pub fn timing(&self) -> Timing {
+        self.timing
+    }
+
+    /// The machine cycles the next instruction takes on the COSMAC VIP, including the fetch.
+    /// The costs follow the routines of the VIP interpreter, so they're approximate for
+    /// instructions whose routines loop. The program counter wraps around the end of memory, as
+    /// it does when the instruction is fetched.
+    pub fn next_instruction_cycles(&self) -> u32 {
+        let address = usize::from(self.program_counter) % self.memory.len();
+        let next = (address + 1) % self.memory.len();
+        let opcode = match (self.memory.peek(address), self.memory.peek(next)) {
+            (Ok(high), Ok(low)) => u16::from_be_bytes([high, low]),
+            _ => return FETCH_CYCLES,
+        };
+        let register = |x: u8| u32::from(self.registers[usize::from(x)]);
+        FETCH_CYCLES
+            + match Instruction::decode(opcode) {
+                Some(Instruction::Cls) => 24 + 3054,
+                Some(Instruction::Ret) => 10,
+                Some(Instruction::Jp(_)) => 12,
+                Some(Instruction::Call(_)) => 26,
+                Some(Instruction::SeByte(..)) | Some(Instruction::SneByte(..)) => 10,
+                Some(Instruction::SeReg(..)) | Some(Instruction::SneReg(..)) => 14,
+                Some(Instruction::LdByte(..)) => 6,
+                Some(Instruction::AddByte(..)) => 10,
+                Some(Instruction::LdReg(..))
+                | Some(Instruction::Or(..))
+                | Some(Instruction::And(..))
+                | Some(Instruction::Xor(..))
+                | Some(Instruction::AddReg(..))
+                | Some(Instruction::Sub(..))
+                | Some(Instruction::Shr(..))
+                | Some(Instruction::Subn(..))
+                | Some(Instruction::Shl(..)) => 44,
+                Some(Instruction::LdI(_)) => 12,
+                Some(Instruction::JpV0(_)) => 22,
+                Some(Instruction::Rnd(..)) => 36,
+                Some(Instruction::Drw(x, _, height)) => sprite_cycles(register(x), height),
+                Some(Instruction::Skp(_)) | Some(Instruction::Sknp(_)) => 14,
+                Some(Instruction::LdVxDt(_))
+                | Some(Instruction::LdDtVx(_))
+                | Some(Instruction::LdStVx(_)) => 10,
+                Some(Instruction::LdVxK(_)) => 18,
+                Some(Instruction::AddI(_)) => 16,
+                Some(Instruction::LdF(_)) => 20,
+
+                // Each digit is found by repeated subtraction
+                Some(Instruction::LdB(x)) => {
+                    let value = register(x);
+                    80 + 16 * (value / 100 + value / 10 % 10 + value % 10)
+                }
+                Some(Instruction::LdIVx(x)) | Some(Instruction::LdVxI(x)) => {
+                    14 + 14 * (u32::from(x) + 1)
+                }
+                Some(Instruction::Sys(_)) | None => 0,
+            }
+    }
+
+    /// Run instructions until the frame's machine cycles are spent. A frame which overruns,
+    /// e.g. by clearing the screen, takes the extra cycles from the next frame. The timers are
+    /// counted down by the vertical blank run_frame signals first, not by each instruction.
+    pub(crate) fn run_vip_frame(&mut self) -> Result<(), Error> {
+        let budget = MACHINE_CYCLES_PER_FRAME - DISPLAY_CYCLES_PER_FRAME;
+        let mut spent = self.cycle_debt;
+        while spent < budget {
+            // The interpreter idles until the interrupt at the start of the next frame
+            if self.waiting_for_vblank() {
+                spent = budget;
+                break;
+            }
+            spent += self.next_instruction_cycles();
+            self.tick()?;
+        }
+        self.cycle_debt = spent - budget;
+        Ok(())
+    }
+}
+
+/// DXYN shifts each row of the sprite into place one bit at a time, and an unaligned sprite
+/// touches two bytes of the screen on every row
+fn sprite_cycles(x: u32, height: u8) -> u32 {
+    let shift = x % 8;
+    let row = if shift == 0 { 34 } else { 50 + 8 * shift };
+    26 + row * u32::from(height)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chip_with(rom: &[u8]) -> Chip {
+        let mut chip = Chip::default();
+        chip.load_rom_bytes(rom).unwrap();
+        chip.set_timing(Timing::Vip);
+        chip
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(Timing::Vip, "VIP".parse().unwrap());
+        assert_eq!(Timing::Fixed, "fixed".parse().unwrap());
+        assert!("fast".parse::<Timing>().is_err());
+    }
+
+    #[test]
+    fn sprite_cost_depends_on_alignment_and_height() {
+        let mut chip = chip_with(&[0xD0, 0x15]);
+        let aligned = chip.next_instruction_cycles();
+        chip.registers[0] = 3;
+        let unaligned = chip.next_instruction_cycles();
+        assert!(unaligned > aligned);
+
+        chip.memory.write(0x201, 0x1A).unwrap();
+        assert!(chip.next_instruction_cycles() > unaligned);
+    }
+
+    #[test]
+    fn cost_wraps_around_memory() {
+        // DXY0 with the font's first byte, F0, as the low byte
+        let mut chip = chip_with(&[0xD0, 0xF0]);
+        let expected = chip.next_instruction_cycles();
+        assert!(expected > FETCH_CYCLES);
+
+        chip.memory.load(0xFFF, &[0xD0]).unwrap();
+        chip.program_counter = 0xFFF;
+        assert_eq!(expected, chip.next_instruction_cycles());
+        chip.memory.load(0, &[0xD0, 0xF0]).unwrap();
+        chip.program_counter = 0x1000;
+        assert_eq!(expected, chip.next_instruction_cycles());
+    }
+
+    #[test]
+    fn frame_runs_by_cycles() {
+        // Count up in I forever
+        let mut chip = chip_with(&[0x60, 0x01, 0xF0, 0x1E, 0x12, 0x02]);
+        chip.run_frame(1).unwrap();
+        let budget = MACHINE_CYCLES_PER_FRAME - DISPLAY_CYCLES_PER_FRAME;
+        let per_frame = (budget - (40 + 6)) / ((40 + 16) + (40 + 12));
+        let count = u32::from(chip.address);
+        assert!(count == per_frame || count == per_frame + 1, "{}", count);
+    }
+
+    #[test]
+    fn timers_count_down_once_per_frame() {
+        // Set both timers to 10 while running the first frame, then loop forever
+        let mut chip = chip_with(&[0x60, 0x0A, 0xF0, 0x15, 0xF0, 0x18, 0x12, 0x06]);
+        chip.run_frame(1).unwrap();
+        assert!(chip.instruction_count() > 10);
+        assert_eq!(10, chip.delay_timer);
+        assert_eq!(10, chip.sound_timer);
+
+        chip.run_frame(1).unwrap();
+        assert_eq!(9, chip.delay_timer);
+        assert_eq!(9, chip.sound_timer);
+    }
+
+    #[test]
+    fn overrun_delays_the_next_frame() {
+        // Clearing the screen takes longer than a frame
+        let mut chip = chip_with(&[0x00, 0xE0, 0x12, 0x02]);
+        let cost = chip.next_instruction_cycles();
+        let budget = MACHINE_CYCLES_PER_FRAME - DISPLAY_CYCLES_PER_FRAME;
+        assert!(cost > budget);
+
+        chip.run_frame(1).unwrap();
+        assert_eq!(0x202, chip.program_counter);
+        assert_eq!(cost - budget, chip.cycle_debt);
+    }
+}
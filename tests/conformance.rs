@@ -0,0 +1,279 @@
+//! Runs test roms headless and compares the final screen with a golden image.
+//!
+//! The conformance suite is the community test suite
+//! (https://github.com/Timendus/chip8-test-suite): the chip-8 logo, IBM logo, corax+, flags,
+//! quirks and keypad roms. They're read from the directory named by CHIP8_TEST_ROMS and their
+//! screens are compared with tests/golden/reference, which holds images captured from a
+//! reference emulator, never from this one, so UPDATE_GOLDEN doesn't write them. When
+//! CHIP8_TEST_ROMS isn't set each of these tests prints that it was skipped and passes. A
+//! missing rom or reference image fails the test.
+//!
+//! The roms in tests/roms are extra checks, assembled with the builtin assembler. Each checks
+//! its own cases against values written into it, drawing a tick or a cross for every case, and
+//! stores the number of crosses in its last byte, which must be 0. Their screens are also
+//! compared with tests/golden, to catch drawing changes. Run with UPDATE_GOLDEN=1 to write those
+//! images from the current output, then check every case shows a tick before committing them.
+
+use chip8_rs::config::Quirks;
+use chip8_rs::{asm, Chip, Key, KeyState};
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+/// The environment variable naming the directory of community test roms
+const TEST_ROMS_VAR: &str = "CHIP8_TEST_ROMS";
+
+/// Instructions run per frame. Every sprite takes a frame with the display_wait quirk.
+const INSTRUCTIONS_PER_FRAME: u32 = 20;
+
+/// The keys which choose each platform from the menu of the community quirks rom, and which
+/// the assembled roms wait for to choose their expected values
+const PLATFORMS: [(&str, &[usize]); 3] = [("chip8", &[0x1]), ("schip", &[0x2]), ("xochip", &[0x3])];
+
+/// Where a test rom comes from
+#[derive(Clone, Copy)]
+enum Source {
+    // Assembly in tests/roms, without the extension
+    Asm(&'static str),
+
+    // A rom file in the CHIP8_TEST_ROMS directory
+    Rom(&'static str),
+}
+
+/// A test rom run with one set of quirks
+struct Case {
+    source: Source,
+    quirks: &'static str,
+    frames: u32,
+
+    // Keys held down for the whole run
+    keys: &'static [usize],
+}
+
+impl Case {
+    fn new(rom: &'static str, quirks: &'static str) -> Case {
+        Case {
+            source: Source::Asm(rom),
+            quirks,
+            frames: 120,
+            keys: &[],
+        }
+    }
+
+    /// A community test rom, which takes longer to finish than the assembled roms
+    fn rom(file: &'static str, quirks: &'static str) -> Case {
+        Case {
+            source: Source::Rom(file),
+            frames: 600,
+            ..Case::new(file, quirks)
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self.source {
+            Source::Asm(name) => name,
+            Source::Rom(file) => file.trim_end_matches(".ch8"),
+        }
+    }
+
+    fn golden_path(&self) -> PathBuf {
+        let dir = match self.source {
+            Source::Asm(_) => test_dir().join("golden"),
+            Source::Rom(_) => test_dir().join("golden").join("reference"),
+        };
+        dir.join(format!("{}-{}.txt", self.name(), self.quirks))
+    }
+
+    /// Assemble or read the rom, or None if the community roms aren't available
+    fn load(&self) -> Option<Vec<u8>> {
+        match self.source {
+            Source::Asm(name) => {
+                let path = test_dir().join("roms").join(format!("{}.asm", name));
+                let source = fs::read_to_string(&path).unwrap();
+                let rom = asm::assemble(&source, 0x200)
+                    .unwrap_or_else(|error| panic!("{}: {}", path.display(), error));
+                Some(rom)
+            }
+            Source::Rom(file) => {
+                let dir = match env::var_os(TEST_ROMS_VAR) {
+                    Some(dir) => PathBuf::from(dir),
+                    None => {
+                        // Written past the test harness's capture, so the skip shows in the
+                        // output of a passing run
+                        let _ = writeln!(
+                            io::stderr(),
+                            "skipped {} with {}: set {} to a directory of the community test roms",
+                            file,
+                            self.quirks,
+                            TEST_ROMS_VAR
+                        );
+                        return None;
+                    }
+                };
+                let path = dir.join(file);
+                let rom =
+                    fs::read(&path).unwrap_or_else(|error| panic!("{}: {}", path.display(), error));
+                Some(rom)
+            }
+        }
+    }
+
+    /// Run the rom, returning the chip at the end
+    fn run(&self, rom: &[u8]) -> Chip {
+        let mut chip = Chip::default();
+        chip.set_quirks(self.quirks.parse::<Quirks>().unwrap());
+        chip.load_rom_bytes(rom).unwrap();
+        chip.update_keys(
+            (0..16)
+                .map(|key| Key {
+                    state: if self.keys.contains(&key) {
+                        KeyState::Pressed
+                    } else {
+                        KeyState::NotPressed
+                    },
+                })
+                .collect(),
+        );
+        for _ in 0..self.frames {
+            chip.run_frame(INSTRUCTIONS_PER_FRAME).unwrap();
+        }
+        chip
+    }
+
+    /// Compare the screen with the golden image, or write it with UPDATE_GOLDEN=1
+    fn check(&self) {
+        let rom = match self.load() {
+            Some(rom) => rom,
+            None => return,
+        };
+        let chip = self.run(&rom);
+        let screen = chip.screen_buffer.to_text();
+        if let Source::Asm(_) = self.source {
+            // The last byte of the rom counts the failed cases, and is 0xFF until it finishes
+            let result = chip.memory().peek(0x200 + rom.len() - 1).unwrap();
+            match result {
+                0 => {}
+                0xFF => panic!("{} didn't finish, the screen was:\n{}", self.name(), screen),
+                failed => panic!(
+                    "{} failed {} cases with {}, see the crosses:\n{}",
+                    self.name(),
+                    failed,
+                    self.quirks,
+                    screen
+                ),
+            }
+        }
+
+        let golden = self.golden_path();
+        if let Source::Asm(_) = self.source {
+            if env::var_os("UPDATE_GOLDEN").is_some() {
+                fs::write(&golden, &screen).unwrap();
+                return;
+            }
+        }
+
+        let expected = fs::read_to_string(&golden).unwrap_or_else(|error| {
+            panic!(
+                "{}: {}, the screen was:\n{}",
+                golden.display(),
+                error,
+                screen
+            )
+        });
+        if screen != expected {
+            let rows: Vec<String> = expected
+                .lines()
+                .zip(screen.lines())
+                .map(|(expected, actual)| format!("{}  {}", expected, actual))
+                .collect();
+            panic!(
+                "{} doesn't match {}, expected then actual:\n{}",
+                self.name(),
+                golden.display(),
+                rows.join("\n")
+            );
+        }
+    }
+}
+
+fn test_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests")
+}
+
+#[test]
+fn logo() {
+    Case::new("logo", "chip8").check();
+}
+
+#[test]
+fn flags() {
+    for (quirks, keys) in PLATFORMS {
+        Case {
+            keys,
+            ..Case::new("flags", quirks)
+        }
+        .check();
+    }
+}
+
+#[test]
+fn quirks() {
+    for (quirks, keys) in PLATFORMS {
+        Case {
+            keys,
+            ..Case::new("quirks", quirks)
+        }
+        .check();
+    }
+}
+
+#[test]
+fn keypad() {
+    Case {
+        keys: &[0xA],
+        ..Case::new("keypad", "chip8")
+    }
+    .check();
+}
+
+#[test]
+fn chip8_logo_rom() {
+    Case::rom("1-chip8-logo.ch8", "chip8").check();
+}
+
+#[test]
+fn ibm_logo_rom() {
+    Case::rom("2-ibm-logo.ch8", "chip8").check();
+}
+
+#[test]
+fn corax_rom() {
+    Case::rom("3-corax+.ch8", "chip8").check();
+}
+
+#[test]
+fn flags_rom() {
+    Case::rom("4-flags.ch8", "chip8").check();
+}
+
+#[test]
+fn quirks_rom() {
+    for (quirks, keys) in PLATFORMS {
+        Case {
+            keys,
+            ..Case::rom("5-quirks.ch8", quirks)
+        }
+        .check();
+    }
+}
+
+#[test]
+fn keypad_rom() {
+    // 1 picks the EX9E test from the menu, then shows the held keys
+    Case {
+        keys: &[0x1],
+        ..Case::rom("6-keypad.ch8", "chip8")
+    }
+    .check();
+}
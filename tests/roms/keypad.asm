@@ -0,0 +1,73 @@
+; Checks the key instructions while the harness holds key A. The cases, in order:
+; 1 LD VX, K waits for a key and loads A, 2 SKP skips for A, 3 SKNP skips for 5, which isn't
+; held, 4 SKP doesn't skip for 5, 5 SKNP doesn't skip for A
+        CLS
+        LD VA, 0
+        LD VB, 0
+        LD VD, 1
+        LD VE, 0
+        LD V5, 0
+        LD V6, 0
+
+        LD V8, K
+        LD V3, V8
+        LD V4, 0xA
+        CALL check
+
+        LD V3, 1
+        SKP V8
+        LD V3, 0
+        LD V4, 1
+        CALL check
+
+        LD V9, 5
+        LD V3, 1
+        SKNP V9
+        LD V3, 0
+        LD V4, 1
+        CALL check
+
+        LD V3, 1
+        SKP V9
+        LD V3, 0
+        LD V4, 0
+        CALL check
+
+        LD V3, 1
+        SKNP V8
+        LD V3, 0
+        LD V4, 0
+        CALL check
+        JP finish
+
+; Draw the case number in VD at VA, VB, then a tick if V3 = V4 and V5 = V6 or a cross if not,
+; counting the crosses in VE. Cells are 12 pixels wide, five to a row.
+check:  LD F, VD
+        DRW VA, VB, 5
+        ADD VA, 5
+        SE V3, V4
+        JP failed
+        SE V5, V6
+        JP failed
+        LD I, tick
+        JP mark
+failed: LD I, cross
+        ADD VE, 1
+mark:   DRW VA, VB, 5
+        ADD VA, 7
+        ADD VD, 1
+        SE VA, 60
+        RET
+        LD VA, 0
+        ADD VB, 7
+        RET
+
+; Store the number of crosses in the last byte of the rom, where the harness reads it
+finish: LD I, result
+        LD V0, VE
+        LD [I], V0
+end:    JP end
+
+tick:   DB 0x08, 0x10, 0xA0, 0x40, 0x00
+cross:  DB 0x88, 0x50, 0x20, 0x50, 0x88
+result: DB 0xFF
@@ -0,0 +1,128 @@
+; Draws "CHIP" in large letters under a bar, like the IBM logo rom, and checks the sprites
+; collide as they should. The cases, in order:
+; 1 A sprite drawn again after CLS doesn't collide
+; 2 None of the sprites of the logo collide, as they don't overlap
+; 3 Drawing a letter over itself collides and erases it, and drawing it once more doesn't
+        CLS
+        LD VA, 0
+        LD VB, 27
+        LD VD, 1
+        LD VE, 0
+        LD V5, 0
+        LD V6, 0
+        LD V8, 0
+
+        LD V0, 0
+        LD V1, 0
+        LD I, bar
+        DRW V0, V1, 2
+        CLS
+        DRW V0, V1, 2
+        LD V3, VF
+        DRW V0, V1, 2
+        LD V4, 0
+        CALL check
+
+        LD V0, 4
+        LD V1, 4
+        LD I, bar
+        DRW V0, V1, 2
+        SE VF, 0
+        LD V8, 1
+        LD V0, 12
+        DRW V0, V1, 2
+        SE VF, 0
+        LD V8, 1
+        LD V0, 20
+        DRW V0, V1, 2
+        SE VF, 0
+        LD V8, 1
+        LD V0, 28
+        DRW V0, V1, 2
+        SE VF, 0
+        LD V8, 1
+        LD V0, 36
+        DRW V0, V1, 2
+        SE VF, 0
+        LD V8, 1
+        LD V0, 44
+        DRW V0, V1, 2
+        SE VF, 0
+        LD V8, 1
+        LD V0, 52
+        DRW V0, V1, 2
+        SE VF, 0
+        LD V8, 1
+
+        LD V1, 10
+        LD V0, 8
+        LD I, letter_c
+        DRW V0, V1, 15
+        SE VF, 0
+        LD V8, 1
+        LD V0, 20
+        LD I, letter_h
+        DRW V0, V1, 15
+        SE VF, 0
+        LD V8, 1
+        LD V0, 32
+        LD I, letter_i
+        DRW V0, V1, 15
+        SE VF, 0
+        LD V8, 1
+        LD V0, 44
+        LD I, letter_p
+        DRW V0, V1, 15
+        SE VF, 0
+        LD V8, 1
+        LD V3, V8
+        LD V4, 0
+        CALL check
+
+        LD V0, 8
+        LD I, letter_c
+        DRW V0, V1, 15
+        LD V3, VF
+        DRW V0, V1, 15
+        LD V5, VF
+        LD V4, 1
+        LD V6, 0
+        CALL check
+        JP finish
+
+; Draw the case number in VD at VA, VB, then a tick if V3 = V4 and V5 = V6 or a cross if not,
+; counting the crosses in VE. Cells are 12 pixels wide, five to a row.
+check:  LD F, VD
+        DRW VA, VB, 5
+        ADD VA, 5
+        SE V3, V4
+        JP failed
+        SE V5, V6
+        JP failed
+        LD I, tick
+        JP mark
+failed: LD I, cross
+        ADD VE, 1
+mark:   DRW VA, VB, 5
+        ADD VA, 7
+        ADD VD, 1
+        SE VA, 60
+        RET
+        LD VA, 0
+        ADD VB, 7
+        RET
+
+; Store the number of crosses in the last byte of the rom, where the harness reads it
+finish: LD I, result
+        LD V0, VE
+        LD [I], V0
+end:    JP end
+
+tick:   DB 0x08, 0x10, 0xA0, 0x40, 0x00
+cross:  DB 0x88, 0x50, 0x20, 0x50, 0x88
+bar:      DB 0xFF, 0xFF
+letter_c: DB 0x3C, 0x7E, 0xE7, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xE7, 0x7E, 0x3C
+letter_h: DB 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3
+letter_i: DB 0xFF, 0xFF, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF
+letter_p: DB 0xFC, 0xFE, 0xC7, 0xC3, 0xC3, 0xC7, 0xFE, 0xFC, 0xC0, 0xC0, 0xC0, 0xC0, 0xC0, 0xC0, 0xC0
+result:   DB 0xFF
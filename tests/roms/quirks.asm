@@ -0,0 +1,105 @@
+; Checks the quirks which differ between platforms. Hold 1, 2 or 3 to choose chip-8,
+; SUPER-CHIP or XO-CHIP. The cases, in order:
+; 1 VF after OR, reset to 0 with vf_reset
+; 2 The first byte loaded after two one byte stores to I, 7 with memory_increments_i as the
+;   second store goes to the next byte, 9 without
+; 3 Which entry of a jump table BNNN lands on, 0 using V0 and 1 with jump_uses_vx, which
+;   uses V2 as the table is at 0x2NN
+; 4 Whether a sprite drawn over the right edge wraps around to hit a pixel at the left edge,
+;   which it doesn't with clip_sprites
+        CLS
+        LD V7, K
+        LD VA, 0
+        LD VB, 0
+        LD VD, 1
+        LD VE, 0
+        LD V5, 0
+        LD V6, 0
+
+        LD VF, 1
+        OR V8, V8
+        LD V3, VF
+        LD V4, 1
+        SNE V7, 1
+        LD V4, 0
+        CALL check
+
+        LD I, scratch
+        LD V0, 7
+        LD [I], V0
+        LD V0, 9
+        LD [I], V0
+        LD I, scratch
+        LD V0, [I]
+        LD V3, V0
+        LD V4, 7
+        SNE V7, 2
+        LD V4, 9
+        CALL check
+
+        LD V0, 0
+        LD V2, 4
+        JP V0, jump_table
+jumped: LD V4, 0
+        SNE V7, 2
+        LD V4, 1
+        CALL check
+
+        LD V0, 60
+        LD V1, 24
+        LD I, block
+        DRW V0, V1, 8
+        LD V0, 0
+        LD I, dot
+        DRW V0, V1, 1
+        LD V3, VF
+        DRW V0, V1, 1
+        LD V0, 60
+        LD I, block
+        DRW V0, V1, 8
+        LD V4, 0
+        SNE V7, 3
+        LD V4, 1
+        CALL check
+        JP finish
+
+jump_table:
+        LD V3, 0
+        JP jumped
+        LD V3, 1
+        JP jumped
+
+; Draw the case number in VD at VA, VB, then a tick if V3 = V4 and V5 = V6 or a cross if not,
+; counting the crosses in VE. Cells are 12 pixels wide, five to a row.
+check:  LD F, VD
+        DRW VA, VB, 5
+        ADD VA, 5
+        SE V3, V4
+        JP failed
+        SE V5, V6
+        JP failed
+        LD I, tick
+        JP mark
+failed: LD I, cross
+        ADD VE, 1
+mark:   DRW VA, VB, 5
+        ADD VA, 7
+        ADD VD, 1
+        SE VA, 60
+        RET
+        LD VA, 0
+        ADD VB, 7
+        RET
+
+; Store the number of crosses in the last byte of the rom, where the harness reads it
+finish: LD I, result
+        LD V0, VE
+        LD [I], V0
+end:    JP end
+
+tick:   DB 0x08, 0x10, 0xA0, 0x40, 0x00
+cross:  DB 0x88, 0x50, 0x20, 0x50, 0x88
+block:   DB 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF
+dot:     DB 0x80
+scratch: DB 0, 0
+result:  DB 0xFF